@@ -13,6 +13,7 @@ pub mod nud;
 pub mod nut;
 pub mod omo;
 pub mod pack;
+pub mod param;
 pub mod sb;
 pub mod vbn;
 
@@ -239,7 +240,8 @@ file_read_impl!(
     pack::Pack,
     omo::Omo,
     mta::Mta,
-    jtb::Jtb
+    jtb::Jtb,
+    param::Param
 );
 
 file_read_impl!(Endian::Little, nhb::Nhb, sb::Sb);
@@ -262,7 +264,7 @@ macro_rules! file_write_impl {
     };
 }
 
-file_write_impl!(binrw::Endian::Big, nsh::Nsh, vbn::Vbn);
+file_write_impl!(binrw::Endian::Big, nsh::Nsh, vbn::Vbn, param::Param);
 
 macro_rules! arbitrary_bilge_impl {
     ($value_type:ty, $($type:ty),*) => {
@@ -200,6 +200,17 @@ pub struct TextureHeader {
     pub caps2: Caps2,
 }
 
+impl TextureHeader {
+    /// The number of array layers with 6 layers for cube maps.
+    pub fn layers(&self) -> u32 {
+        if self.caps2 == Caps2::CUBEMAP | Caps2::CUBEMAP_ALLFACES {
+            6
+        } else {
+            1
+        }
+    }
+}
+
 // Identical to flags used for DDS.
 // https://github.com/SiegeEngine/ddsfile/blob/3126d7694e42f7b6c84a19d550c5b61aeb8b5869/src/header.rs#L364-L391
 bitflags! {
@@ -562,9 +573,13 @@ impl Ntp3TextureV1 {
         })
     }
 
+    /// The untiled image data for all layers and mipmaps without alignment padding.
+    pub fn image_data(&self) -> Vec<u8> {
+        ntp3_image_data(&self.header, &self.unk_sizes, &self.data)
+    }
+
     pub fn to_surface(&self) -> Result<Surface<Vec<u8>>, CreateSurfaceError> {
-        let data = ntp3_image_data(&self.header, &self.unk_sizes, &self.data);
-        create_surface(&self.header, data)
+        create_surface(&self.header, self.image_data())
     }
 
     pub fn to_dds(&self) -> Result<Dds, image_dds::CreateDdsError> {
@@ -624,9 +639,13 @@ impl Ntp3TextureV2 {
         })
     }
 
+    /// The untiled image data for all layers and mipmaps without alignment padding.
+    pub fn image_data(&self) -> Vec<u8> {
+        ntp3_image_data(&self.header, &self.unk_sizes, &self.data)
+    }
+
     pub fn to_surface(&self) -> Result<Surface<Vec<u8>>, CreateSurfaceError> {
-        let data = ntp3_image_data(&self.header, &self.unk_sizes, &self.data);
-        create_surface(&self.header, data)
+        create_surface(&self.header, self.image_data())
     }
 
     pub fn to_dds(&self) -> Result<Dds, image_dds::CreateDdsError> {
@@ -706,11 +725,7 @@ fn create_surface(
         width: header.width as u32,
         height: header.height as u32,
         depth: 1,
-        layers: if header.caps2 == Caps2::CUBEMAP | Caps2::CUBEMAP_ALLFACES {
-            6
-        } else {
-            1
-        },
+        layers: header.layers(),
         mipmaps: header.mipmap_count as u32,
         image_format: header.format.try_into()?,
         data: image_data,
@@ -806,7 +821,8 @@ impl TryFrom<image_dds::ImageFormat> for NutFormat {
     }
 }
 
-fn swap_red_blue_bgr5a1(data: &mut [u8]) {
+/// Swap the red and blue channels for 16-bit RGB5A1 and BGR5A1 data.
+pub fn swap_red_blue_bgr5a1(data: &mut [u8]) {
     // TODO: Move this logic to image_dds?
     data.chunks_exact_mut(2).for_each(|c| {
         // Most significant bit -> GGGBBBBBARRRRRGG -> least significant bit.
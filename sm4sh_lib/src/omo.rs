@@ -61,6 +61,7 @@ pub struct OmoNode {
 #[br(try_map = |x: u32| x.try_into().map_err(|e| format!("{e:?}")))]
 #[bw(map = |&x| u32::from(x))]
 pub struct OmoFlags {
+    // TODO: unk1 and unk4 are not decoded yet and sm4sh_test reports any nonzero values.
     pub unk1: u4,
     pub scale_type: ScaleType,
     pub rotation_type: RotationType,
@@ -86,13 +87,15 @@ pub enum RotationType {
     Interpolate = 0x5,
     FConst = 0x6,
     Constant = 0x7,
-    Frame = 0xA, // TODO: "keys"?
+    /// Raw quaternion values stored in [Frame::keys].
+    Frame = 0xA,
 }
 
 #[bitsize(8)]
 #[derive(TryFromBits, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum PositionType {
-    Frame = 0x04, // TODO: "keys"?
+    /// Raw translation values stored in [Frame::keys].
+    Frame = 0x04,
     Interpolate = 0x08,
     Constant = 0x20,
 }
@@ -0,0 +1,302 @@
+//! Generic parameter files like `light_set_param.bin` in stage `param` folders.
+//!
+//! Values are stored as a flat list of typed values.
+//! Groups split the values until the next group into entries with the same number of values.
+//! Reading fails if a group's values cannot be split evenly into its entries.
+use std::io::Seek;
+
+use binrw::{BinRead, BinResult, BinWrite};
+
+#[derive(Debug, BinRead, BinWrite, PartialEq, Clone)]
+#[brw(magic(b"\xFF\xFF\x00\x00"))]
+pub struct Param {
+    /// The values split into groups.
+    ///
+    /// The first group always contains the values before the first [ParamValue::Group]
+    /// as a single entry, even if there are no values.
+    /// This keeps the group indices the same for files with and without leading values.
+    #[br(parse_with = parse_groups)]
+    #[bw(write_with = write_groups)]
+    pub groups: Vec<ParamGroup>,
+}
+
+#[derive(Debug, BinRead, BinWrite, PartialEq, Clone)]
+pub enum ParamValue {
+    #[brw(magic(1u8))]
+    U8(u8),
+
+    #[brw(magic(2u8))]
+    I8(i8),
+
+    #[brw(magic(3u8))]
+    U16(u16),
+
+    #[brw(magic(4u8))]
+    I16(i16),
+
+    #[brw(magic(5u8))]
+    U32(u32),
+
+    #[brw(magic(6u8))]
+    I32(i32),
+
+    #[brw(magic(7u8))]
+    F32(f32),
+
+    /// A string including any null terminators from the file.
+    #[brw(magic(8u8))]
+    String(
+        #[br(parse_with = parse_string)]
+        #[bw(write_with = write_string)]
+        String,
+    ),
+
+    /// The start of a group with the given number of entries.
+    #[brw(magic(0x20u8))]
+    Group(u32),
+}
+
+/// The values for a [ParamValue::Group] split into entries.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParamGroup {
+    pub entries: Vec<Vec<ParamValue>>,
+}
+
+impl Param {
+    /// The value at `value` in entry `entry` of group `group`.
+    pub fn value(&self, group: usize, entry: usize, value: usize) -> Option<&ParamValue> {
+        self.groups.get(group)?.value(entry, value)
+    }
+}
+
+impl ParamGroup {
+    /// The value at `value` in entry `entry`.
+    pub fn value(&self, entry: usize, value: usize) -> Option<&ParamValue> {
+        self.entries.get(entry)?.get(value)
+    }
+}
+
+impl ParamValue {
+    /// The value converted to a float for any numeric value.
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            ParamValue::U8(v) => Some(*v as f32),
+            ParamValue::I8(v) => Some(*v as f32),
+            ParamValue::U16(v) => Some(*v as f32),
+            ParamValue::I16(v) => Some(*v as f32),
+            ParamValue::U32(v) => Some(*v as f32),
+            ParamValue::I32(v) => Some(*v as f32),
+            ParamValue::F32(v) => Some(*v),
+            ParamValue::String(_) | ParamValue::Group(_) => None,
+        }
+    }
+}
+
+fn parse_groups<R: std::io::Read + std::io::Seek>(
+    reader: &mut R,
+    endian: binrw::Endian,
+    _args: (),
+) -> BinResult<Vec<ParamGroup>> {
+    let mut groups = Vec::new();
+
+    // Values before the first group have a single entry.
+    let mut pos = reader.stream_position()?;
+    let mut count = 1;
+    let mut values = Vec::new();
+    loop {
+        let value_pos = reader.stream_position()?;
+        match ParamValue::read_options(reader, endian, ()) {
+            Ok(ParamValue::Group(entry_count)) => {
+                groups.push(param_group(std::mem::take(&mut values), count, pos)?);
+                pos = value_pos;
+                count = entry_count as usize;
+            }
+            Ok(value) => values.push(value),
+            Err(e) if e.is_eof() => break,
+            Err(e) => return Err(e),
+        }
+    }
+    groups.push(param_group(values, count, pos)?);
+
+    Ok(groups)
+}
+
+#[binrw::writer(writer, endian)]
+fn write_groups(groups: &Vec<ParamGroup>) -> BinResult<()> {
+    for (i, group) in groups.iter().enumerate() {
+        // The first group is the values before the first group marker.
+        if i > 0 {
+            ParamValue::Group(group.entries.len() as u32).write_options(writer, endian, ())?;
+        }
+        for value in group.entries.iter().flatten() {
+            value.write_options(writer, endian, ())?;
+        }
+    }
+    Ok(())
+}
+
+fn param_group(values: Vec<ParamValue>, count: usize, pos: u64) -> BinResult<ParamGroup> {
+    if count == 0 {
+        if values.is_empty() {
+            return Ok(ParamGroup {
+                entries: Vec::new(),
+            });
+        }
+    } else if values.len().is_multiple_of(count) {
+        let entry_size = values.len() / count;
+        let mut values = values.into_iter();
+        let entries = (0..count)
+            .map(|_| values.by_ref().take(entry_size).collect())
+            .collect();
+        return Ok(ParamGroup { entries });
+    }
+
+    Err(binrw::Error::AssertFail {
+        pos,
+        message: format!(
+            "{} values cannot be split evenly into {count} entries",
+            values.len()
+        ),
+    })
+}
+
+fn parse_string<R: std::io::Read + std::io::Seek>(
+    reader: &mut R,
+    endian: binrw::Endian,
+    _args: (),
+) -> BinResult<String> {
+    let len = u32::read_options(reader, endian, ())?;
+    let bytes = Vec::<u8>::read_options(
+        reader,
+        endian,
+        binrw::VecArgs {
+            count: len as usize,
+            inner: (),
+        },
+    )?;
+    Ok(String::from_utf8_lossy(&bytes).to_string())
+}
+
+#[binrw::writer(writer, endian)]
+fn write_string(value: &String) -> BinResult<()> {
+    (value.len() as u32).write_options(writer, endian, ())?;
+    value.as_bytes().write_options(writer, endian, ())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hexlit::hex;
+
+    #[test]
+    fn read_param_groups() {
+        let param = Param::from_bytes(hex!(
+            // magic
+            ffff0000
+            // ungrouped values
+            05 00000001
+            08 00000003 616263
+            // group with 2 entries
+            20 00000002
+            07 3f800000 01 02
+            07 40000000 01 03
+        ))
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                ParamGroup {
+                    entries: vec![vec![
+                        ParamValue::U32(1),
+                        ParamValue::String("abc".to_string())
+                    ]]
+                },
+                ParamGroup {
+                    entries: vec![
+                        vec![ParamValue::F32(1.0), ParamValue::U8(2)],
+                        vec![ParamValue::F32(2.0), ParamValue::U8(3)]
+                    ]
+                }
+            ],
+            param.groups
+        );
+        assert_eq!(Some(&ParamValue::U8(3)), param.value(1, 1, 1));
+        assert_eq!(Some(&ParamValue::F32(1.0)), param.groups[1].value(0, 0));
+        assert_eq!(None, param.value(2, 0, 0));
+
+        let mut writer = std::io::Cursor::new(Vec::new());
+        param.write(&mut writer).unwrap();
+        assert_eq!(Param::from_bytes(writer.into_inner()).unwrap(), param);
+    }
+
+    #[test]
+    fn read_param_groups_empty() {
+        let param = Param::from_bytes(hex!(
+            // magic
+            ffff0000
+            // empty groups
+            20 00000000
+            20 00000002
+        ))
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                ParamGroup {
+                    entries: vec![Vec::new()]
+                },
+                ParamGroup {
+                    entries: Vec::new()
+                },
+                ParamGroup {
+                    entries: vec![Vec::new(), Vec::new()]
+                }
+            ],
+            param.groups
+        );
+    }
+
+    #[test]
+    fn write_param_groups() {
+        let bytes = hex!(
+            // magic
+            ffff0000
+            // group with 1 entry
+            20 00000001
+            08 00000004 61626300
+            // group with 2 entries
+            20 00000002
+            01 01
+            01 02
+        );
+        let param = Param::from_bytes(bytes).unwrap();
+        assert_eq!(
+            Some(&ParamValue::String("abc\0".to_string())),
+            param.value(1, 0, 0)
+        );
+
+        let mut writer = std::io::Cursor::new(Vec::new());
+        param.write(&mut writer).unwrap();
+        assert_eq!(&bytes[..], &writer.into_inner()[..]);
+    }
+
+    #[test]
+    fn read_param_groups_uneven() {
+        let result = Param::from_bytes(hex!(
+            // magic
+            ffff0000
+            // group with 2 entries and 3 values
+            20 00000002
+            01 01
+            01 02
+            01 03
+        ));
+
+        assert!(matches!(
+            result,
+            Err(binrw::Error::AssertFail { pos: 4, .. })
+        ));
+    }
+}
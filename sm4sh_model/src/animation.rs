@@ -1,17 +1,26 @@
 use std::{
     collections::{BTreeMap, BTreeSet},
     io::Cursor,
+    ops::Range,
     path::Path,
 };
 
+use bilge::prelude::Number;
 use binrw::{BinReaderExt, BinResult};
 use glam::{EulerRot, Mat4, Quat, Vec3, vec3};
+use log::warn;
 use sm4sh_lib::{
     omo::{Omo, OmoNode, PositionType, RotationType, ScaleType},
     pack::Pack,
 };
 
-use crate::VbnSkeleton;
+use crate::{VbnBone, VbnSkeleton};
+
+mod keyframes;
+pub use keyframes::{
+    BezierHandles, FCurveKeyframes, Keyframe, KeyframeInterpolation, KeyframeTolerance,
+    reduce_keyframes,
+};
 
 /// Load animations from a `path` like `"main.pac"`.
 pub fn load_animations<P: AsRef<Path>>(path: P) -> BinResult<Vec<(String, Animation)>> {
@@ -43,7 +52,7 @@ pub struct AnimationNode {
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct FCurves {
-    // TODO: also store keyframes?
+    // TODO: Store keyframes directly instead of using FCurves::keyframes?
     // TODO: methods to return values per channel to work efficiently in Blender?
     /// Translation keyframes for each bone hash.
     pub translation: BTreeMap<u32, Vec<Vec3>>,
@@ -53,32 +62,29 @@ pub struct FCurves {
     pub scale: BTreeMap<u32, Vec<Vec3>>,
 }
 
+/// How to find the bone in the target skeleton for each bone in the source skeleton.
+#[derive(Debug, PartialEq, Clone)]
+pub enum BoneMapping {
+    /// Match bones with identical names.
+    Name,
+    /// Match bones using a map from source bone names to target bone names.
+    /// Source bones not in the map are not retargeted.
+    Explicit(BTreeMap<String, String>),
+}
+
 impl Animation {
+    /// Decode the keys for each node in `omo`.
+    ///
+    /// Nodes with invalid data or missing keys are skipped with a warning.
+    /// [PositionType::Frame] and [RotationType::Frame] tracks are assumed to store
+    /// raw big endian floats in the keys, which has not been verified against game files.
     pub fn from_omo(omo: &Omo) -> BinResult<Self> {
         let mut nodes = Vec::new();
         for node in &omo.nodes {
-            let data = omo_node_data(node, &omo.inter_data)?;
-
-            // TODO: Find a nicer way to select key data for each frame.
-            let mut translation_keyframes = Vec::new();
-            let mut rotation_keyframes = Vec::new();
-            let mut scale_keyframes = Vec::new();
-
-            for frame in &omo.frames {
-                // Convert a byte offset to an index for u16s.
-                let mut key_index = node.key_offset as usize / 2;
-                translation_keyframes.push(data.translation(&frame.keys, &mut key_index));
-                rotation_keyframes.push(data.rotation(&frame.keys, &mut key_index));
-                scale_keyframes.push(data.scale(&frame.keys, &mut key_index));
+            match animation_node(omo, node) {
+                Ok(animation_node) => nodes.push(animation_node),
+                Err(e) => warn!("Skipping node {:08X}: {e}", node.hash),
             }
-
-            let animation_node = AnimationNode {
-                hash: node.hash,
-                translation_keyframes,
-                rotation_keyframes,
-                scale_keyframes,
-            };
-            nodes.push(animation_node);
         }
 
         Ok(Self {
@@ -236,6 +242,356 @@ impl Animation {
             scale: scale_points,
         }
     }
+
+    /// Convert an animation for the `source` skeleton to an animation for the `target` skeleton.
+    ///
+    /// Each keyframe is applied relative to the source bone's rest pose
+    /// and then converted back using the target bone's rest pose.
+    /// This preserves the motion for skeletons with different bone lengths or orientations.
+    /// Nodes without a matching bone in both skeletons are removed.
+    pub fn retarget(
+        &self,
+        source: &VbnSkeleton,
+        target: &VbnSkeleton,
+        mapping: &BoneMapping,
+    ) -> Animation {
+        let nodes = self
+            .nodes
+            .iter()
+            .filter_map(|node| {
+                let Some(source_bone) = source.bones.iter().find(|b| b.hash == node.hash) else {
+                    warn!("Bone hash {:x} not found in source skeleton.", node.hash);
+                    return None;
+                };
+
+                let target_name = match mapping {
+                    BoneMapping::Name => &source_bone.name,
+                    BoneMapping::Explicit(names) => names.get(&source_bone.name)?,
+                };
+                let Some(target_bone) = target.bones.iter().find(|b| &b.name == target_name) else {
+                    warn!("Bone {target_name:?} not found in target skeleton.");
+                    return None;
+                };
+
+                Some(retarget_node(
+                    node,
+                    source_bone,
+                    target_bone.matrix(),
+                    target_bone.hash,
+                ))
+            })
+            .collect();
+
+        Animation {
+            nodes,
+            frame_count: self.frame_count,
+        }
+    }
+
+    /// Select the keyframes in `frames` clamped to [frame_count](#structfield.frame_count).
+    pub fn slice(&self, frames: Range<usize>) -> Animation {
+        let end = frames.end.min(self.frame_count);
+        let start = frames.start.min(end);
+
+        Animation {
+            nodes: self
+                .nodes
+                .iter()
+                .map(|n| AnimationNode {
+                    hash: n.hash,
+                    translation_keyframes: slice_keyframes(&n.translation_keyframes, start, end),
+                    rotation_keyframes: slice_keyframes(&n.rotation_keyframes, start, end),
+                    scale_keyframes: slice_keyframes(&n.scale_keyframes, start, end),
+                })
+                .collect(),
+            frame_count: end - start,
+        }
+    }
+
+    /// Play the animation `count` times in a row.
+    pub fn repeat(&self, count: usize) -> Animation {
+        Animation {
+            nodes: self
+                .nodes
+                .iter()
+                .map(|n| {
+                    let frames = (0..count)
+                        .flat_map(|_| (0..self.frame_count).map(|i| keyframe_values(Some(n), i)));
+                    node_from_values(n.hash, frames)
+                })
+                .collect(),
+            frame_count: self.frame_count * count,
+        }
+    }
+
+    /// Play `other` immediately after this animation.
+    ///
+    /// Bones animated in only one of the animations use the rest pose for the other animation.
+    pub fn concat(&self, other: &Animation) -> Animation {
+        let nodes = node_hashes(self, other)
+            .into_iter()
+            .map(|hash| {
+                let a = self.nodes.iter().find(|n| n.hash == hash);
+                let b = other.nodes.iter().find(|n| n.hash == hash);
+
+                let frames = (0..self.frame_count)
+                    .map(|i| keyframe_values(a, i))
+                    .chain((0..other.frame_count).map(|i| keyframe_values(b, i)));
+                node_from_values(hash, frames)
+            })
+            .collect();
+
+        Animation {
+            nodes,
+            frame_count: self.frame_count + other.frame_count,
+        }
+    }
+
+    /// Play `other` after this animation with a linear blend over `blend_frames` overlapping frames.
+    ///
+    /// Missing keyframes use the rest pose from `skeleton`.
+    pub fn crossfade(
+        &self,
+        other: &Animation,
+        skeleton: &VbnSkeleton,
+        blend_frames: usize,
+    ) -> Animation {
+        let blend_frames = blend_frames.min(self.frame_count).min(other.frame_count);
+        let start = self.frame_count - blend_frames;
+
+        let nodes = node_hashes(self, other)
+            .into_iter()
+            .map(|hash| {
+                let bone = skeleton.bones.iter().find(|b| b.hash == hash);
+                let a = self.nodes.iter().find(|n| n.hash == hash);
+                let b = other.nodes.iter().find(|n| n.hash == hash);
+
+                let (rest_t, rest_r, rest_s) = rest_values((None, None, None), bone);
+                let blended = (0..blend_frames).map(|i| {
+                    // Exclude the endpoints to avoid duplicating frames.
+                    let factor = (i + 1) as f32 / (blend_frames + 1) as f32;
+                    let (t0, r0, s0) = keyframe_values(a, start + i);
+                    let (t1, r1, s1) = keyframe_values(b, i);
+                    (
+                        blend_rest(t0, t1, rest_t, |t0, t1| t0.lerp(t1, factor)),
+                        blend_rest(r0, r1, rest_r, |r0, r1| r0.slerp(r1, factor)),
+                        blend_rest(s0, s1, rest_s, |s0, s1| s0.lerp(s1, factor)),
+                    )
+                });
+
+                let frames = (0..start)
+                    .map(|i| keyframe_values(a, i))
+                    .chain(blended)
+                    .chain((blend_frames..other.frame_count).map(|i| keyframe_values(b, i)));
+                node_from_values(hash, frames)
+            })
+            .collect();
+
+        Animation {
+            nodes,
+            frame_count: self.frame_count + other.frame_count - blend_frames,
+        }
+    }
+
+    /// Apply the difference between `layer` and the rest pose from `skeleton` on top of this animation.
+    ///
+    /// This is used for partial poses like faces or hands that should not replace the base animation.
+    /// A `weight` of `0.0` has no effect and a `weight` of `1.0` applies the full layer.
+    /// The final keyframe of `layer` persists if `layer` has fewer frames.
+    pub fn add_layer(&self, layer: &Animation, skeleton: &VbnSkeleton, weight: f32) -> Animation {
+        let nodes = node_hashes(self, layer)
+            .into_iter()
+            .map(|hash| {
+                let base = self.nodes.iter().find(|n| n.hash == hash);
+                let (Some(node), Some(bone)) = (
+                    layer.nodes.iter().find(|n| n.hash == hash),
+                    skeleton.bones.iter().find(|b| b.hash == hash),
+                ) else {
+                    // Layer bones without a rest pose have no defined difference to apply.
+                    let frames = (0..self.frame_count).map(|i| keyframe_values(base, i));
+                    return node_from_values(hash, frames);
+                };
+
+                let rest_rotation = bone_rotation(bone);
+
+                let frames = (0..self.frame_count).map(|i| {
+                    let (t, r, s) = rest_values(keyframe_values(base, i), Some(bone));
+                    let (layer_t, layer_r, layer_s) = keyframe_values(Some(node), i);
+
+                    let t = match layer_t {
+                        Some(layer_t) => t.map(|t| t + (layer_t - bone.translation) * weight),
+                        None => t,
+                    };
+                    let r = match layer_r {
+                        Some(layer_r) => {
+                            let delta = rest_rotation.inverse() * layer_r;
+                            r.map(|r| r * Quat::IDENTITY.slerp(delta, weight))
+                        }
+                        None => r,
+                    };
+                    let s = match layer_s {
+                        Some(layer_s) => {
+                            s.map(|s| s * Vec3::ONE.lerp(layer_s / bone.scale, weight))
+                        }
+                        None => s,
+                    };
+                    (t, r, s)
+                });
+                node_from_values(hash, frames)
+            })
+            .collect();
+
+        Animation {
+            nodes,
+            frame_count: self.frame_count,
+        }
+    }
+}
+
+type KeyframeValues = (Option<Vec3>, Option<Quat>, Option<Vec3>);
+
+fn slice_keyframes<T: Copy>(keyframes: &[Option<T>], start: usize, end: usize) -> Vec<Option<T>> {
+    (start..end).map(|i| keyframe(keyframes, i)).collect()
+}
+
+fn keyframe<T: Copy>(keyframes: &[Option<T>], frame: usize) -> Option<T> {
+    // The final keyframe should persist for the rest of the animation.
+    let index = frame.min(keyframes.len().saturating_sub(1));
+    keyframes.get(index).copied().flatten()
+}
+
+fn keyframe_values(node: Option<&AnimationNode>, frame: usize) -> KeyframeValues {
+    match node {
+        Some(node) => (
+            keyframe(&node.translation_keyframes, frame),
+            keyframe(&node.rotation_keyframes, frame),
+            keyframe(&node.scale_keyframes, frame),
+        ),
+        None => (None, None, None),
+    }
+}
+
+fn rest_values((t, r, s): KeyframeValues, bone: Option<&VbnBone>) -> KeyframeValues {
+    // Missing channels use the rest pose like in Animation::model_space_transforms.
+    match bone {
+        Some(bone) => (
+            t.or(Some(bone.translation)),
+            r.or_else(|| Some(bone_rotation(bone))),
+            s.or(Some(bone.scale)),
+        ),
+        None => (t, r, s),
+    }
+}
+
+fn bone_rotation(bone: &VbnBone) -> Quat {
+    Quat::from_euler(
+        EulerRot::XYZEx,
+        bone.rotation.x,
+        bone.rotation.y,
+        bone.rotation.z,
+    )
+}
+
+fn blend<T>(a: Option<T>, b: Option<T>, f: impl Fn(T, T) -> T) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(f(a, b)),
+        (a, b) => a.or(b),
+    }
+}
+
+fn blend_rest<T: Copy>(
+    a: Option<T>,
+    b: Option<T>,
+    rest: Option<T>,
+    f: impl Fn(T, T) -> T,
+) -> Option<T> {
+    // Channels not animated by either animation stay unset like frames outside the blend.
+    match (a, b) {
+        (None, None) => None,
+        (a, b) => blend(a.or(rest), b.or(rest), f),
+    }
+}
+
+fn node_hashes(a: &Animation, b: &Animation) -> Vec<u32> {
+    let mut hashes: Vec<_> = a.nodes.iter().map(|n| n.hash).collect();
+    for node in &b.nodes {
+        if !hashes.contains(&node.hash) {
+            hashes.push(node.hash);
+        }
+    }
+    hashes
+}
+
+fn node_from_values(hash: u32, frames: impl Iterator<Item = KeyframeValues>) -> AnimationNode {
+    let mut translation_keyframes = Vec::new();
+    let mut rotation_keyframes = Vec::new();
+    let mut scale_keyframes = Vec::new();
+    for (t, r, s) in frames {
+        translation_keyframes.push(t);
+        rotation_keyframes.push(r);
+        scale_keyframes.push(s);
+    }
+
+    AnimationNode {
+        hash,
+        translation_keyframes,
+        rotation_keyframes,
+        scale_keyframes,
+    }
+}
+
+fn retarget_node(
+    node: &AnimationNode,
+    source_bone: &VbnBone,
+    target_rest: Mat4,
+    target_hash: u32,
+) -> AnimationNode {
+    let source_rest = source_bone.matrix();
+    let source_rest_inverse = source_rest.inverse();
+
+    let frame_count = node
+        .translation_keyframes
+        .len()
+        .max(node.rotation_keyframes.len())
+        .max(node.scale_keyframes.len());
+
+    let mut translation_keyframes = Vec::new();
+    let mut rotation_keyframes = Vec::new();
+    let mut scale_keyframes = Vec::new();
+
+    for i in 0..frame_count {
+        let translation = node.translation_keyframes.get(i).copied().flatten();
+        let rotation = node.rotation_keyframes.get(i).copied().flatten();
+        let scale = node.scale_keyframes.get(i).copied().flatten();
+
+        // Missing channels use the rest pose like in Animation::model_space_transforms.
+        let rotation_matrix = rotation.map(Mat4::from_quat).unwrap_or_else(|| {
+            Mat4::from_euler(
+                EulerRot::XYZEx,
+                source_bone.rotation.x,
+                source_bone.rotation.y,
+                source_bone.rotation.z,
+            )
+        });
+        let local = Mat4::from_translation(translation.unwrap_or(source_bone.translation))
+            * rotation_matrix
+            * Mat4::from_scale(scale.unwrap_or(source_bone.scale));
+
+        // Find the transform relative to the rest pose similar to the basis for fcurves.
+        let basis = source_rest_inverse * local;
+        let (s, r, t) = (target_rest * basis).to_scale_rotation_translation();
+
+        translation_keyframes.push(translation.map(|_| t));
+        rotation_keyframes.push(rotation.map(|_| r));
+        scale_keyframes.push(scale.map(|_| s));
+    }
+
+    AnimationNode {
+        hash: target_hash,
+        translation_keyframes,
+        rotation_keyframes,
+        scale_keyframes,
+    }
 }
 
 impl AnimationNode {
@@ -311,61 +667,135 @@ enum ScaleData {
 }
 
 impl TransformData {
-    fn translation(&self, keys: &[u16], key_index: &mut usize) -> Option<Vec3> {
-        match self.translation.as_ref()? {
-            TranslationData::Frame => None,
+    fn translation(&self, keys: &[u16], key_index: &mut usize) -> BinResult<Option<Vec3>> {
+        let Some(translation) = &self.translation else {
+            return Ok(None);
+        };
+        match translation {
+            TranslationData::Frame => {
+                let [x, y, z] = frame_f32s(keys, key_index)?;
+                Ok(Some(vec3(x, y, z)))
+            }
             TranslationData::Interpolate { min, max } => {
-                Some(interpolate_vec3(*min, *max, keys, key_index))
+                interpolate_vec3(*min, *max, keys, key_index).map(Some)
             }
 
-            TranslationData::Constant(v) => Some(*v),
+            TranslationData::Constant(v) => Ok(Some(*v)),
         }
     }
 
-    fn rotation(&self, keys: &[u16], key_index: &mut usize) -> Option<Quat> {
-        match self.rotation.as_ref()? {
+    fn rotation(&self, keys: &[u16], key_index: &mut usize) -> BinResult<Option<Quat>> {
+        let Some(rotation) = &self.rotation else {
+            return Ok(None);
+        };
+        match rotation {
             RotationData::Interpolate { min, max } => {
-                let v = interpolate_vec3(*min, *max, keys, key_index);
-                Some(Quat::from_xyzw(v.x, v.y, v.z, calculate_w(v)))
+                let v = interpolate_vec3(*min, *max, keys, key_index)?;
+                Ok(Some(Quat::from_xyzw(v.x, v.y, v.z, calculate_w(v))))
             }
             RotationData::FConst { value, extra } => {
                 // https://github.com/jam1garner/Smash-Forge/blob/36d221f1182cdb14927acb1fc3399c8f06d42a53/Smash%20Forge/Filetypes/Animation/OMOOld.cs#L320-L335
-                let scale = keys[*key_index] as f32 / 65535.0 * extra;
-                *key_index += 1;
+                let [key] = read_keys(keys, key_index)?;
+                let scale = key as f32 / 65535.0 * extra;
                 let x = value.x;
                 let y = value.y;
                 let z = value.z + scale;
                 let w = rotation_type6_w(x, y, z);
-                Some(Quat::from_xyzw(x, y, z, w))
+                Ok(Some(Quat::from_xyzw(x, y, z, w)))
+            }
+            RotationData::Constant(v) => Ok(Some(Quat::from_xyzw(v.x, v.y, v.z, calculate_w(*v)))),
+            RotationData::Frame => {
+                let [x, y, z, w] = frame_f32s(keys, key_index)?;
+                Ok(Some(Quat::from_xyzw(x, y, z, w)))
             }
-            RotationData::Constant(v) => Some(Quat::from_xyzw(v.x, v.y, v.z, calculate_w(*v))),
-            RotationData::Frame => None,
         }
     }
 
-    fn scale(&self, keys: &[u16], key_index: &mut usize) -> Option<Vec3> {
-        match self.scale.as_ref()? {
-            ScaleData::Constant(v) => Some(*v),
+    fn scale(&self, keys: &[u16], key_index: &mut usize) -> BinResult<Option<Vec3>> {
+        let Some(scale) = &self.scale else {
+            return Ok(None);
+        };
+        match scale {
+            ScaleData::Constant(v) => Ok(Some(*v)),
             ScaleData::Interpolate { min, max } => {
-                Some(interpolate_vec3(*min, *max, keys, key_index))
+                interpolate_vec3(*min, *max, keys, key_index).map(Some)
             }
         }
     }
 }
 
+fn animation_node(omo: &Omo, node: &OmoNode) -> BinResult<AnimationNode> {
+    let unk1 = node.flags.unk1().value();
+    let unk4 = node.flags.unk4().value();
+    if unk1 != 0 || unk4 != 0 {
+        // These bits are not decoded and may change how keys should be read.
+        warn!(
+            "Unknown flags unk1: {unk1:X}, unk4: {unk4:X} for node {:08X}",
+            node.hash
+        );
+    }
+
+    let data = omo_node_data(node, &omo.inter_data)?;
+
+    // TODO: Find a nicer way to select key data for each frame.
+    let mut translation_keyframes = Vec::new();
+    let mut rotation_keyframes = Vec::new();
+    let mut scale_keyframes = Vec::new();
+
+    for frame in &omo.frames {
+        // Convert a byte offset to an index for u16s.
+        let mut key_index = node.key_offset as usize / 2;
+        translation_keyframes.push(data.translation(&frame.keys, &mut key_index)?);
+        rotation_keyframes.push(data.rotation(&frame.keys, &mut key_index)?);
+        scale_keyframes.push(data.scale(&frame.keys, &mut key_index)?);
+    }
+
+    Ok(AnimationNode {
+        hash: node.hash,
+        translation_keyframes,
+        rotation_keyframes,
+        scale_keyframes,
+    })
+}
+
 fn calculate_w(v: Vec3) -> f32 {
     // Assume unit quaternion.
     (1.0 - v.length_squared()).abs().sqrt()
 }
 
-fn interpolate_vec3(min: Vec3, max: Vec3, keys: &[u16], key_index: &mut usize) -> Vec3 {
-    let f = vec3(
-        keys[*key_index] as f32,
-        keys[*key_index + 1] as f32,
-        keys[*key_index + 2] as f32,
-    ) / 65535.0;
-    *key_index += 3;
-    min + f * max
+fn read_keys<const N: usize>(keys: &[u16], key_index: &mut usize) -> BinResult<[u16; N]> {
+    // Missing keys would shift the offsets for all remaining values for this node.
+    let values = keys
+        .get(*key_index..*key_index + N)
+        .and_then(|k| k.try_into().ok())
+        .ok_or_else(|| binrw::Error::AssertFail {
+            pos: *key_index as u64 * 2,
+            message: format!(
+                "expected {N} keys at index {} for frame with {} keys",
+                *key_index,
+                keys.len()
+            ),
+        })?;
+    *key_index += N;
+    Ok(values)
+}
+
+fn frame_f32s<const N: usize>(keys: &[u16], key_index: &mut usize) -> BinResult<[f32; N]> {
+    // TODO: Verify this layout with a test using keys from an in game animation.
+    // Assume frame values have no inter data and store raw big endian floats in the keys.
+    // sm4sh_test --omo reports decoded values that are not finite or normalized.
+    let mut values = [0.0; N];
+    for value in &mut values {
+        let [high, low] = read_keys(keys, key_index)?;
+        *value = f32::from_bits(((high as u32) << 16) | low as u32);
+    }
+    Ok(values)
+}
+
+fn interpolate_vec3(min: Vec3, max: Vec3, keys: &[u16], key_index: &mut usize) -> BinResult<Vec3> {
+    let [x, y, z] = read_keys(keys, key_index)?;
+    let f = vec3(x as f32, y as f32, z as f32) / 65535.0;
+    Ok(min + f * max)
 }
 
 fn omo_node_data(node: &OmoNode, inter_data: &[u8]) -> BinResult<TransformData> {
@@ -401,10 +831,7 @@ fn omo_node_data(node: &OmoNode, inter_data: &[u8]) -> BinResult<TransformData>
                 let v: [f32; 3] = data.read_be()?;
                 Some(RotationData::Constant(v.into()))
             }
-            RotationType::Frame => {
-                // TODO: what does "frame" mean?
-                Some(RotationData::Frame)
-            }
+            RotationType::Frame => Some(RotationData::Frame),
         }
     } else {
         None
@@ -503,7 +930,6 @@ fn insert_fcurve_point<T: Copy>(points: &mut BTreeMap<u32, Vec<T>>, hash: u32, t
 mod tests {
     use super::*;
 
-    use crate::VbnBone;
     use glam::quat;
     use sm4sh_lib::vbn::BoneType;
 
@@ -796,4 +1222,256 @@ mod tests {
             fcurves
         );
     }
+
+    #[test]
+    fn retarget_rest_pose_differences() {
+        let animation = Animation {
+            frame_count: 2,
+            nodes: vec![AnimationNode {
+                translation_keyframes: vec![Some(vec3(1.0, 0.0, 0.0)), Some(vec3(2.0, 0.0, 0.0))],
+                rotation_keyframes: vec![None, None],
+                scale_keyframes: vec![Some(Vec3::ONE), Some(Vec3::ONE)],
+                hash: 1,
+            }],
+        };
+
+        let source = VbnSkeleton {
+            bones: vec![VbnBone {
+                name: "a".to_string(),
+                hash: 1,
+                parent_bone_index: None,
+                bone_type: BoneType::Normal,
+                translation: vec3(1.0, 0.0, 0.0),
+                rotation: Vec3::ZERO,
+                scale: Vec3::ONE,
+            }],
+        };
+
+        let target = VbnSkeleton {
+            bones: vec![VbnBone {
+                name: "b".to_string(),
+                hash: 2,
+                parent_bone_index: None,
+                bone_type: BoneType::Normal,
+                translation: vec3(0.0, 3.0, 0.0),
+                rotation: Vec3::ZERO,
+                scale: Vec3::ONE,
+            }],
+        };
+
+        // Bone names don't match.
+        assert!(
+            animation
+                .retarget(&source, &target, &BoneMapping::Name)
+                .nodes
+                .is_empty()
+        );
+
+        let mapping = BoneMapping::Explicit([("a".to_string(), "b".to_string())].into());
+        let retargeted = animation.retarget(&source, &target, &mapping);
+        assert_eq!(2, retargeted.frame_count);
+        assert_eq!(1, retargeted.nodes.len());
+
+        let node = &retargeted.nodes[0];
+        assert_eq!(2, node.hash);
+        assert_eq!(vec![None, None], node.rotation_keyframes);
+        assert!(
+            node.translation_keyframes[0]
+                .unwrap()
+                .abs_diff_eq(vec3(0.0, 3.0, 0.0), 0.0001)
+        );
+        assert!(
+            node.translation_keyframes[1]
+                .unwrap()
+                .abs_diff_eq(vec3(1.0, 3.0, 0.0), 0.0001)
+        );
+        assert!(
+            node.scale_keyframes[1]
+                .unwrap()
+                .abs_diff_eq(Vec3::ONE, 0.0001)
+        );
+    }
+
+    fn translation_animation(hash: u32, xs: &[f32]) -> Animation {
+        Animation {
+            frame_count: xs.len(),
+            nodes: vec![AnimationNode {
+                translation_keyframes: xs.iter().map(|x| Some(vec3(*x, 0.0, 0.0))).collect(),
+                rotation_keyframes: vec![None; xs.len()],
+                scale_keyframes: vec![None; xs.len()],
+                hash,
+            }],
+        }
+    }
+
+    fn single_bone_skeleton(hash: u32) -> VbnSkeleton {
+        VbnSkeleton {
+            bones: vec![VbnBone {
+                name: "a".to_string(),
+                hash,
+                parent_bone_index: None,
+                bone_type: BoneType::Normal,
+                translation: Vec3::ZERO,
+                rotation: Vec3::ZERO,
+                scale: Vec3::ONE,
+            }],
+        }
+    }
+
+    #[test]
+    fn slice_repeat_concat() {
+        let animation = translation_animation(1, &[0.0, 1.0, 2.0, 3.0]);
+
+        assert_eq!(translation_animation(1, &[1.0, 2.0]), animation.slice(1..3));
+        assert_eq!(translation_animation(1, &[3.0]), animation.slice(3..10));
+        assert_eq!(
+            translation_animation(1, &[0.0, 1.0, 0.0, 1.0, 0.0, 1.0]),
+            animation.slice(0..2).repeat(3)
+        );
+        assert_eq!(
+            translation_animation(1, &[0.0, 1.0, 2.0, 3.0, 2.0, 3.0]),
+            animation.concat(&animation.slice(2..4))
+        );
+    }
+
+    #[test]
+    fn concat_different_bones() {
+        let a = translation_animation(1, &[1.0]);
+        let b = translation_animation(2, &[2.0]);
+
+        let animation = a.concat(&b);
+        assert_eq!(2, animation.frame_count);
+        assert_eq!(
+            vec![Some(vec3(1.0, 0.0, 0.0)), None],
+            animation.nodes[0].translation_keyframes
+        );
+        assert_eq!(
+            vec![None, Some(vec3(2.0, 0.0, 0.0))],
+            animation.nodes[1].translation_keyframes
+        );
+    }
+
+    #[test]
+    fn crossfade_translation() {
+        let a = translation_animation(1, &[0.0, 0.0, 0.0]);
+        let b = translation_animation(1, &[3.0, 3.0, 3.0]);
+
+        let animation = a.crossfade(&b, &single_bone_skeleton(1), 2);
+        assert_eq!(4, animation.frame_count);
+        assert_eq!(
+            translation_animation(1, &[0.0, 1.0, 2.0, 3.0]).nodes[0].translation_keyframes,
+            animation.nodes[0].translation_keyframes
+        );
+        assert_eq!(vec![None; 4], animation.nodes[0].rotation_keyframes);
+    }
+
+    #[test]
+    fn crossfade_missing_bone_uses_rest_pose() {
+        let a = translation_animation(1, &[3.0, 3.0]);
+        let b = translation_animation(2, &[0.0, 0.0]);
+
+        let animation = a.crossfade(&b, &single_bone_skeleton(1), 1);
+        assert_eq!(3, animation.frame_count);
+        assert_eq!(
+            vec![Some(vec3(3.0, 0.0, 0.0)), Some(vec3(1.5, 0.0, 0.0)), None],
+            animation.nodes[0].translation_keyframes
+        );
+    }
+
+    #[test]
+    fn add_layer_translation() {
+        let base = translation_animation(1, &[1.0, 2.0]);
+        let layer = translation_animation(1, &[4.0]);
+
+        let animation = base.add_layer(&layer, &single_bone_skeleton(1), 0.5);
+        assert_eq!(
+            translation_animation(1, &[3.0, 4.0]).nodes[0].translation_keyframes,
+            animation.nodes[0].translation_keyframes
+        );
+    }
+
+    #[test]
+    fn frame_translation_rotation_keys() {
+        let data = TransformData {
+            translation: Some(TranslationData::Frame),
+            rotation: Some(RotationData::Frame),
+            scale: None,
+        };
+
+        // 1.0, 2.0, 3.0 and 0.0, 0.0, 0.0, 1.0 as big endian f32.
+        let keys = [
+            0x3F80, 0x0000, 0x4000, 0x0000, 0x4040, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000,
+            0x0000, 0x3F80, 0x0000,
+        ];
+        let mut key_index = 0;
+        assert_eq!(
+            Some(vec3(1.0, 2.0, 3.0)),
+            data.translation(&keys, &mut key_index).unwrap()
+        );
+        assert_eq!(
+            Some(quat(0.0, 0.0, 0.0, 1.0)),
+            data.rotation(&keys, &mut key_index).unwrap()
+        );
+        assert_eq!(keys.len(), key_index);
+
+        // Missing keys should be an error instead of shifting later values.
+        assert!(data.translation(&keys, &mut key_index).is_err());
+    }
+
+    #[test]
+    fn from_omo_skip_invalid_nodes() {
+        use bilge::prelude::{u4, u5};
+        use sm4sh_lib::omo::{Frame, OmoFlags};
+
+        let flags = |position| {
+            OmoFlags::new(
+                u4::new(0),
+                ScaleType::Constant,
+                RotationType::Constant,
+                PositionType::Frame,
+                position,
+                false,
+                false,
+                u5::new(0),
+            )
+        };
+        let omo = Omo {
+            version: (3, 0),
+            flags: 0,
+            unk1: 0,
+            node_count: 2,
+            frame_count: 1,
+            frame_size: 4,
+            nodes: vec![
+                // Frame translations need 6 keys.
+                OmoNode {
+                    flags: flags(true),
+                    hash: 1,
+                    inter_offset: 0,
+                    key_offset: 0,
+                },
+                OmoNode {
+                    flags: flags(false),
+                    hash: 2,
+                    inter_offset: 0,
+                    key_offset: 0,
+                },
+            ],
+            inter_data: Vec::new(),
+            frames: vec![Frame {
+                keys: vec![0x3F80, 0x0000],
+            }],
+        };
+
+        let animation = Animation::from_omo(&omo).unwrap();
+        assert_eq!(
+            vec![AnimationNode {
+                hash: 2,
+                translation_keyframes: vec![None],
+                rotation_keyframes: vec![None],
+                scale_keyframes: vec![None],
+            }],
+            animation.nodes
+        );
+    }
 }
@@ -0,0 +1,329 @@
+use std::collections::BTreeMap;
+
+use glam::{Vec2, vec2};
+
+use super::FCurves;
+
+/// Keyframes for each component of each channel after removing redundant baked frames.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FCurveKeyframes {
+    /// Translation XYZ keyframes for each bone hash.
+    pub translation: BTreeMap<u32, [Vec<Keyframe>; 3]>,
+    /// Rotation quaternion XYZW keyframes for each bone hash.
+    ///
+    /// All components use the same frames, so each key is a normalized quaternion.
+    pub rotation: BTreeMap<u32, [Vec<Keyframe>; 4]>,
+    /// Scale XYZ keyframes for each bone hash.
+    pub scale: BTreeMap<u32, [Vec<Keyframe>; 3]>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Keyframe {
+    pub frame: f32,
+    pub value: f32,
+    /// Handles for Bezier interpolation or `None` for linear interpolation.
+    pub handles: Option<BezierHandles>,
+}
+
+/// Bezier control points as `(frame, value)` pairs.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct BezierHandles {
+    pub left: Vec2,
+    pub right: Vec2,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum KeyframeInterpolation {
+    Linear,
+    Bezier,
+}
+
+/// The maximum absolute difference from the baked values for each channel.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct KeyframeTolerance {
+    pub translation: f32,
+    pub rotation: f32,
+    pub scale: f32,
+}
+
+impl Default for KeyframeTolerance {
+    fn default() -> Self {
+        Self {
+            translation: 0.001,
+            rotation: 0.0001,
+            scale: 0.0001,
+        }
+    }
+}
+
+impl FCurves {
+    /// Reduce each curve to keyframes that stay within `tolerance` of the baked values.
+    ///
+    /// See [reduce_keyframes] for details on the reduction.
+    /// Rotation components are reduced together and share keyframes
+    /// to avoid unnormalized quaternions at the keys.
+    /// Keyframes are only placed on existing frames.
+    /// The first and last frame of each curve are always included.
+    pub fn keyframes(
+        &self,
+        tolerance: &KeyframeTolerance,
+        interpolation: KeyframeInterpolation,
+    ) -> FCurveKeyframes {
+        FCurveKeyframes {
+            translation: self
+                .translation
+                .iter()
+                .map(|(hash, values)| {
+                    let curves = [0, 1, 2].map(|c| {
+                        let values: Vec<_> = values.iter().map(|v| v[c]).collect();
+                        reduce_keyframes(&values, tolerance.translation, interpolation)
+                    });
+                    (*hash, curves)
+                })
+                .collect(),
+            rotation: self
+                .rotation
+                .iter()
+                .map(|(hash, values)| {
+                    let values: Vec<_> = values.iter().map(|v| v.to_array()).collect();
+                    let curves =
+                        reduce_keyframes_components(&values, tolerance.rotation, interpolation);
+                    (*hash, curves)
+                })
+                .collect(),
+            scale: self
+                .scale
+                .iter()
+                .map(|(hash, values)| {
+                    let curves = [0, 1, 2].map(|c| {
+                        let values: Vec<_> = values.iter().map(|v| v[c]).collect();
+                        reduce_keyframes(&values, tolerance.scale, interpolation)
+                    });
+                    (*hash, curves)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Reduce the baked `values` for each frame to keyframes within `tolerance`.
+///
+/// This uses the Ramer-Douglas-Peucker algorithm, which greedily adds keys at the frame with the highest error.
+/// The result typically has few keyframes but is not guaranteed to be the minimum.
+pub fn reduce_keyframes(
+    values: &[f32],
+    tolerance: f32,
+    interpolation: KeyframeInterpolation,
+) -> Vec<Keyframe> {
+    let values: Vec<_> = values.iter().map(|v| [*v]).collect();
+    let [keyframes] = reduce_keyframes_components(&values, tolerance, interpolation);
+    keyframes
+}
+
+// Components share the same key frames using the highest error of any component.
+fn reduce_keyframes_components<const N: usize>(
+    values: &[[f32; N]],
+    tolerance: f32,
+    interpolation: KeyframeInterpolation,
+) -> [Vec<Keyframe>; N] {
+    if values.is_empty() {
+        return std::array::from_fn(|_| Vec::new());
+    }
+
+    let components: [Vec<f32>; N] = std::array::from_fn(|c| values.iter().map(|v| v[c]).collect());
+    let slopes: Option<[Vec<f32>; N]> = match interpolation {
+        KeyframeInterpolation::Linear => None,
+        KeyframeInterpolation::Bezier => {
+            Some(std::array::from_fn(|c| sample_slopes(&components[c])))
+        }
+    };
+    let component_slopes = |c: usize| slopes.as_ref().map(|s| s[c].as_slice());
+
+    // Recursively split segments at the frame with the highest error.
+    // This is the Ramer-Douglas-Peucker algorithm applied to a 1D function.
+    let mut key_frames = vec![0, values.len() - 1];
+    let mut segments = vec![(0, values.len() - 1)];
+    while let Some((start, end)) = segments.pop() {
+        let max_error = (start + 1..end)
+            .map(|i| {
+                let error = (0..N)
+                    .map(|c| {
+                        let values = &components[c];
+                        let predicted =
+                            evaluate_segment(values, component_slopes(c), start, end, i);
+                        (predicted - values[i]).abs()
+                    })
+                    .fold(0.0, f32::max);
+                (i, error)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        if let Some((i, _)) = max_error.filter(|(_, error)| *error > tolerance) {
+            key_frames.push(i);
+            segments.push((start, i));
+            segments.push((i, end));
+        }
+    }
+    key_frames.sort();
+    key_frames.dedup();
+
+    std::array::from_fn(|c| keyframes(&components[c], component_slopes(c), &key_frames))
+}
+
+fn keyframes(values: &[f32], slopes: Option<&[f32]>, key_frames: &[usize]) -> Vec<Keyframe> {
+    key_frames
+        .iter()
+        .enumerate()
+        .map(|(k, i)| Keyframe {
+            frame: *i as f32,
+            value: values[*i],
+            handles: slopes.map(|slopes| {
+                // Place handles a third of the way to the neighboring keys.
+                // This makes the Bezier curve equivalent to the Hermite curve used for fitting.
+                let point = vec2(*i as f32, values[*i]);
+                let tangent = vec2(1.0, slopes[*i]);
+                let left_width = k.checked_sub(1).map(|p| i - key_frames[p]).unwrap_or(0);
+                let right_width = key_frames.get(k + 1).map(|n| n - i).unwrap_or(0);
+                BezierHandles {
+                    left: point - tangent * left_width as f32 / 3.0,
+                    right: point + tangent * right_width as f32 / 3.0,
+                }
+            }),
+        })
+        .collect()
+}
+
+fn sample_slopes(values: &[f32]) -> Vec<f32> {
+    // Estimate the derivative at each frame using finite differences.
+    (0..values.len())
+        .map(|i| {
+            let previous = i.saturating_sub(1);
+            let next = (i + 1).min(values.len() - 1);
+            if next > previous {
+                (values[next] - values[previous]) / (next - previous) as f32
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+fn evaluate_segment(
+    values: &[f32],
+    slopes: Option<&[f32]>,
+    start: usize,
+    end: usize,
+    frame: usize,
+) -> f32 {
+    let width = (end - start) as f32;
+    let t = (frame - start) as f32 / width;
+    let p0 = values[start];
+    let p1 = values[end];
+    match slopes {
+        Some(slopes) => {
+            // Cubic Hermite spline with tangents scaled to the segment width.
+            let m0 = slopes[start] * width;
+            let m1 = slopes[end] * width;
+            let t2 = t * t;
+            let t3 = t2 * t;
+            (2.0 * t3 - 3.0 * t2 + 1.0) * p0
+                + (t3 - 2.0 * t2 + t) * m0
+                + (-2.0 * t3 + 3.0 * t2) * p1
+                + (t3 - t2) * m1
+        }
+        None => p0 + (p1 - p0) * t,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(keyframes: &[Keyframe]) -> Vec<f32> {
+        keyframes.iter().map(|k| k.frame).collect()
+    }
+
+    #[test]
+    fn reduce_keyframes_empty() {
+        assert!(reduce_keyframes(&[], 0.001, KeyframeInterpolation::Linear).is_empty());
+    }
+
+    #[test]
+    fn reduce_keyframes_single() {
+        assert_eq!(
+            vec![Keyframe {
+                frame: 0.0,
+                value: 1.0,
+                handles: None
+            }],
+            reduce_keyframes(&[1.0], 0.001, KeyframeInterpolation::Linear)
+        );
+    }
+
+    #[test]
+    fn reduce_keyframes_linear() {
+        let keyframes = reduce_keyframes(
+            &[0.0, 1.0, 2.0, 3.0, 4.0],
+            0.001,
+            KeyframeInterpolation::Linear,
+        );
+        assert_eq!(vec![0.0, 4.0], frames(&keyframes));
+    }
+
+    #[test]
+    fn reduce_keyframes_step() {
+        let values = [0.0, 0.0, 0.0, 1.0, 1.0, 1.0];
+        let keyframes = reduce_keyframes(&values, 0.001, KeyframeInterpolation::Linear);
+        assert_eq!(vec![0.0, 2.0, 3.0, 5.0], frames(&keyframes));
+
+        // A large tolerance should remove the step entirely.
+        let keyframes = reduce_keyframes(&values, 1.0, KeyframeInterpolation::Linear);
+        assert_eq!(vec![0.0, 5.0], frames(&keyframes));
+    }
+
+    #[test]
+    fn reduce_keyframes_bezier() {
+        let keyframes =
+            reduce_keyframes(&[0.0, 1.0, 2.0, 3.0], 0.001, KeyframeInterpolation::Bezier);
+        assert_eq!(
+            vec![
+                Keyframe {
+                    frame: 0.0,
+                    value: 0.0,
+                    handles: Some(BezierHandles {
+                        left: vec2(0.0, 0.0),
+                        right: vec2(1.0, 1.0)
+                    })
+                },
+                Keyframe {
+                    frame: 3.0,
+                    value: 3.0,
+                    handles: Some(BezierHandles {
+                        left: vec2(2.0, 2.0),
+                        right: vec2(3.0, 3.0)
+                    })
+                }
+            ],
+            keyframes
+        );
+    }
+
+    #[test]
+    fn reduce_keyframes_bezier_smooth() {
+        // Smooth curves need fewer Bezier keys than linear keys.
+        let values: Vec<_> = (0..60).map(|i| (i as f32 / 10.0).sin()).collect();
+        let linear = reduce_keyframes(&values, 0.001, KeyframeInterpolation::Linear);
+        let bezier = reduce_keyframes(&values, 0.001, KeyframeInterpolation::Bezier);
+        assert!(bezier.len() < linear.len());
+        assert!(bezier.len() < values.len());
+    }
+
+    #[test]
+    fn reduce_keyframes_components_shared_frames() {
+        // The step in the second component also adds keys to the first component.
+        let values = [[0.0, 0.0], [1.0, 0.0], [2.0, 1.0], [3.0, 1.0]];
+        let [x, y] = reduce_keyframes_components(&values, 0.001, KeyframeInterpolation::Linear);
+        assert_eq!(vec![0.0, 1.0, 2.0, 3.0], frames(&x));
+        assert_eq!(frames(&x), frames(&y));
+    }
+}
@@ -1,4 +1,8 @@
-use std::{collections::BTreeMap, path::Path};
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
 
 use binrw::BinResult;
 use smol_str::SmolStr;
@@ -8,21 +12,48 @@ pub use xc3_shader::expr::{
     xyz::{ChannelXyz, OutputExprXyz, ValueXyz},
 };
 
-use crate::database::uniforms::uniform_parameter_value;
+use crate::{database::uniforms::uniform_parameter_value, globals::RenderGlobals};
+pub use diff::{DatabaseDiff, ProgramDiff};
+pub use eval::EvalInputs;
+pub use io::VERSION;
+#[cfg(feature = "serde")]
+pub use json::DatabaseJsonError;
 
+mod diff;
+mod eval;
 mod io;
+#[cfg(feature = "serde")]
+mod json;
+#[cfg(test)]
+mod test_utils;
 // TODO: Find a nicer way to handle uniform buffers.
 mod uniforms;
 
 // Faster than the default hash implementation.
 type IndexMap<K, V> = indexmap::IndexMap<K, V, ahash::RandomState>;
 
-#[derive(Debug, PartialEq, Clone)]
-pub struct ShaderDatabase {
-    programs: BTreeMap<u32, ShaderProgram>,
+/// A database of shader programs by shader ID.
+///
+/// Programs loaded from a file are decoded on first access and cached.
+/// Cloning only clones a reference to the shared data,
+/// so the database can be shared cheaply between threads or renderers.
+#[derive(Debug, Clone)]
+pub struct ShaderDatabase(Arc<ShaderDatabaseInner>);
+
+#[derive(Debug)]
+struct ShaderDatabaseInner {
+    programs: BTreeMap<u32, OnceLock<ShaderProgram>>,
+    // Only present for databases loaded from a file.
+    indexed: Option<io::ShaderDatabaseIndexed>,
+}
+
+impl PartialEq for ShaderDatabase {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0) || self.programs().eq(other.programs())
+    }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Default)]
 pub struct ShaderProgram {
     /// Indices into [exprs](#structfield.exprs) for values assigned to a fragment output.
     pub output_dependencies: IndexMap<SmolStr, usize>,
@@ -46,39 +77,169 @@ pub struct ShaderProgram {
 
 impl ShaderDatabase {
     /// Load the database data from `path`.
+    ///
+    /// Programs are decoded on first access by methods like [Self::get_shader].
+    /// Invalid indices in the file return an error here instead of when decoding.
     pub fn from_file<P: AsRef<Path>>(path: P) -> BinResult<Self> {
-        // Store non indexed programs to avoid converting an indexed program more than once.
         let indexed = io::ShaderDatabaseIndexed::from_file(path)?;
-        Ok(Self {
-            programs: indexed.programs(),
-        })
+        Ok(Self(Arc::new(ShaderDatabaseInner {
+            programs: indexed
+                .program_ids()
+                .map(|id| (id, OnceLock::new()))
+                .collect(),
+            indexed: Some(indexed),
+        })))
     }
 
     /// Serialize and save the database data to `path`.
     pub fn save<P: AsRef<Path>>(&self, path: P) -> BinResult<()> {
-        let indexed = io::ShaderDatabaseIndexed::from_programs(&self.programs);
-        indexed.save(path)?;
-        Ok(())
+        match &self.0.indexed {
+            // Avoid decoding programs if the data is unchanged.
+            Some(indexed) => indexed.save(path),
+            None => io::ShaderDatabaseIndexed::from_programs(self.programs()).save(path),
+        }
     }
 
     pub fn get_shader(&self, shader_id: u32) -> Option<&ShaderProgram> {
-        self.programs.get(&shader_id)
+        let program = self.0.programs.get(&shader_id)?;
+        Some(self.decode(shader_id, program))
+    }
+
+    /// All programs and their shader IDs in ascending order by ID.
+    ///
+    /// This decodes any programs not previously accessed.
+    pub fn programs(&self) -> impl Iterator<Item = (u32, &ShaderProgram)> {
+        self.0
+            .programs
+            .iter()
+            .map(|(id, p)| (*id, self.decode(*id, p)))
+    }
+
+    /// All shader IDs in ascending order without decoding any programs.
+    pub fn shader_ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.0.programs.keys().copied()
+    }
+
+    pub fn contains_shader(&self, shader_id: u32) -> bool {
+        self.0.programs.contains_key(&shader_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.programs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.programs.is_empty()
     }
 
     /// Create the internal database representation from non indexed data.
     pub fn from_programs(programs: BTreeMap<u32, ShaderProgram>) -> Self {
-        Self { programs }
+        Self(Arc::new(ShaderDatabaseInner {
+            programs: programs
+                .into_iter()
+                .map(|(id, p)| (id, OnceLock::from(p)))
+                .collect(),
+            indexed: None,
+        }))
+    }
+
+    fn decode<'a>(
+        &'a self,
+        shader_id: u32,
+        program: &'a OnceLock<ShaderProgram>,
+    ) -> &'a ShaderProgram {
+        program.get_or_init(|| {
+            // Programs are only uninitialized for databases loaded from a file.
+            // The indices are checked when loading, so decoding should always succeed.
+            self.0
+                .indexed
+                .as_ref()
+                .and_then(|indexed| indexed.program(shader_id))
+                .expect("uninitialized programs should be in the indexed database")
+        })
+    }
+
+    /// The shader IDs for programs that sample the texture for sampler `name`.
+    pub fn programs_sampling_texture<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = u32> + 'a {
+        self.programs_filtered(move |p| p.samples_texture(name))
+    }
+
+    /// The shader IDs for programs that use the attribute `name`.
+    pub fn programs_using_attribute<'a>(&'a self, name: &'a str) -> impl Iterator<Item = u32> + 'a {
+        self.programs_filtered(move |p| p.uses_attribute(name))
+    }
+
+    /// The shader IDs for programs with unrecognized operations.
+    pub fn programs_with_unk_operations(&self) -> impl Iterator<Item = u32> + '_ {
+        self.programs_filtered(|p| p.has_unk_operations())
+    }
+
+    fn programs_filtered<'a>(
+        &'a self,
+        f: impl Fn(&ShaderProgram) -> bool + 'a,
+    ) -> impl Iterator<Item = u32> + 'a {
+        self.programs().filter(move |(_, p)| f(p)).map(|(id, _)| id)
     }
 }
 
 impl ShaderProgram {
-    pub fn parameter_value(&self, parameter: &Parameter) -> Option<f32> {
-        // TODO: Is there a better way to pass global parameters to consumers like Python?
-        uniform_parameter_value(self, parameter)
+    /// The value for a global uniform buffer parameter like `FB1.fog_color.x` using `globals`.
+    pub fn parameter_value(&self, parameter: &Parameter, globals: &RenderGlobals) -> Option<f32> {
+        uniform_parameter_value(self, parameter, globals)
+    }
+
+    /// Returns `true` if any expression samples the texture for sampler `name` like `"normalSampler"`.
+    pub fn samples_texture(&self, name: &str) -> bool {
+        self.exprs.iter().any(|e| match e {
+            OutputExpr::Value(Value::Texture(t)) => t.name == name,
+            _ => false,
+        }) || self.exprs_xyz.iter().any(|e| match e {
+            OutputExprXyz::Value(ValueXyz::Texture(t)) => t.name == name,
+            _ => false,
+        })
+    }
+
+    /// Returns `true` if the program has the attribute `name` like `"a_Color"`
+    /// or any expression uses the attribute.
+    pub fn uses_attribute(&self, name: &str) -> bool {
+        self.attributes.iter().any(|a| a == name)
+            || self.exprs.iter().any(|e| match e {
+                OutputExpr::Value(Value::Attribute(a)) => a.name == name,
+                _ => false,
+            })
+            || self.exprs_xyz.iter().any(|e| match e {
+                OutputExprXyz::Value(ValueXyz::Attribute(a)) => a.name == name,
+                _ => false,
+            })
+    }
+
+    /// Returns `true` if any expression uses an operation that could not be recognized.
+    pub fn has_unk_operations(&self) -> bool {
+        self.exprs.iter().any(|e| {
+            matches!(
+                e,
+                OutputExpr::Func {
+                    op: Operation::Unk,
+                    ..
+                }
+            )
+        }) || self.exprs_xyz.iter().any(|e| {
+            matches!(
+                e,
+                OutputExprXyz::Func {
+                    op: OperationXyz::Unk,
+                    ..
+                }
+            )
+        })
     }
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, FromRepr, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Operation {
     #[default]
     Unk,
@@ -146,6 +307,7 @@ impl std::fmt::Display for Operation {
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, FromRepr, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum OperationXyz {
     #[default]
     Unk,
@@ -190,3 +352,81 @@ pub enum OperationXyz {
     TintColor,
     NegReflect,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::database::test_utils::{attribute, float, func, program, texture};
+
+    #[test]
+    fn database_queries() {
+        let a = program(
+            vec![
+                attribute("a_TexCoord0", 'x'),
+                texture("normalSampler", &[0, 0], 'x'),
+            ],
+            &[("out_attr0.x", 1)],
+        );
+        let b = program(
+            vec![
+                attribute("a_Color", 'x'),
+                float(1.0),
+                func(Operation::Unk, &[0, 1]),
+            ],
+            &[("out_attr0.x", 2)],
+        );
+        let database = ShaderDatabase::from_programs([(1, a), (2, b)].into_iter().collect());
+
+        assert_eq!(2, database.len());
+        assert_eq!(
+            vec![1, 2],
+            database.programs().map(|(id, _)| id).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![1],
+            database
+                .programs_sampling_texture("normalSampler")
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![2],
+            database
+                .programs_using_attribute("a_Color")
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![2],
+            database.programs_with_unk_operations().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn decode_programs_on_access() {
+        let programs = BTreeMap::from([
+            (
+                1,
+                program(vec![attribute("a_Color", 'x')], &[("out_attr0.x", 0)]),
+            ),
+            (
+                2,
+                program(vec![attribute("a_Normal", 'x')], &[("out_attr0.x", 0)]),
+            ),
+        ]);
+        let indexed =
+            io::ShaderDatabaseIndexed::from_programs(programs.iter().map(|(id, p)| (*id, p)));
+        let database = ShaderDatabase(Arc::new(ShaderDatabaseInner {
+            programs: indexed
+                .program_ids()
+                .map(|id| (id, OnceLock::new()))
+                .collect(),
+            indexed: Some(indexed),
+        }));
+
+        let shared = database.clone();
+        assert_eq!(programs.get(&2), shared.get_shader(2));
+        assert!(database.0.programs[&1].get().is_none());
+        assert!(database.0.programs[&2].get().is_some());
+        assert_eq!(ShaderDatabase::from_programs(programs), database);
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use smol_str::SmolStr;
+
+use crate::database::{OutputExpr, OutputExprXyz, ShaderDatabase, ShaderProgram, Value, ValueXyz};
+
+/// Differences between two [ShaderDatabase] found by [ShaderDatabase::diff].
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct DatabaseDiff {
+    /// Shader IDs only in the new database.
+    pub added: Vec<u32>,
+    /// Shader IDs only in the old database.
+    pub removed: Vec<u32>,
+    /// Programs in both databases with different outputs.
+    pub changed: BTreeMap<u32, ProgramDiff>,
+}
+
+impl DatabaseDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Differences between the outputs of two [ShaderProgram].
+///
+/// Outputs include both scalar outputs like `out_attr0.x` and merged outputs like `out_attr0.xyz`.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ProgramDiff {
+    pub added_outputs: Vec<SmolStr>,
+    pub removed_outputs: Vec<SmolStr>,
+    /// Outputs in both programs with a different expression.
+    pub changed_outputs: Vec<SmolStr>,
+}
+
+impl ProgramDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_outputs.is_empty()
+            && self.removed_outputs.is_empty()
+            && self.changed_outputs.is_empty()
+    }
+}
+
+impl ShaderDatabase {
+    /// Compare the programs in this database with the programs in `new`.
+    ///
+    /// Outputs are compared by the structure of their expressions,
+    /// so programs are unchanged if only the order of expressions changes.
+    pub fn diff(&self, new: &ShaderDatabase) -> DatabaseDiff {
+        let added = new
+            .shader_ids()
+            .filter(|id| !self.contains_shader(*id))
+            .collect();
+
+        let removed = self
+            .shader_ids()
+            .filter(|id| !new.contains_shader(*id))
+            .collect();
+
+        let changed = self
+            .programs()
+            .filter_map(|(id, old)| {
+                let diff = old.diff(new.get_shader(id)?);
+                (!diff.is_empty()).then_some((id, diff))
+            })
+            .collect();
+
+        DatabaseDiff {
+            added,
+            removed,
+            changed,
+        }
+    }
+}
+
+impl ShaderProgram {
+    /// Compare the outputs of this program with the outputs of `new`.
+    pub fn diff(&self, new: &ShaderProgram) -> ProgramDiff {
+        let mut diff = ProgramDiff::default();
+        let mut comparison = ExprComparison::new(self, new);
+
+        let old_outputs: BTreeSet<_> = self
+            .output_dependencies
+            .keys()
+            .chain(self.output_dependencies_xyz.keys())
+            .collect();
+        let new_outputs: BTreeSet<_> = new
+            .output_dependencies
+            .keys()
+            .chain(new.output_dependencies_xyz.keys())
+            .collect();
+
+        for name in old_outputs.union(&new_outputs) {
+            if !new_outputs.contains(name) {
+                diff.removed_outputs.push((*name).clone());
+            } else if !old_outputs.contains(name) {
+                diff.added_outputs.push((*name).clone());
+            } else {
+                let is_equal = match (
+                    self.output_dependencies.get(*name),
+                    new.output_dependencies.get(*name),
+                    self.output_dependencies_xyz.get(*name),
+                    new.output_dependencies_xyz.get(*name),
+                ) {
+                    (Some(a), Some(b), _, _) => comparison.expr_eq(*a, *b),
+                    (_, _, Some(a), Some(b)) => comparison.expr_xyz_eq(*a, *b),
+                    _ => false,
+                };
+                if !is_equal {
+                    diff.changed_outputs.push((*name).clone());
+                }
+            }
+        }
+
+        diff
+    }
+}
+
+struct ExprComparison<'a> {
+    old: &'a ShaderProgram,
+    new: &'a ShaderProgram,
+    // Expressions are shared, so cache results to avoid exponential work.
+    equal: BTreeMap<(usize, usize), bool>,
+    equal_xyz: BTreeMap<(usize, usize), bool>,
+}
+
+impl<'a> ExprComparison<'a> {
+    fn new(old: &'a ShaderProgram, new: &'a ShaderProgram) -> Self {
+        Self {
+            old,
+            new,
+            equal: BTreeMap::new(),
+            equal_xyz: BTreeMap::new(),
+        }
+    }
+
+    fn exprs_eq(&mut self, a: &[usize], b: &[usize]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| self.expr_eq(*a, *b))
+    }
+
+    fn exprs_xyz_eq(&mut self, a: &[usize], b: &[usize]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| self.expr_xyz_eq(*a, *b))
+    }
+
+    fn expr_eq(&mut self, a: usize, b: usize) -> bool {
+        if let Some(is_equal) = self.equal.get(&(a, b)) {
+            return *is_equal;
+        }
+
+        let is_equal = match (self.old.exprs.get(a), self.new.exprs.get(b)) {
+            (Some(OutputExpr::Value(a)), Some(OutputExpr::Value(b))) => match (a, b) {
+                (Value::Texture(a), Value::Texture(b)) => {
+                    a.name == b.name
+                        && a.channel == b.channel
+                        && self.exprs_eq(&a.texcoords, &b.texcoords)
+                }
+                _ => a == b,
+            },
+            (
+                Some(OutputExpr::Func { op: op_a, args: a }),
+                Some(OutputExpr::Func { op: op_b, args: b }),
+            ) => op_a == op_b && self.exprs_eq(a, b),
+            _ => false,
+        };
+
+        self.equal.insert((a, b), is_equal);
+        is_equal
+    }
+
+    fn expr_xyz_eq(&mut self, a: usize, b: usize) -> bool {
+        if let Some(is_equal) = self.equal_xyz.get(&(a, b)) {
+            return *is_equal;
+        }
+
+        let is_equal = match (self.old.exprs_xyz.get(a), self.new.exprs_xyz.get(b)) {
+            (Some(OutputExprXyz::Value(a)), Some(OutputExprXyz::Value(b))) => match (a, b) {
+                (ValueXyz::Texture(a), ValueXyz::Texture(b)) => {
+                    a.name == b.name
+                        && a.channel == b.channel
+                        && self.exprs_eq(&a.texcoords, &b.texcoords)
+                }
+                _ => a == b,
+            },
+            (
+                Some(OutputExprXyz::Func {
+                    op: op_a,
+                    args: a,
+                    channel: channel_a,
+                }),
+                Some(OutputExprXyz::Func {
+                    op: op_b,
+                    args: b,
+                    channel: channel_b,
+                }),
+            ) => op_a == op_b && channel_a == channel_b && self.exprs_xyz_eq(a, b),
+            _ => false,
+        };
+
+        self.equal_xyz.insert((a, b), is_equal);
+        is_equal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::database::{
+        Operation,
+        test_utils::{attribute, float, func, program, texture},
+    };
+
+    #[test]
+    fn diff_reordered_exprs_unchanged() {
+        let old = program(
+            vec![float(1.0), float(2.0), func(Operation::Add, &[0, 1])],
+            &[("out_attr0.x", 2)],
+        );
+        let new = program(
+            vec![func(Operation::Add, &[1, 2]), float(1.0), float(2.0)],
+            &[("out_attr0.x", 0)],
+        );
+        assert!(old.diff(&new).is_empty());
+    }
+
+    #[test]
+    fn diff_outputs() {
+        let old = program(
+            vec![float(1.0), float(2.0)],
+            &[("out_attr0.x", 0), ("out_attr0.y", 1)],
+        );
+        let new = program(
+            vec![float(1.0), float(3.0)],
+            &[("out_attr0.x", 1), ("out_attr0.z", 0)],
+        );
+        assert_eq!(
+            ProgramDiff {
+                added_outputs: vec!["out_attr0.z".into()],
+                removed_outputs: vec!["out_attr0.y".into()],
+                changed_outputs: vec!["out_attr0.x".into()],
+            },
+            old.diff(&new)
+        );
+    }
+
+    #[test]
+    fn diff_databases() {
+        let a = program(vec![float(1.0)], &[("out_attr0.x", 0)]);
+        let b = program(vec![float(2.0)], &[("out_attr0.x", 0)]);
+        let old =
+            ShaderDatabase::from_programs([(1, a.clone()), (2, a.clone())].into_iter().collect());
+        let new = ShaderDatabase::from_programs([(2, b), (3, a)].into_iter().collect());
+
+        let diff = old.diff(&new);
+        assert_eq!(vec![3], diff.added);
+        assert_eq!(vec![1], diff.removed);
+        assert_eq!(vec![2], diff.changed.keys().copied().collect::<Vec<_>>());
+        assert!(new.diff(&new).is_empty());
+    }
+
+    #[test]
+    fn diff_texture_coords() {
+        let old = program(
+            vec![
+                attribute("a_TexCoord0", 'x'),
+                attribute("a_TexCoord1", 'x'),
+                texture("colorSampler", &[0, 0], 'x'),
+            ],
+            &[("out_attr0.x", 2)],
+        );
+        let new = program(
+            vec![
+                attribute("a_TexCoord0", 'x'),
+                attribute("a_TexCoord1", 'x'),
+                texture("colorSampler", &[1, 1], 'x'),
+            ],
+            &[("out_attr0.x", 2)],
+        );
+        assert_eq!(
+            vec![SmolStr::from("out_attr0.x")],
+            old.diff(&new).changed_outputs
+        );
+    }
+}
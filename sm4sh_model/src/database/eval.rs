@@ -0,0 +1,498 @@
+use std::collections::BTreeMap;
+
+use glam::{Mat4, Vec3, Vec4, vec3};
+use smol_str::{SmolStr, format_smolstr};
+use xc3_shader::expr::{OutputExpr, Parameter, Value};
+
+use crate::{
+    NudMaterial,
+    database::{Operation, ShaderProgram},
+    globals::RenderGlobals,
+    material::property_info,
+};
+
+/// Per pixel inputs for evaluating a [ShaderProgram] on the CPU.
+pub struct EvalInputs<'a> {
+    /// Values for attributes like `a_Normal`, `a_TexCoord0`, `a_Color`, or `eye`.
+    pub attributes: BTreeMap<SmolStr, Vec4>,
+    /// The material for `MC` and `MC_EFFECT` parameters.
+    /// Parameters use the defaults from [PROPERTIES](crate::material::PROPERTIES) if `None`.
+    pub material: Option<&'a NudMaterial>,
+    /// The world to view matrix used for sphere map coordinates.
+    pub view: Mat4,
+    /// Values for global uniform buffer parameters like `FB0` or `FB1`.
+    pub globals: RenderGlobals,
+    /// Sample the texture for a sampler name like `colorSampler` at the given coordinates.
+    pub sample_texture: &'a dyn Fn(&str, &[f32]) -> Vec4,
+}
+
+impl<'a> EvalInputs<'a> {
+    /// Inputs with no attributes, default material and global parameters, and an identity view matrix.
+    pub fn new(sample_texture: &'a dyn Fn(&str, &[f32]) -> Vec4) -> Self {
+        Self {
+            attributes: BTreeMap::new(),
+            material: None,
+            view: Mat4::IDENTITY,
+            globals: RenderGlobals::default(),
+            sample_texture,
+        }
+    }
+
+    fn attribute(&self, name: &str) -> Option<Vec4> {
+        self.attributes.get(name).copied()
+    }
+}
+
+impl ShaderProgram {
+    /// Evaluate the value for output `name` like `"out_attr0.x"`.
+    ///
+    /// Returns `None` if the output is not present
+    /// or depends on unsupported operations or missing inputs.
+    pub fn evaluate(&self, name: &str, inputs: &EvalInputs) -> Option<f32> {
+        let i = *self.output_dependencies.get(name)?;
+        Evaluator::new(self, inputs).value(i)
+    }
+
+    /// Evaluate the RGBA color for the first fragment output.
+    ///
+    /// Outputs that can't be evaluated use 0.0 for RGB and 1.0 for alpha.
+    pub fn evaluate_color(&self, inputs: &EvalInputs) -> Vec4 {
+        let mut evaluator = Evaluator::new(self, inputs);
+        let mut output = |c: char, default: f32| {
+            self.output_dependencies
+                .get(&format_smolstr!("out_attr0.{c}"))
+                .and_then(|i| evaluator.value(*i))
+                .unwrap_or(default)
+        };
+        Vec4::new(
+            output('x', 0.0),
+            output('y', 0.0),
+            output('z', 0.0),
+            output('w', 1.0),
+        )
+    }
+}
+
+struct Evaluator<'a> {
+    program: &'a ShaderProgram,
+    inputs: &'a EvalInputs<'a>,
+    // Expressions are shared between outputs, so cache values to avoid duplicate work.
+    values: Vec<Option<Option<f32>>>,
+}
+
+impl<'a> Evaluator<'a> {
+    fn new(program: &'a ShaderProgram, inputs: &'a EvalInputs<'a>) -> Self {
+        Self {
+            program,
+            inputs,
+            values: vec![None; program.exprs.len()],
+        }
+    }
+
+    fn value(&mut self, i: usize) -> Option<f32> {
+        if let Some(value) = self.values.get(i)? {
+            return *value;
+        }
+        let value = match self.program.exprs.get(i)? {
+            OutputExpr::Value(v) => self.eval_value(v),
+            OutputExpr::Func { op, args } => self.eval_func(*op, args),
+        };
+        self.values[i] = Some(value);
+        value
+    }
+
+    fn values<const N: usize>(&mut self, args: &[usize]) -> Option<[f32; N]> {
+        let mut values = [0.0; N];
+        for (value, arg) in values.iter_mut().zip(args.get(..N)?) {
+            *value = self.value(*arg)?;
+        }
+        Some(values)
+    }
+
+    fn vec3(&mut self, args: &[usize]) -> Option<Vec3> {
+        self.values::<3>(args).map(Vec3::from_array)
+    }
+
+    fn eval_value(&mut self, value: &Value) -> Option<f32> {
+        match value {
+            Value::Int(i) => Some(*i as f32),
+            Value::Float(f) => Some(f.0),
+            Value::Parameter(p) => self.parameter(p),
+            Value::Texture(t) => {
+                let mut coords = Vec::new();
+                for i in &t.texcoords {
+                    coords.push(self.value(*i)?);
+                }
+                let color = (self.inputs.sample_texture)(&t.name, &coords);
+                Some(channel(color, t.channel))
+            }
+            Value::Attribute(a) => self
+                .inputs
+                .attribute(&a.name)
+                .map(|v| channel(v, a.channel)),
+        }
+    }
+
+    fn parameter(&self, p: &Parameter) -> Option<f32> {
+        match p.name.as_str() {
+            "MC" | "MC_EFFECT" => {
+                // TODO: Are any material parameters arrays?
+                let name = if p.field.starts_with("NU_") {
+                    p.field.to_string()
+                } else {
+                    format!("NU_{}", p.field)
+                };
+                let value = match self.inputs.material {
+                    Some(material) => material.property_or_default(&name),
+                    None => property_info(&name)
+                        .map(|p| Vec4::from_array(p.default))
+                        .unwrap_or_default(),
+                };
+                Some(channel(value, p.channel))
+            }
+            _ => self.program.parameter_value(p, &self.inputs.globals),
+        }
+    }
+
+    fn eval_func(&mut self, op: Operation, args: &[usize]) -> Option<f32> {
+        let inputs = self.inputs;
+        let input = |name| inputs.attribute(name).map(|v| v.truncate());
+        match op {
+            Operation::Unk => None,
+            Operation::Add => self.values(args).map(|[a, b]| a + b),
+            Operation::Sub => self.values(args).map(|[a, b]| a - b),
+            Operation::Mul => self.values(args).map(|[a, b]| a * b),
+            Operation::Div => self.values(args).map(|[a, b]| a / b),
+            Operation::Mix => self.values(args).map(|[a, b, t]| a + (b - a) * t),
+            Operation::Clamp => self.values(args).map(|[a, min, max]| a.max(min).min(max)),
+            Operation::Min => self.values(args).map(|[a, b]| a.min(b)),
+            Operation::Max => self.values(args).map(|[a, b]| a.max(b)),
+            Operation::Abs => self.values(args).map(|[a]| a.abs()),
+            Operation::Floor => self.values(args).map(|[a]| a.floor()),
+            Operation::Power => self.values(args).map(|[a, b]| a.powf(b)),
+            Operation::Sqrt => self.values(args).map(|[a]| a.sqrt()),
+            Operation::InverseSqrt => self.values(args).map(|[a]| 1.0 / a.sqrt()),
+            Operation::Fma => self.values(args).map(|[a, b, c]| a * b + c),
+            Operation::Dot3 => self
+                .values::<6>(args)
+                .map(|v| vec3(v[0], v[1], v[2]).dot(vec3(v[3], v[4], v[5]))),
+            Operation::Dot4 => self
+                .values::<8>(args)
+                .map(|v| Vec4::from_slice(&v[..4]).dot(Vec4::from_slice(&v[4..]))),
+            Operation::Sin => self.values(args).map(|[a]| a.sin()),
+            Operation::Cos => self.values(args).map(|[a]| a.cos()),
+            Operation::Exp2 => self.values(args).map(|[a]| a.exp2()),
+            Operation::Log2 => self.values(args).map(|[a]| a.log2()),
+            Operation::Fract => self.values(args).map(|[a]| a - a.floor()),
+            Operation::IntBitsToFloat => {
+                self.values(args).map(|[a]| f32::from_bits(a as i32 as u32))
+            }
+            Operation::FloatBitsToInt => self.values(args).map(|[a]| a.to_bits() as i32 as f32),
+            Operation::Select => self
+                .values(args)
+                .map(|[condition, a, b]| if condition != 0.0 { a } else { b }),
+            Operation::Negate => self.values(args).map(|[a]| -a),
+            Operation::Equal => self.values(args).map(|[a, b]| bool_value(a == b)),
+            Operation::NotEqual => self.values(args).map(|[a, b]| bool_value(a != b)),
+            Operation::Less => self.values(args).map(|[a, b]| bool_value(a < b)),
+            Operation::Greater => self.values(args).map(|[a, b]| bool_value(a > b)),
+            Operation::LessEqual => self.values(args).map(|[a, b]| bool_value(a <= b)),
+            Operation::GreaterEqual => self.values(args).map(|[a, b]| bool_value(a >= b)),
+            Operation::NormalMapX | Operation::NormalMapY | Operation::NormalMapZ => {
+                let tangent = input("a_Tangent")?;
+                let bitangent = input("a_Binormal")?;
+                let normal = input("a_Normal")?;
+                let normal_map = self.vec3(args)?;
+                let value = apply_normal_map(normal_map, tangent, bitangent, normal);
+                Some(vec3_channel(op, value))
+            }
+            Operation::NormalizeX | Operation::NormalizeY | Operation::NormalizeZ => {
+                let value = self.vec3(args)?.normalize();
+                Some(vec3_channel(op, value))
+            }
+            Operation::SphereMapCoordX | Operation::SphereMapCoordY => {
+                let position = input("a_Position")?;
+                let normal = input("a_Normal")?;
+                let [param] = self.values(args)?;
+                let coords = sphere_map_coords(position, normal, param, self.inputs.view);
+                Some(if op == Operation::SphereMapCoordX {
+                    coords.x
+                } else {
+                    coords.y
+                })
+            }
+            // TODO: Don't assume attributes are already in world space.
+            Operation::LocalToWorldPointX | Operation::LocalToWorldVectorX => {
+                self.value(*args.first()?)
+            }
+            Operation::LocalToWorldPointY | Operation::LocalToWorldVectorY => {
+                self.value(*args.get(1)?)
+            }
+            Operation::LocalToWorldPointZ | Operation::LocalToWorldVectorZ => {
+                self.value(*args.get(2)?)
+            }
+            Operation::VarianceShadow => self
+                .values(args)
+                .map(|[m1, m2, z, offset]| variance_shadow(m1, m2, z, offset)),
+            Operation::BlinnPhongSpecular => self.values::<10>(args).map(|v| {
+                blinn_phong_specular(
+                    vec3(v[0], v[1], v[2]),
+                    vec3(v[3], v[4], v[5]),
+                    vec3(v[6], v[7], v[8]),
+                    v[9],
+                )
+            }),
+            Operation::AnisotropicSpecular => self.values::<11>(args).map(|v| {
+                anisotropic_specular(
+                    vec3(v[0], v[1], v[2]),
+                    vec3(v[3], v[4], v[5]),
+                    vec3(v[6], v[7], v[8]),
+                    [v[9], v[10]],
+                )
+            }),
+            Operation::Fresnel => self
+                .values::<7>(args)
+                .map(|v| fresnel(vec3(v[0], v[1], v[2]), vec3(v[3], v[4], v[5]), v[6])),
+            Operation::TintColorX | Operation::TintColorY | Operation::TintColorZ => {
+                let color = self.vec3(args)?;
+                let [amount] = self.values(args.get(3..)?)?;
+                Some(vec3_channel(op, tint_color(color, amount)))
+            }
+            Operation::NegReflectX | Operation::NegReflectY | Operation::NegReflectZ => {
+                let i = self.vec3(args)?;
+                let n = self.vec3(args.get(3..)?)?;
+                Some(vec3_channel(op, -reflect(i, n)))
+            }
+        }
+    }
+}
+
+fn channel(value: Vec4, c: Option<char>) -> f32 {
+    match c {
+        Some('y') => value.y,
+        Some('z') => value.z,
+        Some('w') => value.w,
+        _ => value.x,
+    }
+}
+
+fn vec3_channel(op: Operation, value: Vec3) -> f32 {
+    match op {
+        Operation::NormalMapY
+        | Operation::NormalizeY
+        | Operation::TintColorY
+        | Operation::NegReflectY => value.y,
+        Operation::NormalMapZ
+        | Operation::NormalizeZ
+        | Operation::TintColorZ
+        | Operation::NegReflectZ => value.z,
+        _ => value.x,
+    }
+}
+
+fn bool_value(b: bool) -> f32 {
+    if b { 1.0 } else { 0.0 }
+}
+
+fn reflect(i: Vec3, n: Vec3) -> Vec3 {
+    i - 2.0 * n.dot(i) * n
+}
+
+// The functions below should match the helper functions in the wgpu model shader.
+fn apply_normal_map(normal_map: Vec3, tangent: Vec3, bitangent: Vec3, normal: Vec3) -> Vec3 {
+    let offset = 1.0 / (255.0 * 2.0);
+    let v = (normal_map + offset) * 2.0 - 1.0;
+    (tangent * v.x + bitangent * v.y + normal * v.z).normalize()
+}
+
+fn sphere_map_coords(position: Vec3, normal: Vec3, param: f32, view: Mat4) -> Vec3 {
+    let adjusted_normal = normal.normalize() + position * param;
+    let view_normal = view.transform_vector3(adjusted_normal);
+    (-param * 0.25 + 0.5) * vec3(view_normal.x, view_normal.y, 0.0) + 0.5
+}
+
+fn variance_shadow(m1: f32, m2: f32, light_position_z: f32, offset: f32) -> f32 {
+    let current_depth = light_position_z.min(1.0);
+    let sigma2 = (m2 - m1 * m1 + offset).clamp(0.0, 1.0);
+    let t_dif = (current_depth - m1).max(0.0);
+    let shadow = sigma2 / (sigma2 + t_dif * t_dif);
+    shadow.powf(4.0)
+}
+
+fn blinn_phong_specular(normal: Vec3, light_dir: Vec3, eye: Vec3, exponent: f32) -> f32 {
+    let h = (eye - light_dir).normalize();
+    normal.dot(h).max(0.001).powf(exponent)
+}
+
+fn anisotropic_specular(normal: Vec3, tangent: Vec3, eye: Vec3, params: [f32; 2]) -> f32 {
+    let param_x = params[0] * 3.0;
+    let param_y = params[1] * 3.0;
+
+    let dot_eye_n = eye.dot(normal);
+    let dot_eye_n2 = dot_eye_n * dot_eye_n;
+
+    let b = (eye - normal * dot_eye_n).normalize();
+    let dot_tangent_b = tangent.dot(b);
+    let dot_tangent_b2 = dot_tangent_b * dot_tangent_b;
+
+    let x_term = dot_tangent_b2 / (param_x * param_x);
+    let y_term = (1.0 - dot_tangent_b2) / (param_y * param_y);
+
+    ((dot_eye_n2 - 1.0) / dot_eye_n2 * (x_term + y_term)).exp()
+}
+
+fn fresnel(normal: Vec3, eye: Vec3, param: f32) -> f32 {
+    let fresnel = 1.0 - eye.dot(normal).clamp(0.0, 1.0);
+    fresnel.powf(1.0 + param)
+}
+
+fn tint_color(color: Vec3, amount: f32) -> Vec3 {
+    let max_component = color.max_element();
+    (color - max_component) * amount + 1.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        AlphaFunc, CullMode, DstFactor, NudProperty, SrcFactor,
+        database::test_utils::{attribute, float, func, program, texture},
+    };
+
+    fn no_texture(_: &str, _: &[f32]) -> Vec4 {
+        Vec4::ZERO
+    }
+
+    #[test]
+    fn evaluate_arithmetic() {
+        let program = program(
+            vec![
+                float(2.0),
+                float(3.0),
+                float(0.5),
+                func(Operation::Fma, &[0, 1, 2]),
+                func(Operation::Greater, &[3, 1]),
+                func(Operation::Select, &[4, 0, 1]),
+            ],
+            &[("out_attr0.x", 3), ("out_attr0.y", 5)],
+        );
+        let inputs = EvalInputs::new(&no_texture);
+        assert_eq!(Some(6.5), program.evaluate("out_attr0.x", &inputs));
+        assert_eq!(Some(2.0), program.evaluate("out_attr0.y", &inputs));
+        assert_eq!(None, program.evaluate("out_attr0.z", &inputs));
+    }
+
+    #[test]
+    fn evaluate_fresnel() {
+        let mut exprs: Vec<_> = "xyz".chars().map(|c| attribute("a_Normal", c)).collect();
+        exprs.extend("xyz".chars().map(|c| attribute("eye", c)));
+        exprs.push(float(1.0));
+        exprs.push(func(Operation::Fresnel, &[0, 1, 2, 3, 4, 5, 6]));
+        let program = program(exprs, &[("out_attr0.x", 7)]);
+
+        let mut inputs = EvalInputs::new(&no_texture);
+        inputs.attributes.insert("a_Normal".into(), Vec4::Z);
+        inputs.attributes.insert("eye".into(), Vec4::Z);
+        assert_eq!(Some(0.0), program.evaluate("out_attr0.x", &inputs));
+
+        // Edges facing away from the viewer have full fresnel.
+        inputs.attributes.insert("eye".into(), Vec4::X);
+        assert_eq!(Some(1.0), program.evaluate("out_attr0.x", &inputs));
+    }
+
+    #[test]
+    fn evaluate_blinn_phong_specular() {
+        let mut exprs: Vec<_> = "xyz".chars().map(|c| attribute("a_Normal", c)).collect();
+        exprs.extend("xyz".chars().map(|c| attribute("light", c)));
+        exprs.extend("xyz".chars().map(|c| attribute("eye", c)));
+        exprs.push(float(8.0));
+        exprs.push(func(
+            Operation::BlinnPhongSpecular,
+            &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9],
+        ));
+        let program = program(exprs, &[("out_attr0.x", 10)]);
+
+        let mut inputs = EvalInputs::new(&no_texture);
+        inputs.attributes.insert("a_Normal".into(), Vec4::Z);
+        inputs.attributes.insert("light".into(), Vec4::NEG_Z);
+        inputs.attributes.insert("eye".into(), Vec4::Z);
+        assert_eq!(Some(1.0), program.evaluate("out_attr0.x", &inputs));
+
+        inputs.attributes.insert("a_Normal".into(), Vec4::X);
+        assert_eq!(
+            Some(0.001f32.powf(8.0)),
+            program.evaluate("out_attr0.x", &inputs)
+        );
+    }
+
+    #[test]
+    fn evaluate_color_texture_material() {
+        let program = program(
+            vec![
+                attribute("a_TexCoord0", 'x'),
+                attribute("a_TexCoord0", 'y'),
+                texture("colorSampler", &[0, 1], 'y'),
+                OutputExpr::Value(Value::Parameter(Parameter {
+                    name: "MC".into(),
+                    field: "NU_colorGain".into(),
+                    index: None,
+                    channel: Some('x'),
+                })),
+                func(Operation::Mul, &[2, 3]),
+            ],
+            &[("out_attr0.x", 4)],
+        );
+        let material = NudMaterial {
+            shader_id: 0,
+            src_factor: SrcFactor::One,
+            dst_factor: DstFactor::Zero,
+            alpha_func: AlphaFunc::Disabled,
+            alpha_test_ref: 0,
+            cull_mode: CullMode::Disabled,
+            textures: Vec::new(),
+            properties: vec![NudProperty {
+                name: "NU_colorGain".to_string(),
+                values: vec![0.5; 4],
+            }],
+        };
+
+        let sample = |name: &str, coords: &[f32]| {
+            assert_eq!("colorSampler", name);
+            Vec4::new(0.0, coords[0] + coords[1], 0.0, 0.0)
+        };
+        let mut inputs = EvalInputs::new(&sample);
+        inputs
+            .attributes
+            .insert("a_TexCoord0".into(), Vec4::new(0.25, 0.5, 0.0, 0.0));
+        assert_eq!(
+            Vec4::new(0.75, 0.0, 0.0, 1.0),
+            program.evaluate_color(&inputs)
+        );
+
+        inputs.material = Some(&material);
+        assert_eq!(
+            Vec4::new(0.375, 0.0, 0.0, 1.0),
+            program.evaluate_color(&inputs)
+        );
+    }
+
+    #[test]
+    fn evaluate_globals() {
+        let program = program(
+            vec![OutputExpr::Value(Value::Parameter(Parameter {
+                name: "FB1".into(),
+                field: "fogColor".into(),
+                index: None,
+                channel: Some('y'),
+            }))],
+            &[("out_attr0.x", 0)],
+        );
+
+        let mut inputs = EvalInputs::new(&no_texture);
+        assert_eq!(Some(1.0), program.evaluate("out_attr0.x", &inputs));
+
+        inputs.globals.fog_color = Vec4::new(0.0, 0.25, 0.0, 0.0);
+        assert_eq!(Some(0.25), program.evaluate("out_attr0.x", &inputs));
+    }
+}
@@ -15,17 +15,25 @@ use super::{Attribute, Operation, OutputExpr, Parameter, ShaderProgram, Texture,
 type IndexSet<T> = indexmap::IndexSet<T, ahash::RandomState>;
 type IndexMap<K, V> = indexmap::IndexMap<K, V, ahash::RandomState>;
 
-// Create a separate format optimized for storing on disk.
+/// The version of the binary database format written by [ShaderDatabaseIndexed::save].
+///
+/// The version should be incremented for any change to the binary layout after the magic and version.
+/// Readers for previous versions should be kept as separate types
+/// and converted to the current layout in [ShaderDatabaseIndexed::from_file].
+/// Version 1 is the first and only released layout.
+pub const VERSION: u32 = 1;
+
 #[binrw]
-#[derive(Debug, PartialEq, Clone, Default)]
+#[derive(Debug)]
 #[brw(magic(b"SHDB"))]
-pub struct ShaderDatabaseIndexed {
-    // File version numbers should be updated with each release.
-    // This improves the error when parsing an incompatible version.
-    #[br(assert(version == 1))]
-    #[bw(calc = 1)]
+struct Header {
     version: u32,
+}
 
+// Create a separate format optimized for storing on disk.
+#[binrw]
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ShaderDatabaseIndexed {
     // Use an ordered map for consistent ordering.
     #[br(parse_with = parse_map32)]
     #[bw(write_with = write_map32)]
@@ -300,31 +308,167 @@ impl From<Option<xc3_shader::expr::xyz::ChannelXyz>> for ChannelXyz {
 }
 
 impl ShaderDatabaseIndexed {
+    /// Read the database from `path` and check that all indices are valid.
+    ///
+    /// Programs are decoded later, so this avoids panics when accessing programs.
     pub fn from_file<P: AsRef<Path>>(path: P) -> BinResult<Self> {
         let mut reader = Cursor::new(std::fs::read(path)?);
-        reader.read_le()
+        let header: Header = reader.read_le()?;
+        match header.version {
+            VERSION => {
+                let database: Self = reader.read_le()?;
+                database
+                    .validate_indices()
+                    .map_err(|message| binrw::Error::AssertFail {
+                        pos: reader.position(),
+                        message,
+                    })?;
+                Ok(database)
+            }
+            // Convert older versions here once the layout changes.
+            version => Err(binrw::Error::AssertFail {
+                pos: 4,
+                message: if version > VERSION {
+                    format!(
+                        "shader database version {version} is newer than the supported version {VERSION}"
+                    )
+                } else {
+                    format!("unsupported shader database version {version}")
+                },
+            }),
+        }
+    }
+
+    /// Check that all indices are in range and that expressions only depend on earlier expressions.
+    ///
+    /// The writer always adds dependencies first, so this also rules out cycles.
+    fn validate_indices(&self) -> Result<(), String> {
+        for (id, p) in &self.programs {
+            let check = |name, index: &VarInt, len| {
+                check_index(name, index.0, len).map_err(|e| format!("program {id:08X}: {e}"))
+            };
+            for (output, value) in &p.output_dependencies {
+                check("output", output, self.outputs.len())?;
+                check("expr", value, self.output_exprs.len())?;
+            }
+            for (output, value) in &p.output_dependencies_xyz {
+                check("output", output, self.outputs.len())?;
+                check("expr xyz", value, self.output_exprs_xyz.len())?;
+            }
+            for a in &p.attributes {
+                check("attribute name", a, self.attribute_names.len())?;
+            }
+            for s in &p.samplers {
+                check("texture name", s, self.texture_names.len())?;
+            }
+            for p in &p.parameters {
+                check("buffer field name", p, self.buffer_field_names.len())?;
+            }
+        }
+
+        for (i, expr) in self.output_exprs.iter().enumerate() {
+            match expr {
+                OutputExprIndexed::Value(v) => {
+                    check_index("value", v.0, self.values.len())?;
+                    if let ValueIndexed::Texture(t) = &self.values[v.0] {
+                        for coord in &t.texcoords {
+                            check_index("expr", coord.0, i)?;
+                        }
+                    }
+                }
+                OutputExprIndexed::Func { args, .. } => {
+                    for arg in args {
+                        check_index("expr", arg.0, i)?;
+                    }
+                }
+            }
+        }
+
+        for value in &self.values {
+            match value {
+                ValueIndexed::Float(_) | ValueIndexed::Int(_) => (),
+                ValueIndexed::Parameter(p) => {
+                    check_index("buffer name", p.name.0, self.buffer_names.len())?;
+                    check_index(
+                        "buffer field name",
+                        p.field.0,
+                        self.buffer_field_names.len(),
+                    )?;
+                }
+                ValueIndexed::Texture(t) => {
+                    check_index("texture name", t.name.0, self.texture_names.len())?;
+                    for coord in &t.texcoords {
+                        check_index("expr", coord.0, self.output_exprs.len())?;
+                    }
+                }
+                ValueIndexed::Attribute(a) => {
+                    check_index("attribute name", a.name.0, self.attribute_names.len())?;
+                }
+            }
+        }
+
+        for (i, expr) in self.output_exprs_xyz.iter().enumerate() {
+            match expr {
+                OutputExprXyzIndexed::Value(v) => {
+                    check_index("value xyz", v.0, self.values_xyz.len())?;
+                }
+                OutputExprXyzIndexed::Func { args, .. } => {
+                    for arg in args {
+                        check_index("expr xyz", arg.0, i)?;
+                    }
+                }
+            }
+        }
+
+        for value in &self.values_xyz {
+            match value {
+                ValueXyzIndexed::Float(_) => (),
+                ValueXyzIndexed::Parameter(p) => {
+                    check_index("buffer name", p.name.0, self.buffer_names.len())?;
+                    check_index(
+                        "buffer field name",
+                        p.field.0,
+                        self.buffer_field_names.len(),
+                    )?;
+                }
+                ValueXyzIndexed::Texture(t) => {
+                    check_index("texture name", t.name.0, self.texture_names.len())?;
+                    // Texture coordinates use the scalar exprs.
+                    for coord in &t.texcoords {
+                        check_index("expr", coord.0, self.output_exprs.len())?;
+                    }
+                }
+                ValueXyzIndexed::Attribute(a) => {
+                    check_index("attribute name", a.name.0, self.attribute_names.len())?;
+                }
+            }
+        }
+
+        Ok(())
     }
 
     pub fn save<P: AsRef<Path>>(&self, path: P) -> BinResult<()> {
         let mut writer = Cursor::new(Vec::new());
+        writer.write_le(&Header { version: VERSION })?;
         writer.write_le(self)?;
         std::fs::write(path, writer.into_inner())?;
         Ok(())
     }
 
-    pub fn programs(&self) -> BTreeMap<u32, ShaderProgram> {
-        self.programs
-            .iter()
-            .map(|(id, p)| (*id, self.program_from_indexed(p)))
-            .collect()
+    pub fn program_ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.programs.keys().copied()
+    }
+
+    pub fn program(&self, id: u32) -> Option<ShaderProgram> {
+        self.programs.get(&id).map(|p| self.program_from_indexed(p))
     }
 
-    pub fn from_programs(programs: &BTreeMap<u32, ShaderProgram>) -> Self {
+    pub fn from_programs<'a>(programs: impl IntoIterator<Item = (u32, &'a ShaderProgram)>) -> Self {
         let mut database = Self::default();
 
-        for (id, p) in programs.iter() {
+        for (id, p) in programs {
             let program = database.program_indexed(p);
-            database.programs.insert(*id, program);
+            database.programs.insert(id, program);
         }
 
         database
@@ -741,6 +885,16 @@ impl ShaderDatabaseIndexed {
     }
 }
 
+fn check_index(name: &str, index: usize, len: usize) -> Result<(), String> {
+    if index < len {
+        Ok(())
+    } else {
+        Err(format!(
+            "{name} index {index} is out of range for length {len}"
+        ))
+    }
+}
+
 fn add_string(strings: &mut IndexSet<SmolStr>, str: SmolStr) -> VarInt {
     VarInt(strings.insert_full(str).0)
 }
@@ -905,3 +1059,52 @@ where
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::database::test_utils::{float, func, program};
+
+    #[test]
+    fn validate_indices_valid() {
+        let program = program(
+            vec![float(1.0), float(2.0), func(Operation::Add, &[0, 1])],
+            &[("o0.x", 2)],
+        );
+        let database = ShaderDatabaseIndexed::from_programs([(1, &program)]);
+        assert_eq!(Ok(()), database.validate_indices());
+    }
+
+    #[test]
+    fn validate_indices_out_of_range() {
+        let mut database = ShaderDatabaseIndexed::default();
+        database.programs.insert(
+            1,
+            ShaderProgramIndexed {
+                output_dependencies: vec![(VarInt(0), VarInt(0))],
+                output_dependencies_xyz: Vec::new(),
+                attributes: Vec::new(),
+                samplers: Vec::new(),
+                parameters: Vec::new(),
+            },
+        );
+        assert_eq!(
+            Err("program 00000001: output index 0 is out of range for length 0".to_string()),
+            database.validate_indices()
+        );
+    }
+
+    #[test]
+    fn validate_indices_cycle() {
+        let mut database = ShaderDatabaseIndexed::default();
+        database.output_exprs.insert(OutputExprIndexed::Func {
+            op: Operation::Add,
+            args: vec![VarInt(0), VarInt(0)],
+        });
+        assert_eq!(
+            Err("expr index 0 is out of range for length 0".to_string()),
+            database.validate_indices()
+        );
+    }
+}
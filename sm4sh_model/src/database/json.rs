@@ -0,0 +1,731 @@
+//! A human readable JSON representation of a [ShaderDatabase] for reviewing and editing.
+use std::collections::BTreeMap;
+
+use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+use xc3_shader::expr::xyz::{AttributeXyz, ChannelXyz, ParameterXyz, TextureXyz};
+
+use crate::database::{
+    Attribute, IndexMap, Operation, OperationXyz, OutputExpr, OutputExprXyz, Parameter,
+    ShaderDatabase, ShaderProgram, Texture, Value, ValueXyz,
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ShaderDatabaseJson {
+    /// Programs by shader ID as 8 hex digits like `"96000001"`.
+    programs: BTreeMap<String, ShaderProgramJson>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ShaderProgramJson {
+    output_dependencies: IndexMap<SmolStr, usize>,
+    exprs: Vec<OutputExprJson>,
+    output_dependencies_xyz: IndexMap<SmolStr, usize>,
+    exprs_xyz: Vec<OutputExprXyzJson>,
+    attributes: Vec<SmolStr>,
+    samplers: Vec<SmolStr>,
+    parameters: Vec<SmolStr>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum OutputExprJson {
+    Int(i32),
+    Float(F32Json),
+    Parameter {
+        name: SmolStr,
+        field: SmolStr,
+        index: Option<usize>,
+        channel: Option<char>,
+    },
+    Texture {
+        name: SmolStr,
+        texcoords: Vec<usize>,
+        channel: Option<char>,
+    },
+    Attribute {
+        name: SmolStr,
+        channel: Option<char>,
+    },
+    Func {
+        op: Operation,
+        args: Vec<usize>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum OutputExprXyzJson {
+    Float([F32Json; 3]),
+    Parameter {
+        name: SmolStr,
+        field: SmolStr,
+        index: Option<usize>,
+        channel: Option<ChannelXyzJson>,
+    },
+    Texture {
+        name: SmolStr,
+        texcoords: Vec<usize>,
+        channel: Option<ChannelXyzJson>,
+    },
+    Attribute {
+        name: SmolStr,
+        channel: Option<ChannelXyzJson>,
+    },
+    Func {
+        op: OperationXyz,
+        args: Vec<usize>,
+        channel: Option<ChannelXyzJson>,
+    },
+}
+
+/// A float stored as a JSON number if finite and as a string otherwise.
+///
+/// JSON has no infinity or NaN, and serde_json would write these values as `null`.
+/// Non-finite values use `"inf"`, `"-inf"`, or the hex bits like `"nan:0x7FC00000"`
+/// to preserve the NaN payload.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(into = "F32JsonRepr", try_from = "F32JsonRepr")]
+struct F32Json(f32);
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum F32JsonRepr {
+    Number(f32),
+    String(String),
+}
+
+impl From<F32Json> for F32JsonRepr {
+    fn from(f: F32Json) -> Self {
+        let f = f.0;
+        if f.is_finite() {
+            Self::Number(f)
+        } else if f == f32::INFINITY {
+            Self::String("inf".to_string())
+        } else if f == f32::NEG_INFINITY {
+            Self::String("-inf".to_string())
+        } else {
+            Self::String(format!("nan:{:#010X}", f.to_bits()))
+        }
+    }
+}
+
+impl TryFrom<F32JsonRepr> for F32Json {
+    type Error = String;
+
+    fn try_from(f: F32JsonRepr) -> Result<Self, Self::Error> {
+        match f {
+            F32JsonRepr::Number(f) => Ok(Self(f)),
+            F32JsonRepr::String(s) => match s.as_str() {
+                "inf" => Ok(Self(f32::INFINITY)),
+                "-inf" => Ok(Self(f32::NEG_INFINITY)),
+                _ => s
+                    .strip_prefix("nan:0x")
+                    .and_then(|bits| u32::from_str_radix(bits, 16).ok())
+                    .map(f32::from_bits)
+                    .filter(|f| f.is_nan())
+                    .map(Self)
+                    .ok_or_else(|| format!("invalid float {s:?}")),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ChannelXyzJson {
+    Xyz,
+    X,
+    Y,
+    Z,
+    W,
+}
+
+/// An error converting a [ShaderDatabase] from JSON.
+#[derive(Debug, thiserror::Error)]
+pub enum DatabaseJsonError {
+    #[error("error parsing JSON")]
+    Json(#[from] serde_json::Error),
+
+    #[error("shader ID {0:?} is not a hex value")]
+    ShaderId(String),
+
+    #[error("program {shader_id:08X} references out of range expression {index} in {location}")]
+    ExprIndex {
+        shader_id: u32,
+        location: String,
+        index: usize,
+    },
+
+    #[error("program {shader_id:08X} has a cycle containing {exprs}[{index}]")]
+    ExprCycle {
+        shader_id: u32,
+        exprs: &'static str,
+        index: usize,
+    },
+}
+
+impl ShaderDatabase {
+    /// Convert the programs to pretty printed JSON.
+    ///
+    /// Expressions reference other expressions by index like in [ShaderProgram].
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        let database = ShaderDatabaseJson {
+            programs: self
+                .programs()
+                .map(|(id, p)| (format!("{id:08X}"), p.into()))
+                .collect(),
+        };
+        serde_json::to_string_pretty(&database)
+    }
+
+    /// Create a database from JSON in the format produced by [Self::to_json].
+    ///
+    /// Returns an error if any expression index is out of range
+    /// or an expression depends on itself.
+    pub fn from_json(json: &str) -> Result<Self, DatabaseJsonError> {
+        let database: ShaderDatabaseJson = serde_json::from_str(json)?;
+        let programs = database
+            .programs
+            .into_iter()
+            .map(|(id, p)| {
+                let id = u32::from_str_radix(&id, 16)
+                    .map_err(|_| DatabaseJsonError::ShaderId(id.clone()))?;
+                validate_exprs(
+                    id,
+                    "exprs",
+                    &p.exprs.iter().map(OutputExprJson::args).collect::<Vec<_>>(),
+                    "output_dependencies",
+                    &p.output_dependencies,
+                )?;
+                validate_exprs(
+                    id,
+                    "exprs_xyz",
+                    &p.exprs_xyz
+                        .iter()
+                        .map(OutputExprXyzJson::args)
+                        .collect::<Vec<_>>(),
+                    "output_dependencies_xyz",
+                    &p.output_dependencies_xyz,
+                )?;
+                // Texture coordinates for XYZ textures use the scalar exprs.
+                for (i, e) in p.exprs_xyz.iter().enumerate() {
+                    if let Some(index) = e.texcoords().iter().find(|t| **t >= p.exprs.len()) {
+                        return Err(DatabaseJsonError::ExprIndex {
+                            shader_id: id,
+                            location: format!("exprs_xyz[{i}]"),
+                            index: *index,
+                        });
+                    }
+                }
+                Ok((id, p.into()))
+            })
+            .collect::<Result<_, DatabaseJsonError>>()?;
+        Ok(Self::from_programs(programs))
+    }
+}
+
+/// Check that all expression indices are in range and that no expression depends on itself.
+fn validate_exprs(
+    shader_id: u32,
+    exprs_name: &'static str,
+    exprs_args: &[&[usize]],
+    outputs_name: &str,
+    outputs: &IndexMap<SmolStr, usize>,
+) -> Result<(), DatabaseJsonError> {
+    let index_error = |location, index| DatabaseJsonError::ExprIndex {
+        shader_id,
+        location,
+        index,
+    };
+
+    for (name, i) in outputs {
+        if *i >= exprs_args.len() {
+            return Err(index_error(format!("{outputs_name}[{name:?}]"), *i));
+        }
+    }
+    for (i, args) in exprs_args.iter().enumerate() {
+        if let Some(arg) = args.iter().find(|a| **a >= exprs_args.len()) {
+            return Err(index_error(format!("{exprs_name}[{i}]"), *arg));
+        }
+    }
+
+    // Depth first search with an explicit stack to support deeply nested expressions.
+    #[derive(Clone, Copy, PartialEq)]
+    enum Visit {
+        New,
+        InProgress,
+        Done,
+    }
+    let mut visits = vec![Visit::New; exprs_args.len()];
+    for start in 0..exprs_args.len() {
+        if visits[start] != Visit::New {
+            continue;
+        }
+        visits[start] = Visit::InProgress;
+        let mut stack = vec![(start, 0)];
+        while let Some(&(i, arg_index)) = stack.last() {
+            match exprs_args[i].get(arg_index) {
+                Some(&arg) => {
+                    if let Some(last) = stack.last_mut() {
+                        last.1 += 1;
+                    }
+                    match visits[arg] {
+                        Visit::New => {
+                            visits[arg] = Visit::InProgress;
+                            stack.push((arg, 0));
+                        }
+                        Visit::InProgress => {
+                            return Err(DatabaseJsonError::ExprCycle {
+                                shader_id,
+                                exprs: exprs_name,
+                                index: arg,
+                            });
+                        }
+                        Visit::Done => (),
+                    }
+                }
+                None => {
+                    visits[i] = Visit::Done;
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+impl OutputExprJson {
+    fn args(&self) -> &[usize] {
+        match self {
+            Self::Texture { texcoords, .. } => texcoords,
+            Self::Func { args, .. } => args,
+            _ => &[],
+        }
+    }
+}
+
+impl OutputExprXyzJson {
+    fn args(&self) -> &[usize] {
+        match self {
+            Self::Func { args, .. } => args,
+            _ => &[],
+        }
+    }
+
+    fn texcoords(&self) -> &[usize] {
+        match self {
+            Self::Texture { texcoords, .. } => texcoords,
+            _ => &[],
+        }
+    }
+}
+
+impl From<&ShaderProgram> for ShaderProgramJson {
+    fn from(p: &ShaderProgram) -> Self {
+        Self {
+            output_dependencies: p.output_dependencies.clone(),
+            exprs: p.exprs.iter().map(Into::into).collect(),
+            output_dependencies_xyz: p.output_dependencies_xyz.clone(),
+            exprs_xyz: p.exprs_xyz.iter().map(Into::into).collect(),
+            attributes: p.attributes.clone(),
+            samplers: p.samplers.clone(),
+            parameters: p.parameters.clone(),
+        }
+    }
+}
+
+impl From<ShaderProgramJson> for ShaderProgram {
+    fn from(p: ShaderProgramJson) -> Self {
+        Self {
+            output_dependencies: p.output_dependencies,
+            exprs: p.exprs.into_iter().map(Into::into).collect(),
+            output_dependencies_xyz: p.output_dependencies_xyz,
+            exprs_xyz: p.exprs_xyz.into_iter().map(Into::into).collect(),
+            attributes: p.attributes,
+            samplers: p.samplers,
+            parameters: p.parameters,
+        }
+    }
+}
+
+impl From<&OutputExpr<Operation>> for OutputExprJson {
+    fn from(e: &OutputExpr<Operation>) -> Self {
+        match e {
+            OutputExpr::Value(Value::Int(i)) => Self::Int(*i),
+            OutputExpr::Value(Value::Float(f)) => Self::Float(F32Json(f.0)),
+            OutputExpr::Value(Value::Parameter(p)) => Self::Parameter {
+                name: p.name.clone(),
+                field: p.field.clone(),
+                index: p.index,
+                channel: p.channel,
+            },
+            OutputExpr::Value(Value::Texture(t)) => Self::Texture {
+                name: t.name.clone(),
+                texcoords: t.texcoords.clone(),
+                channel: t.channel,
+            },
+            OutputExpr::Value(Value::Attribute(a)) => Self::Attribute {
+                name: a.name.clone(),
+                channel: a.channel,
+            },
+            OutputExpr::Func { op, args } => Self::Func {
+                op: *op,
+                args: args.clone(),
+            },
+        }
+    }
+}
+
+impl From<OutputExprJson> for OutputExpr<Operation> {
+    fn from(e: OutputExprJson) -> Self {
+        match e {
+            OutputExprJson::Int(i) => Self::Value(Value::Int(i)),
+            OutputExprJson::Float(f) => Self::Value(Value::Float(OrderedFloat(f.0))),
+            OutputExprJson::Parameter {
+                name,
+                field,
+                index,
+                channel,
+            } => Self::Value(Value::Parameter(Parameter {
+                name,
+                field,
+                index,
+                channel,
+            })),
+            OutputExprJson::Texture {
+                name,
+                texcoords,
+                channel,
+            } => Self::Value(Value::Texture(Texture {
+                name,
+                texcoords,
+                channel,
+            })),
+            OutputExprJson::Attribute { name, channel } => {
+                Self::Value(Value::Attribute(Attribute { name, channel }))
+            }
+            OutputExprJson::Func { op, args } => Self::Func { op, args },
+        }
+    }
+}
+
+impl From<&OutputExprXyz<OperationXyz>> for OutputExprXyzJson {
+    fn from(e: &OutputExprXyz<OperationXyz>) -> Self {
+        match e {
+            OutputExprXyz::Value(ValueXyz::Float(f)) => Self::Float(f.map(|f| F32Json(f.0))),
+            OutputExprXyz::Value(ValueXyz::Parameter(p)) => Self::Parameter {
+                name: p.name.clone(),
+                field: p.field.clone(),
+                index: p.index,
+                channel: p.channel.map(Into::into),
+            },
+            OutputExprXyz::Value(ValueXyz::Texture(t)) => Self::Texture {
+                name: t.name.clone(),
+                texcoords: t.texcoords.clone(),
+                channel: t.channel.map(Into::into),
+            },
+            OutputExprXyz::Value(ValueXyz::Attribute(a)) => Self::Attribute {
+                name: a.name.clone(),
+                channel: a.channel.map(Into::into),
+            },
+            OutputExprXyz::Func { op, args, channel } => Self::Func {
+                op: *op,
+                args: args.clone(),
+                channel: channel.map(Into::into),
+            },
+        }
+    }
+}
+
+impl From<OutputExprXyzJson> for OutputExprXyz<OperationXyz> {
+    fn from(e: OutputExprXyzJson) -> Self {
+        match e {
+            OutputExprXyzJson::Float(f) => {
+                Self::Value(ValueXyz::Float(f.map(|f| OrderedFloat(f.0))))
+            }
+            OutputExprXyzJson::Parameter {
+                name,
+                field,
+                index,
+                channel,
+            } => Self::Value(ValueXyz::Parameter(ParameterXyz {
+                name,
+                field,
+                index,
+                channel: channel.map(Into::into),
+            })),
+            OutputExprXyzJson::Texture {
+                name,
+                texcoords,
+                channel,
+            } => Self::Value(ValueXyz::Texture(TextureXyz {
+                name,
+                texcoords,
+                channel: channel.map(Into::into),
+            })),
+            OutputExprXyzJson::Attribute { name, channel } => {
+                Self::Value(ValueXyz::Attribute(AttributeXyz {
+                    name,
+                    channel: channel.map(Into::into),
+                }))
+            }
+            OutputExprXyzJson::Func { op, args, channel } => Self::Func {
+                op,
+                args,
+                channel: channel.map(Into::into),
+            },
+        }
+    }
+}
+
+impl From<ChannelXyz> for ChannelXyzJson {
+    fn from(c: ChannelXyz) -> Self {
+        match c {
+            ChannelXyz::Xyz => Self::Xyz,
+            ChannelXyz::X => Self::X,
+            ChannelXyz::Y => Self::Y,
+            ChannelXyz::Z => Self::Z,
+            ChannelXyz::W => Self::W,
+        }
+    }
+}
+
+impl From<ChannelXyzJson> for ChannelXyz {
+    fn from(c: ChannelXyzJson) -> Self {
+        match c {
+            ChannelXyzJson::Xyz => Self::Xyz,
+            ChannelXyzJson::X => Self::X,
+            ChannelXyzJson::Y => Self::Y,
+            ChannelXyzJson::Z => Self::Z,
+            ChannelXyzJson::W => Self::W,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn database() -> ShaderDatabase {
+        ShaderDatabase::from_programs(BTreeMap::from([(
+            0x96000001,
+            ShaderProgram {
+                output_dependencies: [("out_attr0.w".into(), 2)].into_iter().collect(),
+                exprs: vec![
+                    OutputExpr::Value(Value::Attribute(Attribute {
+                        name: "a_TexCoord0".into(),
+                        channel: Some('x'),
+                    })),
+                    OutputExpr::Value(Value::Texture(Texture {
+                        name: "colorSampler".into(),
+                        texcoords: vec![0, 0],
+                        channel: Some('w'),
+                    })),
+                    OutputExpr::Func {
+                        op: Operation::Mul,
+                        args: vec![1, 3],
+                    },
+                    OutputExpr::Value(Value::Parameter(Parameter {
+                        name: "MC".into(),
+                        field: "NU_colorGain".into(),
+                        index: None,
+                        channel: Some('w'),
+                    })),
+                ],
+                output_dependencies_xyz: [("out_attr0.xyz".into(), 1)].into_iter().collect(),
+                exprs_xyz: vec![
+                    OutputExprXyz::Value(ValueXyz::Float([OrderedFloat(0.5); 3])),
+                    OutputExprXyz::Func {
+                        op: OperationXyz::Normalize,
+                        args: vec![0],
+                        channel: Some(ChannelXyz::Xyz),
+                    },
+                    // Texture coordinates index into exprs instead of exprs_xyz.
+                    OutputExprXyz::Value(ValueXyz::Texture(TextureXyz {
+                        name: "colorSampler".into(),
+                        texcoords: vec![3, 0],
+                        channel: Some(ChannelXyz::Xyz),
+                    })),
+                ],
+                attributes: vec!["a_TexCoord0".into()],
+                samplers: vec!["colorSampler".into()],
+                parameters: vec!["NU_colorGain".into()],
+            },
+        )]))
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let database = database();
+        let json = database.to_json().unwrap();
+        assert!(json.contains("\"96000001\""));
+        assert_eq!(database, ShaderDatabase::from_json(&json).unwrap());
+    }
+
+    #[test]
+    fn json_invalid_shader_id() {
+        assert!(matches!(
+            ShaderDatabase::from_json(r#"{"programs": {"shader": {}}}"#),
+            Err(DatabaseJsonError::Json(_))
+        ));
+        assert!(matches!(
+            ShaderDatabase::from_json(
+                r#"{"programs": {"xyz": {
+                    "output_dependencies": {},
+                    "exprs": [],
+                    "output_dependencies_xyz": {},
+                    "exprs_xyz": [],
+                    "attributes": [],
+                    "samplers": [],
+                    "parameters": []
+                }}}"#
+            ),
+            Err(DatabaseJsonError::ShaderId(_))
+        ));
+    }
+
+    #[test]
+    fn json_round_trip_non_finite_floats() {
+        let mut program = database().programs().next().unwrap().1.clone();
+        program.exprs[0] = OutputExpr::Value(Value::Float(OrderedFloat(f32::INFINITY)));
+        program.exprs_xyz[0] = OutputExprXyz::Value(ValueXyz::Float([
+            OrderedFloat(f32::NEG_INFINITY),
+            OrderedFloat(f32::from_bits(0x7FC00001)),
+            OrderedFloat(-0.0),
+        ]));
+        let database = ShaderDatabase::from_programs(BTreeMap::from([(0x96000001, program)]));
+
+        let json = database.to_json().unwrap();
+        assert!(json.contains("\"inf\""));
+        assert!(json.contains("\"-inf\""));
+        assert!(json.contains("\"nan:0x7FC00001\""));
+        assert!(!json.contains("null"));
+
+        let new_database = ShaderDatabase::from_json(&json).unwrap();
+        let (_, new_program) = new_database.programs().next().unwrap();
+        assert_eq!(
+            OutputExpr::Value(Value::Float(OrderedFloat(f32::INFINITY))),
+            new_program.exprs[0]
+        );
+        let OutputExprXyz::Value(ValueXyz::Float(values)) = &new_program.exprs_xyz[0] else {
+            panic!("expected float values");
+        };
+        assert_eq!(
+            [0xFF800000, 0x7FC00001, 0x80000000],
+            values.map(|f| f.0.to_bits())
+        );
+    }
+
+    #[test]
+    fn json_invalid_float() {
+        assert!(matches!(
+            ShaderDatabase::from_json(
+                r#"{"programs": {"96000001": {
+                    "output_dependencies": {},
+                    "exprs": [{"float": "one"}],
+                    "output_dependencies_xyz": {},
+                    "exprs_xyz": [],
+                    "attributes": [],
+                    "samplers": [],
+                    "parameters": []
+                }}}"#
+            ),
+            Err(DatabaseJsonError::Json(_))
+        ));
+    }
+
+    fn program_json(output: usize, exprs: &str) -> String {
+        format!(
+            r#"{{"programs": {{"96000001": {{
+                "output_dependencies": {{"out_attr0.x": {output}}},
+                "exprs": {exprs},
+                "output_dependencies_xyz": {{}},
+                "exprs_xyz": [],
+                "attributes": [],
+                "samplers": [],
+                "parameters": []
+            }}}}}}"#
+        )
+    }
+
+    #[test]
+    fn json_valid_indices() {
+        let json = program_json(
+            2,
+            r#"[{"float": 1.0}, {"func": {"op": "Add", "args": [0, 0]}}, {"func": {"op": "Mul", "args": [1, 0]}}]"#,
+        );
+        assert!(ShaderDatabase::from_json(&json).is_ok());
+    }
+
+    #[test]
+    fn json_invalid_output_index() {
+        let json = program_json(1, r#"[{"float": 1.0}]"#);
+        assert!(matches!(
+            ShaderDatabase::from_json(&json),
+            Err(DatabaseJsonError::ExprIndex {
+                shader_id: 0x96000001,
+                index: 1,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn json_invalid_arg_index() {
+        let json = program_json(
+            0,
+            r#"[{"texture": {"name": "colorSampler", "texcoords": [0, 3], "channel": "x"}}]"#,
+        );
+        assert!(matches!(
+            ShaderDatabase::from_json(&json),
+            Err(DatabaseJsonError::ExprIndex {
+                shader_id: 0x96000001,
+                index: 3,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn json_expr_cycle() {
+        let json = program_json(
+            0,
+            r#"[{"func": {"op": "Add", "args": [1, 2]}}, {"float": 1.0}, {"func": {"op": "Mul", "args": [1, 0]}}]"#,
+        );
+        assert!(matches!(
+            ShaderDatabase::from_json(&json),
+            Err(DatabaseJsonError::ExprCycle {
+                shader_id: 0x96000001,
+                exprs: "exprs",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn json_invalid_xyz_texcoord_index() {
+        let json = r#"{"programs": {"96000001": {
+            "output_dependencies": {},
+            "exprs": [{"float": 1.0}],
+            "output_dependencies_xyz": {"out_attr0.xyz": 1},
+            "exprs_xyz": [
+                {"float": [1.0, 1.0, 1.0]},
+                {"texture": {"name": "colorSampler", "texcoords": [0, 1], "channel": "xyz"}}
+            ],
+            "attributes": [],
+            "samplers": [],
+            "parameters": []
+        }}}"#;
+        assert!(matches!(
+            ShaderDatabase::from_json(json),
+            Err(DatabaseJsonError::ExprIndex {
+                shader_id: 0x96000001,
+                index: 1,
+                ..
+            })
+        ));
+    }
+}
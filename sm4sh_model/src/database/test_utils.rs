@@ -0,0 +1,42 @@
+//! Helpers for creating [ShaderProgram] values in tests.
+use ordered_float::OrderedFloat;
+
+use crate::database::{Attribute, Operation, OutputExpr, ShaderProgram, Texture, Value};
+
+pub fn program(exprs: Vec<OutputExpr<Operation>>, outputs: &[(&str, usize)]) -> ShaderProgram {
+    ShaderProgram {
+        output_dependencies: outputs.iter().map(|(n, i)| ((*n).into(), *i)).collect(),
+        exprs,
+        output_dependencies_xyz: Default::default(),
+        exprs_xyz: Vec::new(),
+        attributes: Vec::new(),
+        samplers: Vec::new(),
+        parameters: Vec::new(),
+    }
+}
+
+pub fn float(f: f32) -> OutputExpr<Operation> {
+    OutputExpr::Value(Value::Float(OrderedFloat(f)))
+}
+
+pub fn attribute(name: &str, channel: char) -> OutputExpr<Operation> {
+    OutputExpr::Value(Value::Attribute(Attribute {
+        name: name.into(),
+        channel: Some(channel),
+    }))
+}
+
+pub fn texture(name: &str, texcoords: &[usize], channel: char) -> OutputExpr<Operation> {
+    OutputExpr::Value(Value::Texture(Texture {
+        name: name.into(),
+        texcoords: texcoords.to_vec(),
+        channel: Some(channel),
+    }))
+}
+
+pub fn func(op: Operation, args: &[usize]) -> OutputExpr<Operation> {
+    OutputExpr::Func {
+        op,
+        args: args.to_vec(),
+    }
+}
@@ -0,0 +1,654 @@
+//! Global uniform values shared by all shader programs.
+//!
+//! [RenderGlobals] is used both for evaluating programs on the CPU
+//! with [ShaderProgram::parameter_value](crate::database::ShaderProgram::parameter_value)
+//! and for filling the uniform buffers of renderers.
+//! Using the same values for both keeps CPU evaluation and GPU rendering consistent.
+use glam::{EulerRot, Mat3, Mat4, Vec3, Vec4, vec4};
+use sm4sh_lib::param::{Param, ParamGroup};
+
+/// Configurable global state for lighting, fog, reflections, resolution, and time.
+///
+/// The defaults are taken from Rosalina c00 on Miiverse stage.
+/// Values not present here are fixed in the uniform buffers from [Self::fb0], [Self::fb1], [Self::fb3],
+/// [Self::fb4], and [Self::fb5].
+#[derive(Debug, PartialEq, Clone)]
+pub struct RenderGlobals {
+    /// The width of the render target in pixels.
+    pub width: u32,
+    /// The height of the render target in pixels.
+    pub height: u32,
+    /// The counter for animating scrolling texture coordinates.
+    pub time: f32,
+
+    /// The camera position.
+    pub eye: Vec4,
+
+    /// The directions for the three directional lights.
+    pub light_dirs: [Vec4; 3],
+    /// The colors for the three directional lights.
+    pub light_dir_colors: [Vec4; 3],
+    pub ambient_color: Vec4,
+    /// The direction for the hemisphere lighting ceiling color.
+    pub ceiling_dir: Vec4,
+    pub ceiling_color: Vec4,
+    pub ground_color: Vec4,
+    pub light_map_color_gain: Vec4,
+    pub light_map_color_offset: Vec4,
+    pub ibl_color_gain: Vec4,
+    pub ibl_color_offset: Vec4,
+    pub char_shadow_colors: [Vec4; 2],
+    pub bg_shadow_color: Vec4,
+    pub hdr_range: Vec4,
+
+    pub fog_color: Vec4,
+
+    pub reflection_param: Vec4,
+    pub reflection_gain: Vec4,
+    pub reflection_colors: [Vec4; 3],
+    pub post_reflection_color: Vec4,
+}
+
+impl Default for RenderGlobals {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+            time: 0.35,
+            eye: vec4(40.0, 47.40689, 37.02085, 1.0), // TODO: fill in these values
+            light_dirs: [
+                vec4(0.0, -0.84323, -0.53756, 0.0),
+                vec4(-0.87287, 0.43644, -0.21822, 0.0),
+                vec4(0.0, 0.0, 0.0, 0.0),
+            ],
+            light_dir_colors: [
+                vec4(0.75, 0.75, 0.75, 0.0),
+                vec4(0.2, 0.2, 0.2, 1.0),
+                vec4(0.0, 0.0, 0.0, 0.0),
+            ],
+            ambient_color: vec4(0.0, 0.0, 0.0, 0.0),
+            ceiling_dir: vec4(0.0, 1.0, 0.0, 0.0),
+            ceiling_color: vec4(0.15, 0.15, 0.15, 0.0),
+            ground_color: vec4(1.0, 1.0, 1.0, 0.0),
+            light_map_color_gain: vec4(0.4875, 0.4875, 0.4875, 0.0),
+            light_map_color_offset: vec4(0.0, 0.0, 0.0, 0.0),
+            ibl_color_gain: vec4(1.0, 1.0, 1.0, 0.0),
+            ibl_color_offset: vec4(0.15, 0.15, 0.15, 0.0),
+            char_shadow_colors: [
+                vec4(0.315, 0.31792, 0.35, 1.0),
+                vec4(0.685, 0.68208, 0.65, 1.0),
+            ],
+            bg_shadow_color: vec4(0.81, 0.8175, 0.90, 1.0),
+            hdr_range: vec4(0.5, 2.0, 0.0, 0.0),
+            fog_color: vec4(1.0, 1.0, 1.0, 1.0),
+            reflection_param: vec4(0.0075, 2.50, 0.25, 0.00),
+            reflection_gain: vec4(1.0, 1.0, 1.0, 1.0),
+            reflection_colors: [
+                vec4(0.0, 0.0, 0.0, 0.0),
+                vec4(0.0001, 0.0, 0.0, 0.0),
+                vec4(0.315, 0.31792, 0.35, 1.0),
+            ],
+            post_reflection_color: vec4(0.50, 0.50, 0.50, 0.20),
+        }
+    }
+}
+
+impl RenderGlobals {
+    /// Load the directional lights and fog color for `light_set`
+    /// from a stage's `param/light_set_param.bin`.
+    ///
+    /// The locations of values are based on research from Smash Forge.
+    /// Only the first three directional lights and the fog color are loaded.
+    /// The fog distance parameters are not loaded.
+    /// All other values including the ambient, ceiling, ground, and reflection colors
+    /// and the `FB3`, `FB4`, and `FB5` values use the defaults from [RenderGlobals::default].
+    pub fn from_light_set_lights_fog(
+        param: &Param,
+        light_set: usize,
+    ) -> Result<Self, LightSetParamError> {
+        let mut globals = Self::default();
+
+        // Each light set has 4 directional lights in group 1 after the first 4 entries.
+        // TODO: How is the fourth light used in shaders?
+        let lights = param_group(param, LIGHTS_GROUP)?;
+        let dirs_colors = globals
+            .light_dirs
+            .iter_mut()
+            .zip(globals.light_dir_colors.iter_mut());
+        for (i, (dir, color)) in dirs_colors.enumerate() {
+            (*dir, *color) = stage_light(lights, (light_set + 1) * 4 + i)?;
+        }
+
+        // TODO: Find the fog distance parameters.
+        let fog = param_group(param, FOG_GROUP)?;
+        globals.fog_color = hsv_color(fog, FOG_GROUP, light_set + 1, 0)?.extend(1.0);
+
+        Ok(globals)
+    }
+
+    /// The number of light sets in a stage's `param/light_set_param.bin`
+    /// for [Self::from_light_set_lights_fog].
+    pub fn light_set_count(param: &Param) -> usize {
+        param
+            .groups
+            .get(LIGHTS_GROUP)
+            .map(|g| (g.entries.len() / 4).saturating_sub(1))
+            .unwrap_or_default()
+    }
+
+    /// The values for the `FB0` uniform buffer.
+    pub fn fb0(&self) -> Fb0 {
+        let width = self.width as f32;
+        let height = self.height as f32;
+        Fb0 {
+            depth_of_field0: vec4(0.0, 0.0, 0.0, 0.0),
+            depth_of_field1: vec4(0.0, 0.0, 0.0, 0.0),
+            depth_of_field_tex_size: vec4(0.0, 0.0, 0.0, 0.0),
+            proj_inv_matrix: Mat4::IDENTITY, // TODO: Fill in this value
+            refraction_param: vec4(0.0, 0.0, 0.0, 0.0),
+            proj_to_view: vec4(0.47635, 0.26795, 256.00, 0.00),
+            view_to_proj: vec4(1.04964, -1.86603, 0.00391, 0.00),
+            gi_buffer_size: vec4(width / 4.0, height / 4.0, 4.0 / width, 4.0 / height),
+            weight0: vec4(0.14374, 0.1258, 0.09635, 0.06459),
+            weight1: vec4(0.03789, 0.01945, 0.00874, 0.00344),
+            random_vector: [Vec4::ZERO; 31], // TODO: Fill in these values
+            reflection_param: self.reflection_param,
+            sun_shaft_light_param0: [vec4(0.0, 0.0, 0.0, 0.0), vec4(0.0, 0.0, 0.0, 0.0)],
+            sun_shaft_light_param1: [vec4(0.0, 0.0, 0.0, 0.0), vec4(0.0, 0.0, 0.0, 0.0)],
+            sun_shaft_blur_param: [
+                vec4(0.0, 0.0, 0.0, 0.0),
+                vec4(0.0, 0.0, 0.0, 0.0),
+                vec4(0.0, 0.0, 0.0, 0.0),
+                vec4(0.0, 0.0, 0.0, 0.0),
+            ],
+            sun_shaft_composite_param: [vec4(0.0, 0.0, 0.0, 0.0), vec4(0.0, 0.0, 0.0, 0.0)],
+            glare_abstract_param: vec4(1.0, 4.0, 0.0, 0.0),
+            render_target_tex_size: vec4(1.0 / width, 1.0 / height, 2.0 / width, 2.0 / height),
+            glare_fog_param: [vec4(0.0, 0.0, 0.0, 0.0), vec4(0.0, 0.0, 0.0, 0.0)],
+            glare_simple_color: vec4(0.0, 0.0, 0.0, 0.0),
+            pad0_fb0: vec4(0.0, 0.0, 0.0, 0.0),
+            lens_flare_param: vec4(0.0, 0.0, 0.0, 0.0),
+            outline_param: vec4(0.25, 0.00, 0.00, 0.00),
+            post_reflection_color: self.post_reflection_color,
+            multi_shadow_matrix: [Mat4::IDENTITY; 4], // TODO: fill in these values
+            shadow_map_matrix: Mat4::from_cols_array_2d(&[
+                [0.00814, 0.00, 0.00, 0.00],
+                [0.00, -0.00504, -0.01631, 0.00],
+                [0.00, 0.01385, -0.00594, 0.00],
+                [0.49189, 0.67917, 1.09728, 1.00],
+            ]), // TODO: fill in these values
+            view: Mat4::ZERO,                         // TODO: fill in these values
+            eye: self.eye,
+            constant_color: vec4(1.0, 1.0, 1.0, 1.0),
+            light_map_pos: vec4(0.0, 0.0, 0.0, 0.0),
+            reflection_gain: self.reflection_gain,
+            hdr_constant: vec4(0.5, 2.0, 1.0, 1.0),
+            _g_fresnel_color: vec4(1.0, 1.0, 1.0, 1.0),
+            effect_light_param0: vec4(0.1, 0.1, -15.0, 0.0),
+            effect_light_param1: vec4(30.0, 12.0, 29.0, 11.0),
+            bg_rot_inv: Mat4::IDENTITY,
+            reflection_color1: self.reflection_colors[0],
+            reflection_color2: self.reflection_colors[1],
+            reflection_color3: self.reflection_colors[2],
+            effect_light_param2: vec4(0.685, 0.68208, 0.65, 1.00),
+        }
+    }
+
+    /// The values for the `FB1` uniform buffer.
+    pub fn fb1(&self) -> Fb1 {
+        Fb1 {
+            light_map_matrix: Mat4::IDENTITY,
+            blink_color: vec4(1.0, 1.0, 1.0, 0.0),
+            g_constant_volume: vec4(1.0, 1.0, 1.0, 1.0),
+            g_constant_offset: vec4(0.0, 0.0, 0.0, 0.0),
+            uv_scroll_counter: vec4(self.time, 0.0, 0.0, 0.0), // TODO: changes over time?
+            spycloak_params: vec4(-100.0, 0.0, 0.0, 0.0),
+            compress_param: vec4(1.0, 0.0, 0.0, 0.0),
+            g_fresnel_color: vec4(1.0, 1.0, 1.0, 1.0),
+            depth_offset: vec4(0.0, 0.0, 0.0, 0.0),
+            outline_color: vec4(0.0, 0.0, 0.0, 1.0),
+            pad0_fb1: [
+                vec4(0.0, 0.0, 0.0, 0.0),
+                vec4(0.0, 0.0, 0.0, 0.0),
+                vec4(0.0, 0.0, 0.0, 0.0),
+            ],
+            light_map_color_gain: self.light_map_color_gain,
+            light_map_color_offset: self.light_map_color_offset,
+            ceiling_dir: self.ceiling_dir,
+            ceiling_color: self.ceiling_color,
+            ground_color: self.ground_color,
+            ambient_color: self.ambient_color,
+            light_dir_color1: self.light_dir_colors[0],
+            light_dir_color2: self.light_dir_colors[1],
+            light_dir_color3: self.light_dir_colors[2],
+            light_dir1: self.light_dirs[0],
+            light_dir2: self.light_dirs[1],
+            light_dir3: self.light_dirs[2],
+            fog_color: self.fog_color,
+            g_fresnel_offset: vec4(0.0, 0.0, 0.0, 0.0),
+            shadow_map_param: vec4(0.001, 0.0, 0.0, 0.0),
+            char_shadow_color: self.char_shadow_colors[0],
+            char_shadow_color2: self.char_shadow_colors[1],
+            soft_lighting_params2: vec4(0.0, 0.0, 0.0, 1.0),
+            bg_shadow_color: self.bg_shadow_color,
+            g_ibl_color_gain: self.ibl_color_gain,
+            g_ibl_color_offset: self.ibl_color_offset,
+            g_constant_min: Vec4::ZERO,
+            loupe_shadow_params: Vec4::ZERO,
+            soft_light_color_gain: Vec4::ZERO,
+            soft_light_color_offset: Vec4::ZERO,
+            character_color: Vec4::ZERO,
+        }
+    }
+
+    /// The values for the `FB3` uniform buffer.
+    pub fn fb3(&self) -> Fb3 {
+        Fb3 {
+            hdr_range: self.hdr_range,
+            colr_hdr_range: Vec4::ZERO,
+        }
+    }
+
+    /// The values for the `FB4` uniform buffer.
+    ///
+    /// Effect lights are not supported, so all values are zero.
+    pub fn fb4(&self) -> Fb4 {
+        Fb4 {
+            effect_light_entry: [Vec4::ZERO; EFFECT_LIGHT_COUNT],
+        }
+    }
+
+    /// The values for the `FB5` uniform buffer.
+    ///
+    /// Effect lights are not supported, so all values are zero.
+    pub fn fb5(&self) -> Fb5 {
+        Fb5 {
+            effect_light_area: [Vec4::ZERO; EFFECT_LIGHT_COUNT],
+        }
+    }
+}
+
+const LIGHTS_GROUP: usize = 1;
+const FOG_GROUP: usize = 2;
+
+/// An error loading [RenderGlobals] from a `light_set_param.bin`.
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum LightSetParamError {
+    #[error("param group {0} not found")]
+    MissingGroup(usize),
+
+    #[error("entry {entry} not found in param group {group}")]
+    MissingEntry { group: usize, entry: usize },
+
+    #[error("value {value} for entry {entry} in param group {group} is missing or not a number")]
+    InvalidValue {
+        group: usize,
+        entry: usize,
+        value: usize,
+    },
+}
+
+fn param_group(param: &Param, group: usize) -> Result<&ParamGroup, LightSetParamError> {
+    param
+        .groups
+        .get(group)
+        .ok_or(LightSetParamError::MissingGroup(group))
+}
+
+fn param_f32(
+    group: &ParamGroup,
+    group_index: usize,
+    entry: usize,
+    value: usize,
+) -> Result<f32, LightSetParamError> {
+    let values = group
+        .entries
+        .get(entry)
+        .ok_or(LightSetParamError::MissingEntry {
+            group: group_index,
+            entry,
+        })?;
+    values
+        .get(value)
+        .and_then(|v| v.as_f32())
+        .ok_or(LightSetParamError::InvalidValue {
+            group: group_index,
+            entry,
+            value,
+        })
+}
+
+fn stage_light(lights: &ParamGroup, entry: usize) -> Result<(Vec4, Vec4), LightSetParamError> {
+    let value = |i| param_f32(lights, LIGHTS_GROUP, entry, i);
+
+    let enabled = value(0)? == 1.0;
+    let color = if enabled {
+        hsv_color(lights, LIGHTS_GROUP, entry, 1)?
+    } else {
+        Vec3::ZERO
+    };
+
+    // TODO: Is this the correct rotation order?
+    // This has not been compared against in game lighting for a known stage.
+    let rotation = Mat3::from_euler(
+        EulerRot::ZYX,
+        value(9)?.to_radians(),
+        value(8)?.to_radians(),
+        value(7)?.to_radians(),
+    );
+    let dir = (rotation * Vec3::Z).normalize_or_zero();
+
+    Ok((dir.extend(0.0), color.extend(0.0)))
+}
+
+fn hsv_color(
+    group: &ParamGroup,
+    group_index: usize,
+    entry: usize,
+    start: usize,
+) -> Result<Vec3, LightSetParamError> {
+    let value = |i| param_f32(group, group_index, entry, start + i);
+    Ok(hsv_to_rgb(value(0)?, value(1)?, value(2)?))
+}
+
+// Hue is in degrees.
+fn hsv_to_rgb(h: f32, s: f32, v: f32) -> Vec3 {
+    let h = h.rem_euclid(360.0) / 60.0;
+    let c = v * s;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let rgb = match h as u32 {
+        0 => Vec3::new(c, x, 0.0),
+        1 => Vec3::new(x, c, 0.0),
+        2 => Vec3::new(0.0, c, x),
+        3 => Vec3::new(0.0, x, c),
+        4 => Vec3::new(x, 0.0, c),
+        _ => Vec3::new(c, 0.0, x),
+    };
+    rgb + Vec3::splat(v - c)
+}
+
+/// The `FB0` uniform buffer in shaders.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Fb0 {
+    pub depth_of_field0: glam::Vec4,
+    pub depth_of_field1: glam::Vec4,
+    pub depth_of_field_tex_size: glam::Vec4,
+    pub proj_inv_matrix: glam::Mat4,
+    pub refraction_param: glam::Vec4,
+    pub proj_to_view: glam::Vec4,
+    pub view_to_proj: glam::Vec4,
+    pub gi_buffer_size: glam::Vec4,
+    pub weight0: glam::Vec4,
+    pub weight1: glam::Vec4,
+    pub random_vector: [glam::Vec4; 31],
+    pub reflection_param: glam::Vec4,
+    pub sun_shaft_light_param0: [glam::Vec4; 2],
+    pub sun_shaft_light_param1: [glam::Vec4; 2],
+    pub sun_shaft_blur_param: [glam::Vec4; 4],
+    pub sun_shaft_composite_param: [glam::Vec4; 2],
+    pub glare_abstract_param: glam::Vec4,
+    pub render_target_tex_size: glam::Vec4,
+    pub glare_fog_param: [glam::Vec4; 2],
+    pub glare_simple_color: glam::Vec4,
+    pub pad0_fb0: glam::Vec4,
+    pub lens_flare_param: glam::Vec4,
+    pub outline_param: glam::Vec4,
+    pub post_reflection_color: glam::Vec4,
+    pub multi_shadow_matrix: [glam::Mat4; 4],
+    pub shadow_map_matrix: glam::Mat4,
+    pub view: glam::Mat4,
+    pub eye: glam::Vec4,
+    pub constant_color: glam::Vec4,
+    pub light_map_pos: glam::Vec4,
+    pub reflection_gain: glam::Vec4,
+    pub hdr_constant: glam::Vec4,
+    pub _g_fresnel_color: glam::Vec4,
+    pub effect_light_param0: glam::Vec4,
+    pub effect_light_param1: glam::Vec4,
+    pub bg_rot_inv: glam::Mat4,
+    pub reflection_color1: glam::Vec4,
+    pub reflection_color2: glam::Vec4,
+    pub reflection_color3: glam::Vec4,
+    pub effect_light_param2: glam::Vec4,
+}
+
+/// The `FB1` uniform buffer in shaders.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Fb1 {
+    pub light_map_matrix: glam::Mat4,
+    pub blink_color: glam::Vec4,
+    pub g_constant_volume: glam::Vec4,
+    pub g_constant_offset: glam::Vec4,
+    pub uv_scroll_counter: glam::Vec4,
+    pub spycloak_params: glam::Vec4,
+    pub compress_param: glam::Vec4,
+    pub g_fresnel_color: glam::Vec4,
+    pub depth_offset: glam::Vec4,
+    pub outline_color: glam::Vec4,
+    pub pad0_fb1: [glam::Vec4; 3],
+    pub light_map_color_gain: glam::Vec4,
+    pub light_map_color_offset: glam::Vec4,
+    pub ceiling_dir: glam::Vec4,
+    pub ceiling_color: glam::Vec4,
+    pub ground_color: glam::Vec4,
+    pub ambient_color: glam::Vec4,
+    pub light_dir_color1: glam::Vec4,
+    pub light_dir_color2: glam::Vec4,
+    pub light_dir_color3: glam::Vec4,
+    pub light_dir1: glam::Vec4,
+    pub light_dir2: glam::Vec4,
+    pub light_dir3: glam::Vec4,
+    pub fog_color: glam::Vec4,
+    pub g_fresnel_offset: glam::Vec4,
+    pub shadow_map_param: glam::Vec4,
+    pub char_shadow_color: glam::Vec4,
+    pub char_shadow_color2: glam::Vec4,
+    pub soft_lighting_params2: glam::Vec4,
+    pub bg_shadow_color: glam::Vec4,
+    pub g_ibl_color_gain: glam::Vec4,
+    pub g_ibl_color_offset: glam::Vec4,
+    pub g_constant_min: glam::Vec4,
+    pub loupe_shadow_params: glam::Vec4,
+    pub soft_light_color_gain: glam::Vec4,
+    pub soft_light_color_offset: glam::Vec4,
+    pub character_color: glam::Vec4,
+}
+
+/// The `FB3` uniform buffer in shaders.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Fb3 {
+    pub hdr_range: glam::Vec4,
+    pub colr_hdr_range: glam::Vec4,
+}
+
+/// The number of elements in the arrays for [Fb4] and [Fb5].
+// TODO: What should the actual count be?
+pub const EFFECT_LIGHT_COUNT: usize = 4096;
+
+/// The `FB4` uniform buffer in shaders.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Fb4 {
+    pub effect_light_entry: [glam::Vec4; EFFECT_LIGHT_COUNT],
+}
+
+/// The `FB5` uniform buffer in shaders.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Fb5 {
+    /// Integer values stored in floats.
+    pub effect_light_area: [glam::Vec4; EFFECT_LIGHT_COUNT],
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sm4sh_lib::param::ParamValue;
+
+    macro_rules! assert_eq_approx {
+        ($a:expr, $b:expr) => {
+            assert!($a.abs_diff_eq($b, 0.0001), "{:?} != {:?}", $a, $b);
+        };
+    }
+
+    fn light(enabled: u32, hue: f32, rot_x: f32) -> Vec<ParamValue> {
+        let mut values = vec![
+            ParamValue::U32(enabled),
+            ParamValue::F32(hue),
+            ParamValue::F32(1.0),
+            ParamValue::F32(0.5),
+        ];
+        values.extend([0.0, 0.0, 0.0, rot_x, 0.0, 0.0].map(ParamValue::F32));
+        values
+    }
+
+    #[test]
+    fn hsv_to_rgb_primary_colors() {
+        assert_eq_approx!(Vec3::new(1.0, 0.0, 0.0), hsv_to_rgb(0.0, 1.0, 1.0));
+        assert_eq_approx!(Vec3::new(0.0, 1.0, 0.0), hsv_to_rgb(120.0, 1.0, 1.0));
+        assert_eq_approx!(Vec3::new(0.0, 0.0, 0.5), hsv_to_rgb(240.0, 1.0, 0.5));
+        assert_eq_approx!(Vec3::splat(0.25), hsv_to_rgb(60.0, 0.0, 0.25));
+    }
+
+    #[test]
+    fn fb0_globals() {
+        let globals = RenderGlobals {
+            width: 1280,
+            height: 720,
+            eye: vec4(1.0, 2.0, 3.0, 1.0),
+            reflection_param: vec4(0.1, 0.2, 0.3, 0.4),
+            reflection_gain: vec4(0.5, 0.5, 0.5, 1.0),
+            reflection_colors: [Vec4::X, Vec4::Y, Vec4::Z],
+            post_reflection_color: Vec4::W,
+            ..Default::default()
+        };
+        let fb0 = globals.fb0();
+        assert_eq!(
+            vec4(320.0, 180.0, 4.0 / 1280.0, 4.0 / 720.0),
+            fb0.gi_buffer_size
+        );
+        assert_eq!(
+            vec4(1.0 / 1280.0, 1.0 / 720.0, 2.0 / 1280.0, 2.0 / 720.0),
+            fb0.render_target_tex_size
+        );
+        assert_eq!(globals.eye, fb0.eye);
+        assert_eq!(globals.reflection_param, fb0.reflection_param);
+        assert_eq!(globals.reflection_gain, fb0.reflection_gain);
+        assert_eq!(Vec4::X, fb0.reflection_color1);
+        assert_eq!(Vec4::Y, fb0.reflection_color2);
+        assert_eq!(Vec4::Z, fb0.reflection_color3);
+        assert_eq!(Vec4::W, fb0.post_reflection_color);
+    }
+
+    #[test]
+    fn fb1_globals() {
+        let globals = RenderGlobals {
+            time: 2.5,
+            light_dirs: [Vec4::X, Vec4::Y, Vec4::Z],
+            light_dir_colors: [Vec4::ONE, Vec4::splat(0.5), Vec4::ZERO],
+            ambient_color: vec4(0.1, 0.1, 0.1, 0.0),
+            fog_color: vec4(0.0, 0.0, 1.0, 1.0),
+            char_shadow_colors: [Vec4::W, Vec4::X],
+            ibl_color_gain: Vec4::splat(2.0),
+            ibl_color_offset: Vec4::splat(0.25),
+            ..Default::default()
+        };
+        let fb1 = globals.fb1();
+        assert_eq!(vec4(2.5, 0.0, 0.0, 0.0), fb1.uv_scroll_counter);
+        assert_eq!(
+            [Vec4::X, Vec4::Y, Vec4::Z],
+            [fb1.light_dir1, fb1.light_dir2, fb1.light_dir3]
+        );
+        assert_eq!(
+            [Vec4::ONE, Vec4::splat(0.5), Vec4::ZERO],
+            [
+                fb1.light_dir_color1,
+                fb1.light_dir_color2,
+                fb1.light_dir_color3
+            ]
+        );
+        assert_eq!(globals.ambient_color, fb1.ambient_color);
+        assert_eq!(globals.ceiling_dir, fb1.ceiling_dir);
+        assert_eq!(globals.ceiling_color, fb1.ceiling_color);
+        assert_eq!(globals.ground_color, fb1.ground_color);
+        assert_eq!(globals.fog_color, fb1.fog_color);
+        assert_eq!(Vec4::W, fb1.char_shadow_color);
+        assert_eq!(Vec4::X, fb1.char_shadow_color2);
+        assert_eq!(Vec4::splat(2.0), fb1.g_ibl_color_gain);
+        assert_eq!(Vec4::splat(0.25), fb1.g_ibl_color_offset);
+    }
+
+    fn light_set_param() -> Param {
+        // Use light set 0 with entries 4 to 7 for the lights.
+        let mut lights = vec![light(0, 0.0, 0.0); 4];
+        lights.push(light(1, 0.0, -90.0));
+        lights.push(light(1, 120.0, 0.0));
+        lights.push(light(0, 240.0, 0.0));
+        lights.push(light(1, 240.0, 0.0));
+
+        Param {
+            groups: vec![
+                ParamGroup {
+                    entries: vec![vec![ParamValue::U32(0)]],
+                },
+                ParamGroup { entries: lights },
+                ParamGroup {
+                    entries: vec![
+                        [0.0, 0.0, 0.0].map(ParamValue::F32).to_vec(),
+                        [240.0, 1.0, 1.0].map(ParamValue::F32).to_vec(),
+                    ],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn light_set_param_lights_fog() {
+        let param = light_set_param();
+        assert_eq!(1, RenderGlobals::light_set_count(&param));
+
+        let globals = RenderGlobals::from_light_set_lights_fog(&param, 0).unwrap();
+
+        assert_eq_approx!(vec4(0.5, 0.0, 0.0, 0.0), globals.light_dir_colors[0]);
+        assert_eq_approx!(vec4(0.0, 0.5, 0.0, 0.0), globals.light_dir_colors[1]);
+        assert_eq!(Vec4::ZERO, globals.light_dir_colors[2]);
+
+        assert_eq_approx!(vec4(0.0, 1.0, 0.0, 0.0), globals.light_dirs[0]);
+        assert_eq_approx!(vec4(0.0, 0.0, 1.0, 0.0), globals.light_dirs[1]);
+
+        assert_eq!(vec4(0.0, 0.0, 1.0, 1.0), globals.fog_color);
+        assert_eq!(
+            RenderGlobals::default().ambient_color,
+            globals.ambient_color
+        );
+    }
+
+    #[test]
+    fn light_set_param_missing_values() {
+        let mut param = light_set_param();
+        assert_eq!(
+            Err(LightSetParamError::MissingEntry { group: 1, entry: 8 }),
+            RenderGlobals::from_light_set_lights_fog(&param, 1)
+        );
+
+        param.groups[2].entries[1].pop();
+        assert_eq!(
+            Err(LightSetParamError::InvalidValue {
+                group: 2,
+                entry: 1,
+                value: 2
+            }),
+            RenderGlobals::from_light_set_lights_fog(&param, 0)
+        );
+
+        param.groups.pop();
+        assert_eq!(
+            Err(LightSetParamError::MissingGroup(2)),
+            RenderGlobals::from_light_set_lights_fog(&param, 0)
+        );
+    }
+}
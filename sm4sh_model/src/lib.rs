@@ -1,24 +1,26 @@
 use std::{
     borrow::Cow,
-    collections::BTreeSet,
+    collections::{BTreeMap, BTreeSet},
     io::{Cursor, Seek, Write},
     path::Path,
 };
 use thiserror::Error;
 use vertex::{
-    Vertices, buffer0_stride, buffer1_stride, read_vertex_indices, read_vertices,
-    triangle_strip_to_list, write_vertex_indices, write_vertices,
+    StripJoin, VertexCountError, Vertices, buffer0_stride, buffer1_stride, read_vertex_indices,
+    read_vertices, triangle_list_to_strip, triangle_strip_to_list, write_vertex_indices,
+    write_vertices,
 };
 
 use binrw::BinResult;
 use glam::{EulerRot, Mat4, Vec3, Vec4, Vec4Swizzles};
+use log::warn;
 use sm4sh_lib::{
     nud::{
         BoundingSphere, Material, MaterialProperty, MaterialTexture, Mesh, MeshGroup, Nud,
         VertexIndexFlags,
     },
-    nut::{Ntp3, Nut},
-    vbn::Vbn,
+    nut::{CreateSurfaceError, Ntp3, Nut, TextureHeader},
+    vbn::{Bone, BoneTransform, Vbn, VbnInner},
 };
 
 pub use sm4sh_lib::nud::{
@@ -30,9 +32,15 @@ pub use sm4sh_lib::vbn::BoneType;
 
 pub mod animation;
 pub mod database;
+pub mod globals;
+pub mod material;
 pub mod model;
+pub mod normals;
+pub mod optimize;
+pub mod shadergen;
 pub mod skinning;
 pub mod texture;
+pub mod validate;
 pub mod vertex;
 
 /// Load a nud model from `path` and the corresponding `"model.nut"` and `"model.vbn"` if present.
@@ -128,6 +136,14 @@ pub enum PrimitiveType {
     TriangleStrip,
 }
 
+/// Options for [NudModel::to_nud_with_options].
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct NudExportOptions {
+    /// Convert [PrimitiveType::TriangleList] meshes to triangle strips if not `None`.
+    /// In game models mostly use triangle strips with primitive restart.
+    pub triangle_strips: Option<StripJoin>,
+}
+
 #[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 #[derive(Debug, PartialEq, Clone)]
 pub struct ImageTexture {
@@ -167,6 +183,48 @@ pub enum CreateNutError {
     Nut(#[from] sm4sh_lib::nut::CreateNutError),
 }
 
+#[derive(Debug, PartialEq, Error)]
+pub enum CreateVbnError {
+    #[error("bone {index} has type {bone_type:?} after a bone with type {previous:?}")]
+    BoneTypeOrder {
+        index: usize,
+        bone_type: BoneType,
+        previous: BoneType,
+    },
+}
+
+#[derive(Debug, Error)]
+pub enum CreateNudError {
+    #[error("error writing NUD data")]
+    Binrw(#[from] binrw::Error),
+
+    #[error(
+        "mesh {mesh_index} in group {group_name:?} has {count} vertices, which exceeds the maximum of {MAX_VERTEX_COUNT}"
+    )]
+    VertexCount {
+        group_name: String,
+        mesh_index: usize,
+        count: usize,
+    },
+
+    #[error(
+        "mesh {mesh_index} in group {group_name:?} has {count} vertex indices, which exceeds the maximum of {MAX_INDEX_COUNT}"
+    )]
+    IndexCount {
+        group_name: String,
+        mesh_index: usize,
+        count: usize,
+    },
+}
+
+/// The maximum number of vertices for a [NudMesh].
+///
+/// Index `0xFFFF` is reserved for primitive restart.
+pub const MAX_VERTEX_COUNT: usize = u16::MAX as usize;
+
+/// The maximum number of vertex indices for a [NudMesh].
+pub const MAX_INDEX_COUNT: usize = u16::MAX as usize;
+
 impl NudModel {
     pub fn from_nud(nud: &Nud, nut: Option<&Nut>, vbn: Option<&Vbn>) -> BinResult<Self> {
         let mut groups = Vec::new();
@@ -230,7 +288,15 @@ impl NudModel {
         })
     }
 
-    pub fn to_nud(&self) -> BinResult<Nud> {
+    /// Convert the model to NUD data.
+    ///
+    /// Meshes exceeding [MAX_VERTEX_COUNT] or [MAX_INDEX_COUNT] return an error.
+    /// Use [NudMesh::split] to split meshes before conversion.
+    pub fn to_nud(&self) -> Result<Nud, CreateNudError> {
+        self.to_nud_with_options(&NudExportOptions::default())
+    }
+
+    pub fn to_nud_with_options(&self, options: &NudExportOptions) -> Result<Nud, CreateNudError> {
         let mut mesh_groups = Vec::new();
 
         let mut buffer0 = Cursor::new(Vec::new());
@@ -245,7 +311,15 @@ impl NudModel {
             }
 
             let mut meshes = Vec::new();
-            for mesh in &group.meshes {
+            for (mesh_index, mesh) in group.meshes.iter().enumerate() {
+                if mesh.vertices.positions.len() > MAX_VERTEX_COUNT {
+                    return Err(CreateNudError::VertexCount {
+                        group_name: group.name.clone(),
+                        mesh_index,
+                        count: mesh.vertices.positions.len(),
+                    });
+                }
+
                 let vertex_buffer0_offset = buffer0.position() as u32;
                 let vertex_buffer1_offset = buffer1.position() as u32;
                 let vertex_indices_offset = index_buffer.position() as u32;
@@ -255,7 +329,26 @@ impl NudModel {
                 align(&mut buffer0, 16, 0u8)?;
                 align(&mut buffer1, 16, 0u8)?;
 
-                write_vertex_indices(&mut index_buffer, &mesh.vertex_indices)?;
+                let (vertex_indices, primitive_type) =
+                    match (mesh.primitive_type, options.triangle_strips) {
+                        (PrimitiveType::TriangleList, Some(join)) => (
+                            Cow::Owned(triangle_list_to_strip(&mesh.vertex_indices, join)),
+                            PrimitiveType::TriangleStrip,
+                        ),
+                        (primitive_type, _) => {
+                            (Cow::Borrowed(&mesh.vertex_indices[..]), primitive_type)
+                        }
+                    };
+
+                if vertex_indices.len() > MAX_INDEX_COUNT {
+                    return Err(CreateNudError::IndexCount {
+                        group_name: group.name.clone(),
+                        mesh_index,
+                        count: vertex_indices.len(),
+                    });
+                }
+
+                write_vertex_indices(&mut index_buffer, &vertex_indices)?;
 
                 // TODO: Is there a nicer way of setting offsets to 0?
                 let stride0 = buffer0_stride(vertex_flags);
@@ -286,13 +379,13 @@ impl NudModel {
                     material2: mesh.material2.as_ref().map(material),
                     material3: mesh.material3.as_ref().map(material),
                     material4: mesh.material4.as_ref().map(material),
-                    vertex_index_count: mesh.vertex_indices.len() as u16,
+                    vertex_index_count: vertex_indices.len() as u16,
                     vertex_index_flags: VertexIndexFlags::new(
                         false,
                         false,
                         vertex_flags.bones() != sm4sh_lib::nud::BoneType::None,
                         0u8.into(),
-                        mesh.primitive_type == PrimitiveType::TriangleList,
+                        primitive_type == PrimitiveType::TriangleList,
                         false,
                     ),
                     unk: [0; 3],
@@ -349,20 +442,107 @@ impl NudModel {
         })
     }
 
+    /// Calculate a sphere containing the vertices of all groups in the rest pose.
+    /// See [NudMeshGroup::calculate_bounding_sphere] for details.
+    pub fn calculate_bounding_sphere(&self) -> Vec4 {
+        let positions: Vec<_> = self
+            .groups
+            .iter()
+            .flat_map(|g| g.rest_pose_positions(self.skeleton.as_ref()))
+            .collect();
+        model::calculate_bounding_sphere(&positions)
+    }
+
     pub fn to_nut(&self) -> Result<Nut, CreateNutError> {
-        // TODO: add option for tiled ntwu nut?
-        // TODO: add option for different nut versions and types?
-        let textures = self
-            .textures
+        create_nut(&self.textures)
+    }
+
+    /// Apply the skinning for `animation` at `frame` to the vertices for each mesh in each group.
+    ///
+    /// Vertices are returned unchanged if the model has no skeleton.
+    pub fn posed_vertices(
+        &self,
+        animation: &animation::Animation,
+        frame: f32,
+    ) -> Vec<Vec<skinning::PosedVertices>> {
+        let (bone_transforms, skinning_transforms) = match &self.skeleton {
+            Some(skeleton) => (
+                animation.model_space_transforms(skeleton, frame),
+                animation.skinning_transforms(skeleton, frame),
+            ),
+            None => (Vec::new(), Vec::new()),
+        };
+
+        self.groups
+            .iter()
+            .map(|group| {
+                group
+                    .meshes
+                    .iter()
+                    .map(|mesh| {
+                        skinning::posed_vertices(
+                            group,
+                            mesh,
+                            &bone_transforms,
+                            &skinning_transforms,
+                        )
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl NudMeshGroup {
+    /// Calculate a sphere containing the vertices of all meshes in the rest pose.
+    ///
+    /// Groups with a parent bone that use the parent transform are transformed by the `skeleton` if present.
+    /// See [model::calculate_bounding_sphere] for details.
+    pub fn calculate_bounding_sphere(&self, skeleton: Option<&VbnSkeleton>) -> Vec4 {
+        model::calculate_bounding_sphere(&self.rest_pose_positions(skeleton))
+    }
+
+    fn rest_pose_positions(&self, skeleton: Option<&VbnSkeleton>) -> Vec<Vec3> {
+        // Match the parenting logic for rendering with no animation applied.
+        // Skinning and other parenting have identity transforms in the rest pose.
+        let transform = match (self.parent_bone_index, skeleton) {
+            (Some(parent_bone_index), Some(skeleton)) if self.name.contains("NSC") => skeleton
+                .model_space_transforms()
+                .get(parent_bone_index)
+                .copied()
+                .unwrap_or(Mat4::IDENTITY),
+            _ => Mat4::IDENTITY,
+        };
+
+        self.meshes
             .iter()
-            .map(|t| Ok((t.hash_id, t.to_surface()?)))
-            .collect::<Result<Vec<_>, CreateNutError>>()?;
-        let ntp3 = Ntp3::from_textures_v2(textures.into_iter())?;
-        Ok(Nut::Ntp3(ntp3))
+            .flat_map(|m| &m.vertices.positions)
+            .map(|p| transform.transform_point3(*p))
+            .collect()
     }
 }
 
 impl NudMesh {
+    /// The number of vertices if all attributes and vertex indices are consistent.
+    ///
+    /// Primitive restart indices for [PrimitiveType::TriangleStrip] are ignored.
+    pub fn vertex_count(&self) -> Result<usize, VertexCountError> {
+        let vertex_count = self.vertices.vertex_count()?;
+        let is_restart =
+            |i: u16| self.primitive_type == PrimitiveType::TriangleStrip && i == u16::MAX;
+        match self
+            .vertex_indices
+            .iter()
+            .find(|i| !is_restart(**i) && **i as usize >= vertex_count)
+        {
+            Some(index) => Err(VertexCountError::Index {
+                index: *index,
+                vertex_count,
+            }),
+            None => Ok(vertex_count),
+        }
+    }
+
     pub fn triangle_list_indices(&self) -> Cow<'_, [u16]> {
         match self.primitive_type {
             PrimitiveType::TriangleList => Cow::Borrowed(&self.vertex_indices),
@@ -371,6 +551,108 @@ impl NudMesh {
             }
         }
     }
+
+    /// Split the mesh into meshes with the same materials that each have at most
+    /// `max_vertex_count` vertices and `max_index_count` vertex indices.
+    ///
+    /// Use [MAX_VERTEX_COUNT] and [MAX_INDEX_COUNT] to fit the limits for [NudModel::to_nud].
+    /// Meshes that already fit the limits are returned unchanged.
+    /// Otherwise the split meshes use [PrimitiveType::TriangleList].
+    pub fn split(
+        &self,
+        max_vertex_count: usize,
+        max_index_count: usize,
+    ) -> Result<Vec<NudMesh>, VertexCountError> {
+        let vertex_count = self.vertex_count()?;
+        if vertex_count <= max_vertex_count && self.vertex_indices.len() <= max_index_count {
+            return Ok(vec![self.clone()]);
+        }
+
+        // Each mesh needs space for at least one triangle.
+        let max_vertex_count = max_vertex_count.max(3);
+        let max_index_count = max_index_count.max(3);
+
+        let mut meshes = Vec::new();
+
+        let mut remap = BTreeMap::new();
+        let mut vertex_indices = Vec::new();
+        let mut vertices = Vec::new();
+        for triangle in self.triangle_list_indices().chunks_exact(3) {
+            let new_vertex_count = triangle
+                .iter()
+                .filter(|i| !remap.contains_key(*i))
+                .collect::<BTreeSet<_>>()
+                .len();
+            if vertices.len() + new_vertex_count > max_vertex_count
+                || vertex_indices.len() + 3 > max_index_count
+            {
+                meshes.push(self.split_mesh(&vertices, std::mem::take(&mut vertex_indices)));
+                remap.clear();
+                vertices.clear();
+            }
+
+            for i in triangle {
+                let index = *remap.entry(*i).or_insert_with(|| {
+                    vertices.push(*i as usize);
+                    vertices.len() - 1
+                });
+                vertex_indices.push(index as u16);
+            }
+        }
+        if !vertex_indices.is_empty() {
+            meshes.push(self.split_mesh(&vertices, vertex_indices));
+        }
+
+        Ok(meshes)
+    }
+
+    fn split_mesh(&self, vertices: &[usize], vertex_indices: Vec<u16>) -> NudMesh {
+        NudMesh {
+            vertices: self.vertices.select(vertices),
+            vertex_indices,
+            primitive_type: PrimitiveType::TriangleList,
+            ..self.clone()
+        }
+    }
+
+    /// Recalculate smooth normals from the triangle faces.
+    /// See [normals::calculate_smooth_normals] for details.
+    ///
+    /// This preserves the [vertex::Normals] variant and any values other than the normal XYZ.
+    /// Meshes without normals are unchanged.
+    pub fn calculate_smooth_normals(&mut self, smooth_angle: Option<f32>) {
+        let normals = normals::calculate_smooth_normals(
+            &self.vertices.positions,
+            &self.triangle_list_indices(),
+            smooth_angle,
+        );
+        self.vertices.normals = self.vertices.normals.with_normals(&normals);
+    }
+
+    /// Calculate tangents and bitangents from the normals and first UV map.
+    /// Meshes without tangents and bitangents are converted to the appropriate [vertex::Normals] variant.
+    ///
+    /// Meshes without normals or UVs are unchanged.
+    pub fn calculate_tangents_bitangents(&mut self) -> Result<(), VertexCountError> {
+        let Some(normals) = self.vertices.normals.normals() else {
+            return Ok(());
+        };
+        let Some(uvs) = self.vertices.uvs.uvs().into_iter().next() else {
+            return Ok(());
+        };
+
+        let (tangents, bitangents) = normals::calculate_tangents_bitangents(
+            &self.vertices.positions,
+            &normals,
+            &uvs,
+            &self.triangle_list_indices(),
+        );
+        self.vertices.normals = self
+            .vertices
+            .normals
+            .with_tangents_bitangents(&tangents, &bitangents)?;
+        Ok(())
+    }
 }
 
 impl VbnBone {
@@ -404,6 +686,84 @@ impl VbnSkeleton {
 
         final_transforms
     }
+
+    /// The index in [bones](#structfield.bones) of the bone with the given `hash`.
+    ///
+    /// This is used to find the bone for animation data like [animation::AnimationNode::hash].
+    pub fn bone_index_by_hash(&self, hash: u32) -> Option<usize> {
+        self.bones.iter().position(|b| b.hash == hash)
+    }
+
+    /// Convert the skeleton to big endian VBN data.
+    ///
+    /// Bones with a `hash` of `0` are assigned a hash from [bone_name_hash].
+    /// Bones are not reordered since mesh bone indices refer to the current order.
+    /// Returns an error if the bones are not sorted by [BoneType]
+    /// since VBN files only store the number of bones for each type.
+    pub fn to_vbn(&self) -> Result<Vbn, CreateVbnError> {
+        for (i, bones) in self.bones.windows(2).enumerate() {
+            if (bones[1].bone_type as usize) < (bones[0].bone_type as usize) {
+                return Err(CreateVbnError::BoneTypeOrder {
+                    index: i + 1,
+                    bone_type: bones[1].bone_type,
+                    previous: bones[0].bone_type,
+                });
+            }
+        }
+
+        let mut bone_count_per_type = [0; 4];
+        for bone in &self.bones {
+            bone_count_per_type[bone.bone_type as usize] += 1;
+        }
+
+        Ok(Vbn::Be(VbnInner {
+            // TODO: Are other versions used in game?
+            version: 0x00020001,
+            total_bone_count: self.bones.len() as u32,
+            bone_count_per_type,
+            bones: self
+                .bones
+                .iter()
+                .map(|b| Bone {
+                    name: b.name.clone(),
+                    bone_type: b.bone_type,
+                    // Root bones use 0xFFFFFFF to match in game files.
+                    parent_bone_index: b.parent_bone_index.map(|i| i as i32).unwrap_or(0xFFFFFFF),
+                    bone_id: if b.hash == 0 {
+                        bone_name_hash(&b.name)
+                    } else {
+                        b.hash
+                    },
+                })
+                .collect(),
+            transforms: self
+                .bones
+                .iter()
+                .map(|b| BoneTransform {
+                    translation: b.translation.to_array(),
+                    rotation: b.rotation.to_array(),
+                    scale: b.scale.to_array(),
+                })
+                .collect(),
+        }))
+    }
+}
+
+/// Calculate the hash for a bone `name` used for [VbnBone::hash].
+///
+/// This assumes the game uses the CRC32 of the name for VBN bone IDs and OMO node hashes.
+/// Use `sm4sh_test --vbn` to report in game bones that don't match.
+pub fn bone_name_hash(name: &str) -> u32 {
+    // Bitwise CRC32 with the reversed polynomial 0xEDB88320.
+    let mut crc = 0xFFFFFFFFu32;
+    for byte in name.bytes() {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB88320 & mask);
+        }
+    }
+    !crc
 }
 
 fn vbn_skeleton(vbn: &Vbn) -> VbnSkeleton {
@@ -488,9 +848,8 @@ fn bounding_sphere(sphere: Vec4) -> BoundingSphere {
     }
 }
 
-// TODO: make this error public?
 #[derive(Debug, Error)]
-enum CreateImageTextureError {
+pub enum CreateImageTextureError {
     #[error("error creating surface")]
     Surface(#[from] sm4sh_lib::nut::CreateSurfaceError),
 
@@ -498,32 +857,93 @@ enum CreateImageTextureError {
     Nut(#[from] sm4sh_lib::nut::CreateNutError),
 }
 
-fn nut_textures(nut: &Nut) -> Result<Vec<ImageTexture>, CreateImageTextureError> {
+pub(crate) fn create_nut(textures: &[ImageTexture]) -> Result<Nut, CreateNutError> {
+    // TODO: add option for tiled ntwu nut?
+    // TODO: add option for different nut versions and types?
+    let textures = textures
+        .iter()
+        .map(|t| Ok((t.hash_id, t.to_surface()?)))
+        .collect::<Result<Vec<_>, CreateNutError>>()?;
+    let ntp3 = Ntp3::from_textures_v2(textures.into_iter())?;
+    Ok(Nut::Ntp3(ntp3))
+}
+
+/// Load all textures from `nut`.
+///
+/// [NutFormat::B5G6R5Unorm] textures are decoded to [NutFormat::Rgba8Unorm]
+/// since image_dds does not support this format.
+/// The conversion is logged as a warning since saving the textures with [NudModel::to_nut]
+/// will use [NutFormat::Rgba8Unorm] instead of the original format.
+pub fn nut_textures(nut: &Nut) -> Result<Vec<ImageTexture>, CreateImageTextureError> {
     match nut {
         Nut::Ntwu(ntwu) => ntwu
             .textures
             .iter()
-            .map(|t| ImageTexture::from_surface(t.gidx.hash, t.to_surface()?).map_err(Into::into))
+            .map(|t| {
+                nut_image_texture(
+                    t.gidx.hash,
+                    &t.header,
+                    || Ok(t.deswizzle()?),
+                    || t.to_surface(),
+                )
+            })
             .collect(),
         Nut::Ntp3(ntp3) => match &ntp3.inner {
             sm4sh_lib::nut::Ntp3Inner::V1(v1) => v1
                 .textures
                 .iter()
                 .map(|t| {
-                    ImageTexture::from_surface(t.gidx.hash, t.to_surface()?).map_err(Into::into)
+                    nut_image_texture(
+                        t.gidx.hash,
+                        &t.header,
+                        || Ok(t.image_data()),
+                        || t.to_surface(),
+                    )
                 })
                 .collect(),
             sm4sh_lib::nut::Ntp3Inner::V2(v2) => v2
                 .textures
                 .iter()
                 .map(|t| {
-                    ImageTexture::from_surface(t.gidx.hash, t.to_surface()?).map_err(Into::into)
+                    nut_image_texture(
+                        t.gidx.hash,
+                        &t.header,
+                        || Ok(t.image_data()),
+                        || t.to_surface(),
+                    )
                 })
                 .collect(),
         },
     }
 }
 
+fn nut_image_texture(
+    hash: u32,
+    header: &TextureHeader,
+    image_data: impl FnOnce() -> Result<Vec<u8>, CreateSurfaceError>,
+    surface: impl FnOnce() -> Result<image_dds::Surface<Vec<u8>>, CreateSurfaceError>,
+) -> Result<ImageTexture, CreateImageTextureError> {
+    if header.format == NutFormat::B5G6R5Unorm {
+        // image_dds doesn't support this format, so decode to a format supported everywhere.
+        warn!(
+            "Converting texture {hash:08X} from {:?} to {:?}",
+            header.format,
+            NutFormat::Rgba8Unorm
+        );
+        Ok(ImageTexture {
+            hash_id: hash,
+            width: header.width as u32,
+            height: header.height as u32,
+            mipmap_count: header.mipmap_count as u32,
+            layers: header.layers(),
+            image_format: NutFormat::Rgba8Unorm,
+            image_data: texture::decode_b5g6r5(&image_data()?),
+        })
+    } else {
+        Ok(ImageTexture::from_surface(hash, surface()?)?)
+    }
+}
+
 fn align<W: Write + Seek>(writer: &mut W, align: u64, pad: u8) -> Result<(), std::io::Error> {
     let size = writer.stream_position()?;
     let aligned_size = size.next_multiple_of(align);
@@ -533,7 +953,7 @@ fn align<W: Write + Seek>(writer: &mut W, align: u64, pad: u8) -> Result<(), std
 }
 
 fn nud_material(material: &sm4sh_lib::nud::Material) -> NudMaterial {
-    NudMaterial {
+    let material = NudMaterial {
         shader_id: material.shader_id,
         src_factor: material.src_factor,
         dst_factor: material.dst_factor,
@@ -561,7 +981,13 @@ fn nud_material(material: &sm4sh_lib::nud::Material) -> NudMaterial {
                 values: p.values.clone(),
             })
             .collect(),
+    };
+
+    for error in material.property_errors() {
+        warn!("Material {:X}: {error}", material.shader_id);
     }
+
+    material
 }
 
 impl ImageTexture {
@@ -596,3 +1022,378 @@ impl ImageTexture {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bone_name_hash_check_value() {
+        assert_eq!(0xCBF43926, bone_name_hash("123456789"));
+    }
+
+    #[test]
+    fn skeleton_to_vbn() {
+        let skeleton = VbnSkeleton {
+            bones: vec![
+                VbnBone {
+                    name: "a".to_string(),
+                    hash: 1,
+                    parent_bone_index: None,
+                    bone_type: BoneType::Normal,
+                    translation: Vec3::new(1.0, 2.0, 3.0),
+                    rotation: Vec3::ZERO,
+                    scale: Vec3::ONE,
+                },
+                VbnBone {
+                    name: "b".to_string(),
+                    hash: 0,
+                    parent_bone_index: Some(0),
+                    bone_type: BoneType::Helper,
+                    translation: Vec3::ZERO,
+                    rotation: Vec3::new(0.5, 0.0, 0.0),
+                    scale: Vec3::ONE,
+                },
+            ],
+        };
+
+        let vbn = skeleton.to_vbn().unwrap();
+        let Vbn::Be(inner) = &vbn else {
+            panic!("expected big endian vbn")
+        };
+        assert_eq!(2, inner.total_bone_count);
+        assert_eq!([1, 0, 1, 0], inner.bone_count_per_type);
+        assert_eq!(0xFFFFFFF, inner.bones[0].parent_bone_index);
+        assert_eq!(0, inner.bones[1].parent_bone_index);
+        assert_eq!(bone_name_hash("b"), inner.bones[1].bone_id);
+
+        // New bones should be assigned hashes but otherwise convert back unchanged.
+        let new_skeleton = vbn_skeleton(&vbn);
+        assert_eq!(skeleton.bones[0], new_skeleton.bones[0]);
+        assert_eq!(
+            VbnBone {
+                hash: bone_name_hash("b"),
+                ..skeleton.bones[1].clone()
+            },
+            new_skeleton.bones[1]
+        );
+        assert_eq!(
+            Some(1),
+            new_skeleton.bone_index_by_hash(bone_name_hash("b"))
+        );
+        assert_eq!(None, new_skeleton.bone_index_by_hash(2));
+    }
+
+    #[test]
+    fn skeleton_to_vbn_interleaved_bone_types() {
+        let bone = |bone_type| VbnBone {
+            name: "a".to_string(),
+            hash: 1,
+            parent_bone_index: None,
+            bone_type,
+            translation: Vec3::ZERO,
+            rotation: Vec3::ZERO,
+            scale: Vec3::ONE,
+        };
+        let skeleton = VbnSkeleton {
+            bones: vec![
+                bone(BoneType::Normal),
+                bone(BoneType::Helper),
+                bone(BoneType::Normal),
+            ],
+        };
+        assert_eq!(
+            Err(CreateVbnError::BoneTypeOrder {
+                index: 2,
+                bone_type: BoneType::Normal,
+                previous: BoneType::Helper
+            }),
+            skeleton.to_vbn()
+        );
+    }
+
+    fn posed_model(parent_bone_index: Option<usize>, bones: Option<vertex::Bones>) -> NudModel {
+        NudModel {
+            groups: vec![NudMeshGroup {
+                name: "a".to_string(),
+                meshes: vec![NudMesh {
+                    vertices: Vertices {
+                        positions: vec![Vec3::new(1.0, 0.0, 0.0)],
+                        normals: vertex::Normals::NormalsFloat32(vec![vertex::NormalsFloat32 {
+                            unk1: 1.0,
+                            normal: [0.0, 2.0, 0.0, 1.0],
+                        }]),
+                        bones,
+                        colors: None,
+                        uvs: vertex::Uvs::Float16(Vec::new()),
+                    },
+                    vertex_indices: vec![0, 0, 0],
+                    primitive_type: PrimitiveType::TriangleList,
+                    material1: None,
+                    material2: None,
+                    material3: None,
+                    material4: None,
+                }],
+                sort_bias: 0.0,
+                bounding_sphere: Vec4::ZERO,
+                parent_bone_index,
+            }],
+            textures: Vec::new(),
+            bounding_sphere: Vec4::ZERO,
+            skeleton: Some(VbnSkeleton {
+                bones: vec![
+                    VbnBone {
+                        name: "a".to_string(),
+                        hash: 1,
+                        parent_bone_index: None,
+                        bone_type: BoneType::Normal,
+                        translation: Vec3::ZERO,
+                        rotation: Vec3::ZERO,
+                        scale: Vec3::ONE,
+                    },
+                    VbnBone {
+                        name: "b".to_string(),
+                        hash: 2,
+                        parent_bone_index: Some(0),
+                        bone_type: BoneType::Normal,
+                        translation: Vec3::new(0.0, 1.0, 0.0),
+                        rotation: Vec3::ZERO,
+                        scale: Vec3::ONE,
+                    },
+                ],
+            }),
+        }
+    }
+
+    fn translation_animation() -> animation::Animation {
+        animation::Animation {
+            nodes: vec![animation::AnimationNode {
+                hash: 2,
+                translation_keyframes: vec![Some(Vec3::new(0.0, 3.0, 0.0))],
+                rotation_keyframes: vec![None],
+                scale_keyframes: vec![None],
+            }],
+            frame_count: 1,
+        }
+    }
+
+    #[test]
+    fn posed_vertices_no_skinning() {
+        let model = posed_model(None, None);
+        assert_eq!(
+            vec![vec![skinning::PosedVertices {
+                positions: vec![Vec3::new(1.0, 0.0, 0.0)],
+                normals: Some(vec![Vec3::new(0.0, 1.0, 0.0)])
+            }]],
+            model.posed_vertices(&translation_animation(), 0.0)
+        );
+    }
+
+    #[test]
+    fn posed_vertices_skinning() {
+        let model = posed_model(
+            None,
+            Some(vertex::Bones {
+                bone_indices: vec![[0, 1, 0, 0]],
+                weights: vec![Vec4::new(0.5, 0.5, 0.0, 0.0)],
+                element_type: vertex::BoneElementType::Byte,
+            }),
+        );
+        assert_eq!(
+            vec![vec![skinning::PosedVertices {
+                positions: vec![Vec3::new(1.0, 1.0, 0.0)],
+                normals: Some(vec![Vec3::new(0.0, 1.0, 0.0)])
+            }]],
+            model.posed_vertices(&translation_animation(), 0.0)
+        );
+    }
+
+    #[test]
+    fn posed_vertices_parent_bone() {
+        let model = posed_model(Some(1), None);
+        assert_eq!(
+            vec![vec![skinning::PosedVertices {
+                positions: vec![Vec3::new(1.0, 2.0, 0.0)],
+                normals: Some(vec![Vec3::new(0.0, 1.0, 0.0)])
+            }]],
+            model.posed_vertices(&translation_animation(), 0.0)
+        );
+    }
+
+    #[test]
+    fn posed_vertices_parent_bone_nsc() {
+        let mut model = posed_model(Some(1), None);
+        model.groups[0].name = "a_NSC".to_string();
+        assert_eq!(
+            vec![vec![skinning::PosedVertices {
+                positions: vec![Vec3::new(1.0, 3.0, 0.0)],
+                normals: Some(vec![Vec3::new(0.0, 1.0, 0.0)])
+            }]],
+            model.posed_vertices(&translation_animation(), 0.0)
+        );
+    }
+
+    #[test]
+    fn to_nud_triangle_strips() {
+        let mut model = posed_model(None, None);
+        model.groups[0].meshes[0].vertices.positions = vec![Vec3::ZERO; 4];
+        model.groups[0].meshes[0].vertices.normals = vertex::Normals::None(vec![0.0; 4]);
+        model.groups[0].meshes[0].vertex_indices = vec![0, 1, 2, 2, 1, 3];
+
+        let nud = model.to_nud().unwrap();
+        assert_eq!(6, nud.mesh_groups[0].meshes[0].vertex_index_count);
+        assert!(
+            nud.mesh_groups[0].meshes[0]
+                .vertex_index_flags
+                .is_triangle_list()
+        );
+
+        let nud = model
+            .to_nud_with_options(&NudExportOptions {
+                triangle_strips: Some(StripJoin::PrimitiveRestart),
+            })
+            .unwrap();
+        assert_eq!(4, nud.mesh_groups[0].meshes[0].vertex_index_count);
+        assert!(
+            !nud.mesh_groups[0].meshes[0]
+                .vertex_index_flags
+                .is_triangle_list()
+        );
+
+        let new_model = NudModel::from_nud(&nud, None, None).unwrap();
+        assert_eq!(
+            PrimitiveType::TriangleStrip,
+            new_model.groups[0].meshes[0].primitive_type
+        );
+        assert_eq!(
+            vec![0, 1, 2, 2, 1, 3],
+            new_model.groups[0].meshes[0]
+                .triangle_list_indices()
+                .to_vec()
+        );
+    }
+
+    #[test]
+    fn to_nud_vertex_count_overflow() {
+        let mut model = posed_model(None, None);
+        let mesh = &mut model.groups[0].meshes[0];
+        mesh.vertices.positions = vec![Vec3::ZERO; MAX_VERTEX_COUNT + 1];
+        mesh.vertices.normals = vertex::Normals::None(vec![0.0; MAX_VERTEX_COUNT + 1]);
+
+        assert!(matches!(
+            model.to_nud(),
+            Err(CreateNudError::VertexCount {
+                mesh_index: 0,
+                count: 65536,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn to_nud_index_count_overflow() {
+        let mut model = posed_model(None, None);
+        model.groups[0].meshes[0].vertex_indices = vec![0; MAX_INDEX_COUNT + 1];
+
+        assert!(matches!(
+            model.to_nud(),
+            Err(CreateNudError::IndexCount {
+                mesh_index: 0,
+                count: 65536,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn nut_image_texture_b5g6r5() {
+        let header = TextureHeader {
+            size: 0,
+            unk1: 0,
+            data_size: 2,
+            header_size: 0,
+            unk2: 0,
+            unk3: 0,
+            mipmap_count: 1,
+            unk4: 0,
+            format: NutFormat::B5G6R5Unorm,
+            width: 1,
+            height: 1,
+            unk5: 0,
+            caps2: sm4sh_lib::nut::Caps2::empty(),
+        };
+        let texture = nut_image_texture(
+            0x40000001,
+            &header,
+            || Ok(0b11111_000000_00000u16.to_le_bytes().to_vec()),
+            || unreachable!(),
+        )
+        .unwrap();
+        assert_eq!(
+            ImageTexture {
+                hash_id: 0x40000001,
+                width: 1,
+                height: 1,
+                mipmap_count: 1,
+                layers: 1,
+                image_format: NutFormat::Rgba8Unorm,
+                image_data: vec![255, 0, 0, 255],
+            },
+            texture
+        );
+    }
+
+    #[test]
+    fn split_mesh_limits() {
+        let mut mesh = posed_model(None, None).groups[0].meshes[0].clone();
+        mesh.vertices.positions = (0..6).map(|i| Vec3::splat(i as f32)).collect();
+        mesh.vertices.normals = vertex::Normals::None((0..6).map(|i| i as f32).collect());
+        mesh.vertex_indices = vec![0, 1, 2, 2, 1, 3, 3, 4, 5];
+
+        // Already within the limits.
+        assert_eq!(vec![mesh.clone()], mesh.split(6, 9).unwrap());
+
+        let meshes = mesh.split(4, 9).unwrap();
+        assert_eq!(2, meshes.len());
+        assert_eq!(vec![0, 1, 2, 2, 1, 3], meshes[0].vertex_indices);
+        assert_eq!(
+            vertex::Normals::None(vec![0.0, 1.0, 2.0, 3.0]),
+            meshes[0].vertices.normals
+        );
+        assert_eq!(vec![0, 1, 2], meshes[1].vertex_indices);
+        assert_eq!(
+            vec![Vec3::splat(3.0), Vec3::splat(4.0), Vec3::splat(5.0)],
+            meshes[1].vertices.positions
+        );
+
+        let meshes = mesh.split(6, 3).unwrap();
+        assert_eq!(3, meshes.len());
+        assert!(meshes.iter().all(|m| m.vertex_indices == vec![0, 1, 2]));
+    }
+
+    #[test]
+    fn split_mesh_invalid_vertices() {
+        let mut mesh = posed_model(None, None).groups[0].meshes[0].clone();
+        mesh.vertices.positions = (0..6).map(|i| Vec3::splat(i as f32)).collect();
+        mesh.vertices.normals = vertex::Normals::None(vec![0.0; 5]);
+        mesh.vertex_indices = vec![0, 1, 2, 2, 1, 3, 3, 4, 5];
+        assert_eq!(
+            Err(VertexCountError::Attribute {
+                attribute: "normals",
+                count: 5,
+                vertex_count: 6
+            }),
+            mesh.split(4, 9)
+        );
+
+        mesh.vertices.normals = vertex::Normals::None(vec![0.0; 6]);
+        mesh.vertex_indices = vec![0, 1, 6];
+        assert_eq!(
+            Err(VertexCountError::Index {
+                index: 6,
+                vertex_count: 6
+            }),
+            mesh.split(4, 9)
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use smol_str::SmolStr;
+
+use crate::{
+    AlphaFunc, CullMode, DstFactor, MagFilter, MapMode, MinFilter, MipDetail, NudMaterial,
+    NudProperty, NudTexture, SrcFactor, WrapMode, database::ShaderDatabase,
+};
+
+mod properties;
+pub use properties::{PROPERTIES, PropertyError, PropertyInfo, PropertySemantic, property_info};
+
+/// The hash of the global white texture used for new material textures.
+pub const DEFAULT_TEXTURE_HASH: u32 = 0x10080000;
+
+/// Properties that are not uniform parameters but are present for most in game materials.
+const NON_PARAMETER_PROPERTIES: &[&str] = &["NU_materialHash"];
+
+/// Differences between the properties of a [NudMaterial] and the parameters for its shader.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct PropertyReport {
+    /// Parameters read by the shader that are not in the material.
+    pub missing: Vec<SmolStr>,
+    /// Properties in the material that are not read by the shader.
+    ///
+    /// This only considers parameters in the `MC` uniform buffer,
+    /// so some effect properties may be reported even if they are used.
+    pub extra: Vec<String>,
+}
+
+impl PropertyReport {
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty()
+    }
+}
+
+impl NudMaterial {
+    /// Create a material for `shader_id` with a texture for each sampler in order
+    /// and a property with the default values from [PROPERTIES] for each parameter.
+    ///
+    /// Textures use [DEFAULT_TEXTURE_HASH] and should be replaced with the desired textures.
+    /// Returns `None` if the shader is not in `database`.
+    pub fn from_shader(shader_id: u32, database: &ShaderDatabase) -> Option<Self> {
+        let program = database.get_shader(shader_id)?;
+
+        Some(Self {
+            shader_id,
+            src_factor: SrcFactor::One,
+            dst_factor: DstFactor::Zero,
+            alpha_func: AlphaFunc::Disabled,
+            alpha_test_ref: 0,
+            // TODO: Check the most common cull mode for in game materials.
+            cull_mode: CullMode::Inside,
+            textures: program
+                .samplers
+                .iter()
+                .map(|_| NudTexture {
+                    hash: DEFAULT_TEXTURE_HASH,
+                    map_mode: MapMode::TexCoord,
+                    wrap_mode_s: WrapMode::Repeat,
+                    wrap_mode_t: WrapMode::Repeat,
+                    min_filter: MinFilter::LinearMipmapLinear,
+                    mag_filter: MagFilter::Linear,
+                    mip_detail: MipDetail::OneMipLevelAnisotropicOff,
+                })
+                .collect(),
+            properties: program
+                .parameters
+                .iter()
+                .map(|name| NudProperty {
+                    name: name.to_string(),
+                    values: property_info(name)
+                        .map(|p| p.default[..p.value_count].to_vec())
+                        .unwrap_or_else(|| vec![0.0; 4]),
+                })
+                .collect(),
+        })
+    }
+
+    /// Compare the properties with the parameters for the shader in `database`.
+    ///
+    /// Returns `None` if the shader is not in `database`.
+    pub fn property_report(&self, database: &ShaderDatabase) -> Option<PropertyReport> {
+        let program = database.get_shader(self.shader_id)?;
+
+        let missing = program
+            .parameters
+            .iter()
+            .filter(|name| !self.properties.iter().any(|p| p.name == name.as_str()))
+            .cloned()
+            .collect();
+
+        let extra = self
+            .properties
+            .iter()
+            .filter(|p| {
+                !NON_PARAMETER_PROPERTIES.contains(&p.name.as_str())
+                    && !program
+                        .parameters
+                        .iter()
+                        .any(|name| name == p.name.as_str())
+            })
+            .map(|p| p.name.clone())
+            .collect();
+
+        Some(PropertyReport { missing, extra })
+    }
+}
+
+/// Values for parameter `name` that have no effect on the rendered result where possible.
+///
+/// This uses [PropertyInfo::default] and returns zero for unknown properties.
+pub fn default_property_values(name: &str) -> [f32; 4] {
+    property_info(name).map(|p| p.default).unwrap_or([0.0; 4])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeMap;
+
+    use crate::database::ShaderProgram;
+
+    fn database() -> ShaderDatabase {
+        ShaderDatabase::from_programs(BTreeMap::from([(
+            0x96000001,
+            ShaderProgram {
+                output_dependencies: Default::default(),
+                exprs: Vec::new(),
+                output_dependencies_xyz: Default::default(),
+                exprs_xyz: Vec::new(),
+                attributes: Vec::new(),
+                samplers: vec!["colorSampler".into(), "normalSampler".into()],
+                parameters: vec!["NU_colorSamplerUV".into(), "NU_aoMinGain".into()],
+            },
+        )]))
+    }
+
+    #[test]
+    fn from_shader_missing() {
+        assert_eq!(None, NudMaterial::from_shader(1, &database()));
+    }
+
+    #[test]
+    fn from_shader_textures_properties() {
+        let material = NudMaterial::from_shader(0x96000001, &database()).unwrap();
+        assert_eq!(2, material.textures.len());
+        assert_eq!(
+            vec![
+                NudProperty {
+                    name: "NU_colorSamplerUV".to_string(),
+                    values: vec![1.0, 1.0, 0.0, 0.0]
+                },
+                NudProperty {
+                    name: "NU_aoMinGain".to_string(),
+                    values: vec![0.0; 4]
+                }
+            ],
+            material.properties
+        );
+        assert!(material.property_report(&database()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn default_property_values_colors() {
+        for name in [
+            "NU_diffuseColor",
+            "NU_specularColor",
+            "NU_fresnelColor",
+            "NU_reflectionColor",
+        ] {
+            assert_eq!([1.0; 4], default_property_values(name), "{name}");
+        }
+        assert_eq!(
+            [1.0, 1.0, 0.0, 0.0],
+            default_property_values("NU_colorSamplerUV")
+        );
+        assert_eq!([0.0; 4], default_property_values("NU_unknown"));
+    }
+
+    #[test]
+    fn property_report_missing_extra() {
+        let mut material = NudMaterial::from_shader(0x96000001, &database()).unwrap();
+        material.properties.remove(0);
+        material.properties.push(NudProperty {
+            name: "NU_materialHash".to_string(),
+            values: vec![0.0],
+        });
+        material.properties.push(NudProperty {
+            name: "NU_testParam0".to_string(),
+            values: vec![0.0; 4],
+        });
+
+        assert_eq!(
+            Some(PropertyReport {
+                missing: vec!["NU_colorSamplerUV".into()],
+                extra: vec!["NU_testParam0".to_string()]
+            }),
+            material.property_report(&database())
+        );
+    }
+}
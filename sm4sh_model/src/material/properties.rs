@@ -0,0 +1,292 @@
+use glam::Vec4;
+
+use crate::{NudMaterial, NudProperty};
+
+/// How the values of a material property are used by the shaders.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PropertySemantic {
+    /// RGBA color values or color multipliers.
+    Color,
+    /// UV scale in XY and UV translation in ZW.
+    UvTransform,
+    /// A single value in X.
+    Scalar,
+    /// Parameters with different meanings for each component.
+    Params,
+    /// The bits of an integer hash rather than a float value.
+    Hash,
+}
+
+/// A known `NU_` material property.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PropertyInfo {
+    pub name: &'static str,
+    pub value_count: usize,
+    pub semantic: PropertySemantic,
+    /// Values that have no effect on the rendered result where possible.
+    ///
+    /// Colors for lighting terms like `NU_specularColor` default to white
+    /// since the intensity is controlled by the corresponding params like `NU_specularParams`.
+    pub default: [f32; 4],
+}
+
+const fn property(
+    name: &'static str,
+    semantic: PropertySemantic,
+    default: [f32; 4],
+) -> PropertyInfo {
+    PropertyInfo {
+        name,
+        value_count: 4,
+        semantic,
+        default,
+    }
+}
+
+const ONE: [f32; 4] = [1.0; 4];
+const ZERO: [f32; 4] = [0.0; 4];
+const UV: [f32; 4] = [1.0, 1.0, 0.0, 0.0];
+
+use PropertySemantic::{Color, Params, Scalar, UvTransform};
+
+/// All known material properties sorted by name.
+// TODO: Find the semantics for the remaining params and effect properties.
+pub static PROPERTIES: &[PropertyInfo] = &[
+    property("NU_alphaBlendParams", Params, ZERO),
+    property("NU_angleFadeParams", Params, ZERO),
+    property("NU_aoMinGain", Color, ZERO),
+    property("NU_colorGain", Color, ONE),
+    property("NU_colorOffset", Color, ZERO),
+    property("NU_colorSampler2UV", UvTransform, UV),
+    property("NU_colorSampler3UV", UvTransform, UV),
+    property("NU_colorSampler4UV", UvTransform, UV),
+    property("NU_colorSamplerUV", UvTransform, UV),
+    property("NU_colorStepUV", Params, ZERO),
+    property("NU_customSoftLightParams", Params, ZERO),
+    property("NU_diffuseColor", Color, ONE),
+    property("NU_dualNormalScrollParams", Params, ZERO),
+    property("NU_effColorGain", Color, ONE),
+    property("NU_effCombinerAlpha0", Params, ZERO),
+    property("NU_effCombinerColor0", Color, ZERO),
+    property("NU_effCombinerColor1", Color, ZERO),
+    property("NU_effDepthOffset", Scalar, ZERO),
+    property("NU_effMTBlendAlpha", Params, ZERO),
+    property("NU_effMTBlendParam0", Params, ZERO),
+    property("NU_effMTBlendParam1", Params, ZERO),
+    property("NU_effMTBlendParam2", Params, ZERO),
+    property("NU_effRefractParam", Params, ZERO),
+    property("NU_effRotUV", Params, ZERO),
+    property("NU_effScaleUV", Params, ONE),
+    property("NU_effSilhouetteColor", Color, ZERO),
+    property("NU_effSunShaftParams0", Params, ZERO),
+    property("NU_effSunShaftParams1", Params, ZERO),
+    property("NU_effTransUV", Params, ZERO),
+    property("NU_effUniverseParam", Params, ZERO),
+    property("NU_effYGradColorBottom", Color, ZERO),
+    property("NU_effYGradColorTop", Color, ZERO),
+    property("NU_effYGradParam", Params, ZERO),
+    property("NU_finalColorGain", Color, ONE),
+    property("NU_finalColorGain2", Color, ONE),
+    property("NU_finalColorGain3", Color, ONE),
+    property("NU_fogParams", Params, ZERO),
+    property("NU_fresnelColor", Color, ONE),
+    property("NU_fresnelParams", Params, ZERO),
+    // TODO: Check the value count with sm4sh_test --nud-model.
+    PropertyInfo {
+        name: "NU_materialHash",
+        value_count: 1,
+        semantic: PropertySemantic::Hash,
+        default: ZERO,
+    },
+    property("NU_normalParams", Params, ZERO),
+    property("NU_normalSamplerAUV", UvTransform, UV),
+    property("NU_normalSamplerBUV", UvTransform, UV),
+    property("NU_reflectionColor", Color, ONE),
+    property("NU_reflectionParams", Params, ZERO),
+    property("NU_rotatePivotUV", Params, ZERO),
+    property("NU_softLightingParams", Params, ZERO),
+    property("NU_specularColor", Color, ONE),
+    property("NU_specularColorGain", Color, ONE),
+    property("NU_specularParams", Params, ZERO),
+    property("NU_testParam0", Params, ZERO),
+    property("NU_testParam1", Params, ZERO),
+    property("NU_testParam2", Params, ZERO),
+    property("NU_testParam3", Params, ZERO),
+    property("NU_translucentColor", Color, ZERO),
+    property("NU_zOffset", Scalar, ZERO),
+];
+
+/// The registry entry for the property `name` or `None` if the property is not known.
+pub fn property_info(name: &str) -> Option<&'static PropertyInfo> {
+    PROPERTIES
+        .binary_search_by(|p| p.name.cmp(name))
+        .ok()
+        .map(|i| &PROPERTIES[i])
+}
+
+/// A problem with a material property found by [NudMaterial::property_errors].
+#[derive(Debug, PartialEq, Clone, thiserror::Error)]
+pub enum PropertyError {
+    #[error("property {0:?} is not a known material property")]
+    UnknownName(String),
+
+    #[error("property {name:?} has {actual} values but expected {expected}")]
+    ValueCount {
+        name: String,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl NudMaterial {
+    pub fn property(&self, name: &str) -> Option<&NudProperty> {
+        self.properties.iter().find(|p| p.name == name)
+    }
+
+    /// The first 4 values for property `name`
+    /// or `None` if the property is missing or has fewer than 4 values.
+    pub fn property_vec4(&self, name: &str) -> Option<Vec4> {
+        self.property(name)
+            .and_then(|p| p.values.get(..4))
+            .map(Vec4::from_slice)
+    }
+
+    /// The first value for property `name` or `None` if the property is missing or empty.
+    pub fn property_f32(&self, name: &str) -> Option<f32> {
+        self.property(name).and_then(|p| p.values.first().copied())
+    }
+
+    /// The values for property `name` or the registry default for missing properties.
+    ///
+    /// Returns zero for unknown properties not in the material.
+    pub fn property_or_default(&self, name: &str) -> Vec4 {
+        self.property_vec4(name).unwrap_or_else(|| {
+            property_info(name)
+                .map(|p| Vec4::from_array(p.default))
+                .unwrap_or_default()
+        })
+    }
+
+    /// Set the values for property `name` or add a new property if not present.
+    ///
+    /// Known properties use the value count from the registry.
+    pub fn set_property(&mut self, name: &str, value: Vec4) {
+        let count = property_info(name).map(|p| p.value_count).unwrap_or(4);
+        let values = value.to_array()[..count].to_vec();
+        match self.properties.iter_mut().find(|p| p.name == name) {
+            Some(property) => property.values = values,
+            None => self.properties.push(NudProperty {
+                name: name.to_string(),
+                values,
+            }),
+        }
+    }
+
+    /// Check properties against the registry of known properties.
+    ///
+    /// The value count for [PropertySemantic::Hash] properties like `NU_materialHash` is not checked
+    /// since it has not been verified against game files.
+    pub fn property_errors(&self) -> Vec<PropertyError> {
+        self.properties
+            .iter()
+            .filter_map(|p| match property_info(&p.name) {
+                Some(info) => (info.semantic != PropertySemantic::Hash
+                    && p.values.len() != info.value_count)
+                    .then(|| PropertyError::ValueCount {
+                        name: p.name.clone(),
+                        expected: info.value_count,
+                        actual: p.values.len(),
+                    }),
+                None => Some(PropertyError::UnknownName(p.name.clone())),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use glam::vec4;
+
+    use crate::{AlphaFunc, CullMode, DstFactor, SrcFactor};
+
+    fn material(properties: Vec<NudProperty>) -> NudMaterial {
+        NudMaterial {
+            shader_id: 0,
+            src_factor: SrcFactor::One,
+            dst_factor: DstFactor::Zero,
+            alpha_func: AlphaFunc::Disabled,
+            alpha_test_ref: 0,
+            cull_mode: CullMode::Disabled,
+            textures: Vec::new(),
+            properties,
+        }
+    }
+
+    #[test]
+    fn properties_sorted() {
+        assert!(PROPERTIES.windows(2).all(|w| w[0].name < w[1].name));
+    }
+
+    #[test]
+    fn property_info_lookup() {
+        assert_eq!(
+            Some(PropertySemantic::UvTransform),
+            property_info("NU_colorSamplerUV").map(|p| p.semantic)
+        );
+        assert_eq!(None, property_info("NU_unknown"));
+    }
+
+    #[test]
+    fn property_accessors() {
+        let mut material = material(Vec::new());
+        assert_eq!(
+            vec4(1.0, 1.0, 1.0, 1.0),
+            material.property_or_default("NU_colorGain")
+        );
+        assert_eq!(None, material.property_vec4("NU_colorGain"));
+
+        material.set_property("NU_colorGain", vec4(0.5, 0.5, 0.5, 1.0));
+        material.set_property("NU_materialHash", vec4(2.0, 0.0, 0.0, 0.0));
+        assert_eq!(
+            Some(vec4(0.5, 0.5, 0.5, 1.0)),
+            material.property_vec4("NU_colorGain")
+        );
+        assert_eq!(Some(2.0), material.property_f32("NU_materialHash"));
+        assert_eq!(
+            vec![2.0],
+            material.property("NU_materialHash").unwrap().values
+        );
+        assert!(material.property_errors().is_empty());
+    }
+
+    #[test]
+    fn property_errors_unknown_value_count() {
+        let material = material(vec![
+            NudProperty {
+                name: "NU_unknown".to_string(),
+                values: vec![0.0; 4],
+            },
+            NudProperty {
+                name: "NU_colorGain".to_string(),
+                values: vec![1.0; 3],
+            },
+            NudProperty {
+                name: "NU_materialHash".to_string(),
+                values: vec![0.0; 4],
+            },
+        ]);
+        assert_eq!(
+            vec![
+                PropertyError::UnknownName("NU_unknown".to_string()),
+                PropertyError::ValueCount {
+                    name: "NU_colorGain".to_string(),
+                    expected: 4,
+                    actual: 3
+                }
+            ],
+            material.property_errors()
+        );
+    }
+}
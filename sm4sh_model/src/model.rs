@@ -1,9 +1,9 @@
-use glam::{Vec4, vec4};
+use glam::{Vec3, Vec4, vec4};
 use itertools::Itertools;
 
 use crate::vertex::{BoneElementType, Bones, Vertices};
 
-use super::{NudMesh, NudMeshGroup};
+use super::{NudMesh, NudMeshGroup, VbnSkeleton};
 
 /// Data for a [NudMesh] and its parent [NudMeshGroup] to facilitate grouping meshes.
 pub struct NudMeshGroupMesh {
@@ -23,10 +23,15 @@ pub struct NudMeshGroupMesh {
 ///
 /// This may modify the parent bone or skinning for a [NudMesh]
 /// to ensure that all meshes in a [NudMeshGroup] use vertex skinning or all use a parent bone.
-pub fn create_mesh_groups(meshes: &[NudMeshGroupMesh]) -> Vec<NudMeshGroup> {
+///
+/// Bounding spheres are calculated using [NudMeshGroup::calculate_bounding_sphere]
+/// with the `skeleton` for groups that use the parent bone transform.
+pub fn create_mesh_groups(
+    meshes: &[NudMeshGroupMesh],
+    skeleton: Option<&VbnSkeleton>,
+) -> Vec<NudMeshGroup> {
     // TODO: apply parent bone optimization if needed
     // TODO: all groups with the same name should have the same sort bias?
-    // TODO: calculate bounding spheres and overall bounding sphere
 
     // Sort to enable grouping later.
     // TODO: use IndexMap to preserve the ordering as much as possible?
@@ -84,9 +89,60 @@ pub fn create_mesh_groups(meshes: &[NudMeshGroupMesh]) -> Vec<NudMeshGroup> {
             });
         }
     }
+
+    for group in &mut groups {
+        group.bounding_sphere = group.calculate_bounding_sphere(skeleton);
+    }
+
     groups
 }
 
+/// Calculate a sphere containing all `points` as XYZ center and W radius.
+///
+/// This uses Ritter's algorithm with extremal points along each axis for the initial sphere.
+/// The result is not always minimal but only depends on the order of `points`.
+pub fn calculate_bounding_sphere(points: &[Vec3]) -> Vec4 {
+    let Some(first) = points.first() else {
+        return Vec4::ZERO;
+    };
+
+    // Find the most separated pair of minimum and maximum points along each axis.
+    let mut min = [*first; 3];
+    let mut max = [*first; 3];
+    for p in points {
+        for axis in 0..3 {
+            if p[axis] < min[axis][axis] {
+                min[axis] = *p;
+            }
+            if p[axis] > max[axis][axis] {
+                max[axis] = *p;
+            }
+        }
+    }
+    let (a, b) = (0..3)
+        .map(|axis| (min[axis], max[axis]))
+        .max_by(|(a0, b0), (a1, b1)| {
+            a0.distance_squared(*b0)
+                .total_cmp(&a1.distance_squared(*b1))
+        })
+        .unwrap();
+
+    let mut center = (a + b) / 2.0;
+    let mut radius = a.distance(b) / 2.0;
+
+    // Grow the sphere to include any remaining points.
+    for p in points {
+        let distance = p.distance(center);
+        if distance > radius {
+            let new_radius = (radius + distance) / 2.0;
+            center += (*p - center) * ((new_radius - radius) / distance);
+            radius = new_radius;
+        }
+    }
+
+    center.extend(radius)
+}
+
 fn mesh_without_bone_weights(m: &NudMesh) -> NudMesh {
     NudMesh {
         vertices: Vertices {
@@ -117,7 +173,7 @@ mod tests {
     use super::*;
 
     use crate::{
-        PrimitiveType,
+        BoneType, PrimitiveType, VbnBone,
         vertex::{BoneElementType, Bones, Normals, Uvs, Vertices},
     };
 
@@ -150,12 +206,11 @@ mod tests {
 
     #[test]
     fn create_mesh_groups_empty() {
-        assert!(create_mesh_groups(&[]).is_empty());
+        assert!(create_mesh_groups(&[], None).is_empty());
     }
 
     #[test]
     fn create_mesh_groups_single() {
-        // TODO: Test bounding spheres as well.
         assert_eq!(
             vec![NudMeshGroup {
                 name: "a".to_string(),
@@ -164,19 +219,21 @@ mod tests {
                 bounding_sphere: Vec4::ZERO,
                 parent_bone_index: Some(2)
             }],
-            create_mesh_groups(&[NudMeshGroupMesh {
-                name: "a".to_string(),
-                sort_bias: 1.5,
-                parent_bone_index: Some(2),
-                mesh: nud_mesh(None, None)
-            }])
+            create_mesh_groups(
+                &[NudMeshGroupMesh {
+                    name: "a".to_string(),
+                    sort_bias: 1.5,
+                    parent_bone_index: Some(2),
+                    mesh: nud_mesh(None, None)
+                }],
+                None
+            )
         );
     }
 
     #[test]
     fn create_mesh_groups_split_parent_bone() {
         // Test splitting groups with the same name but different parent bones.
-        // TODO: Test bounding spheres as well.
         assert_eq!(
             vec![
                 NudMeshGroup {
@@ -201,39 +258,41 @@ mod tests {
                     parent_bone_index: Some(2)
                 }
             ],
-            create_mesh_groups(&[
-                NudMeshGroupMesh {
-                    name: "a".to_string(),
-                    sort_bias: 1.5,
-                    parent_bone_index: Some(0),
-                    mesh: nud_mesh(None, None)
-                },
-                NudMeshGroupMesh {
-                    name: "a".to_string(),
-                    sort_bias: 1.5,
-                    parent_bone_index: Some(0),
-                    mesh: nud_mesh(None, None)
-                },
-                NudMeshGroupMesh {
-                    name: "a".to_string(),
-                    sort_bias: 1.5,
-                    parent_bone_index: Some(1),
-                    mesh: nud_mesh(None, None)
-                },
-                NudMeshGroupMesh {
-                    name: "b".to_string(),
-                    sort_bias: 1.5,
-                    parent_bone_index: Some(2),
-                    mesh: nud_mesh(None, None)
-                }
-            ])
+            create_mesh_groups(
+                &[
+                    NudMeshGroupMesh {
+                        name: "a".to_string(),
+                        sort_bias: 1.5,
+                        parent_bone_index: Some(0),
+                        mesh: nud_mesh(None, None)
+                    },
+                    NudMeshGroupMesh {
+                        name: "a".to_string(),
+                        sort_bias: 1.5,
+                        parent_bone_index: Some(0),
+                        mesh: nud_mesh(None, None)
+                    },
+                    NudMeshGroupMesh {
+                        name: "a".to_string(),
+                        sort_bias: 1.5,
+                        parent_bone_index: Some(1),
+                        mesh: nud_mesh(None, None)
+                    },
+                    NudMeshGroupMesh {
+                        name: "b".to_string(),
+                        sort_bias: 1.5,
+                        parent_bone_index: Some(2),
+                        mesh: nud_mesh(None, None)
+                    }
+                ],
+                None
+            )
         );
     }
 
     #[test]
     fn create_mesh_groups_mixed_skinning_parent_bone() {
         // Test converting parent bones to skin weights for consistent bone flags.
-        // TODO: Test bounding spheres as well.
         assert_eq!(
             vec![NudMeshGroup {
                 name: "a".to_string(),
@@ -246,30 +305,125 @@ mod tests {
                 bounding_sphere: Vec4::ZERO,
                 parent_bone_index: None
             },],
-            create_mesh_groups(&[
-                NudMeshGroupMesh {
-                    name: "a".to_string(),
-                    sort_bias: 1.5,
-                    parent_bone_index: Some(0),
-                    mesh: nud_mesh(None, None)
-                },
-                NudMeshGroupMesh {
-                    name: "a".to_string(),
-                    sort_bias: 1.5,
-                    parent_bone_index: None,
-                    mesh: nud_mesh(Some([1, 2, 3, 4]), Some(vec4(0.5, 0.25, 0.125, 0.125)))
-                },
-                NudMeshGroupMesh {
-                    name: "a".to_string(),
-                    sort_bias: 1.5,
-                    parent_bone_index: Some(2),
-                    mesh: nud_mesh(None, None)
-                },
-            ])
+            create_mesh_groups(
+                &[
+                    NudMeshGroupMesh {
+                        name: "a".to_string(),
+                        sort_bias: 1.5,
+                        parent_bone_index: Some(0),
+                        mesh: nud_mesh(None, None)
+                    },
+                    NudMeshGroupMesh {
+                        name: "a".to_string(),
+                        sort_bias: 1.5,
+                        parent_bone_index: None,
+                        mesh: nud_mesh(Some([1, 2, 3, 4]), Some(vec4(0.5, 0.25, 0.125, 0.125)))
+                    },
+                    NudMeshGroupMesh {
+                        name: "a".to_string(),
+                        sort_bias: 1.5,
+                        parent_bone_index: Some(2),
+                        mesh: nud_mesh(None, None)
+                    },
+                ],
+                None
+            )
         );
     }
 
     // TODO: test case for applying parent bone optimization
 
     // TODO: test group with both parent bone and weights on an input mesh?
+
+    fn assert_sphere_contains(sphere: Vec4, points: &[Vec3]) {
+        for p in points {
+            assert!(
+                p.distance(sphere.truncate()) <= sphere.w + 0.0001,
+                "{p:?} not in {sphere:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn bounding_sphere_empty() {
+        assert_eq!(Vec4::ZERO, calculate_bounding_sphere(&[]));
+    }
+
+    #[test]
+    fn bounding_sphere_single_point() {
+        assert_eq!(
+            vec4(1.0, 2.0, 3.0, 0.0),
+            calculate_bounding_sphere(&[Vec3::new(1.0, 2.0, 3.0)])
+        );
+    }
+
+    #[test]
+    fn bounding_sphere_axis_points() {
+        let points = [
+            Vec3::new(-1.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ];
+        assert_eq!(vec4(0.0, 0.0, 0.0, 1.0), calculate_bounding_sphere(&points));
+    }
+
+    #[test]
+    fn bounding_sphere_contains_points() {
+        let points: Vec<_> = (0..100)
+            .map(|i| {
+                let i = i as f32;
+                Vec3::new((i * 0.7).sin() * i, (i * 1.3).cos() * 2.0, i * 0.1 - 3.0)
+            })
+            .collect();
+        assert_sphere_contains(calculate_bounding_sphere(&points), &points);
+    }
+
+    #[test]
+    fn create_mesh_groups_bounding_sphere() {
+        let mut mesh = nud_mesh(None, None);
+        mesh.vertices.positions = vec![Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 3.0, 0.0)];
+
+        let groups = create_mesh_groups(
+            &[NudMeshGroupMesh {
+                name: "a".to_string(),
+                sort_bias: 0.0,
+                parent_bone_index: None,
+                mesh,
+            }],
+            None,
+        );
+        assert_eq!(vec4(0.0, 2.0, 0.0, 1.0), groups[0].bounding_sphere);
+    }
+
+    #[test]
+    fn create_mesh_groups_bounding_sphere_nsc() {
+        let mut mesh = nud_mesh(None, None);
+        mesh.vertices.positions = vec![Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 3.0, 0.0)];
+
+        let skeleton = VbnSkeleton {
+            bones: vec![VbnBone {
+                name: "a".to_string(),
+                hash: 1,
+                parent_bone_index: None,
+                bone_type: BoneType::Normal,
+                translation: Vec3::new(1.0, 0.0, 0.0),
+                rotation: Vec3::ZERO,
+                scale: Vec3::ONE,
+            }],
+        };
+
+        let groups = create_mesh_groups(
+            &[NudMeshGroupMesh {
+                name: "a_NSC".to_string(),
+                sort_bias: 0.0,
+                parent_bone_index: Some(0),
+                mesh,
+            }],
+            Some(&skeleton),
+        );
+        assert_eq!(vec4(1.0, 2.0, 0.0, 1.0), groups[0].bounding_sphere);
+    }
 }
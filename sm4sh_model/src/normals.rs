@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+
+use glam::{Vec2, Vec3, Vec4, Vec4Swizzles};
+
+/// Calculate per vertex normals for a triangle list from the angle weighted face normals.
+///
+/// Faces are smoothed across vertices with identical positions like those split for UV seams.
+/// If `smooth_angle` is `Some`, only faces with normals within `smooth_angle` radians are smoothed together.
+/// Vertices are not duplicated, so vertices used by faces in different smoothing groups use the average.
+/// Vertices not used by any face have a normal of zero.
+pub fn calculate_smooth_normals(
+    positions: &[Vec3],
+    indices: &[u16],
+    smooth_angle: Option<f32>,
+) -> Vec<Vec3> {
+    let faces: Vec<[usize; 3]> = indices
+        .chunks_exact(3)
+        .map(|f| [f[0], f[1], f[2]].map(|i| i as usize))
+        .filter(|f| f.iter().all(|i| *i < positions.len()))
+        .collect();
+
+    let face_normals: Vec<_> = faces
+        .iter()
+        .map(|[i0, i1, i2]| {
+            let p0 = positions[*i0];
+            (positions[*i1] - p0)
+                .cross(positions[*i2] - p0)
+                .normalize_or_zero()
+        })
+        .collect();
+
+    // Find the faces and corner angles for each unique position.
+    let mut position_faces: HashMap<[u32; 3], Vec<(usize, f32)>> = HashMap::new();
+    for (face_index, face) in faces.iter().enumerate() {
+        for corner in 0..3 {
+            let p = positions[face[corner]];
+            let a = positions[face[(corner + 1) % 3]] - p;
+            let b = positions[face[(corner + 2) % 3]] - p;
+            let angle = a.angle_between(b);
+            if angle.is_finite() {
+                position_faces
+                    .entry(position_key(p))
+                    .or_default()
+                    .push((face_index, angle));
+            }
+        }
+    }
+
+    let min_cos_angle = smooth_angle.map(f32::cos);
+
+    let mut normals = vec![Vec3::ZERO; positions.len()];
+    for (face_index, face) in faces.iter().enumerate() {
+        let face_normal = face_normals[face_index];
+        for i in face {
+            let corner_normal: Vec3 = position_faces
+                .get(&position_key(positions[*i]))
+                .into_iter()
+                .flatten()
+                .filter(|(other, _)| {
+                    min_cos_angle
+                        .map(|c| face_normal.dot(face_normals[*other]) >= c)
+                        .unwrap_or(true)
+                })
+                .map(|(other, angle)| face_normals[*other] * *angle)
+                .sum();
+            normals[*i] += corner_normal.normalize_or_zero();
+        }
+    }
+
+    normals.iter().map(|n| n.normalize_or_zero()).collect()
+}
+
+fn position_key(p: Vec3) -> [u32; 3] {
+    // Treat 0.0 and -0.0 as the same position.
+    (p + Vec3::ZERO).to_array().map(f32::to_bits)
+}
+
+/// Calculate per vertex tangents and bitangents for a triangle list
+/// using the angle weighted accumulation of MikkTSpace.
+///
+/// The tangent points in the direction of increasing U and the bitangent in the direction of increasing V.
+/// The W component is always `1.0` since the renderer calculates handedness from the vectors.
+pub fn calculate_tangents_bitangents(
+    positions: &[Vec3],
+    normals: &[Vec4],
+    uvs: &[Vec2],
+    indices: &[u16],
+) -> (Vec<Vec4>, Vec<Vec4>) {
+    let mut tangents = vec![Vec3::ZERO; positions.len()];
+    let mut bitangents = vec![Vec3::ZERO; positions.len()];
+
+    for face in indices.chunks_exact(3) {
+        let face = [face[0], face[1], face[2]].map(|i| i as usize);
+        if face
+            .iter()
+            .any(|i| *i >= positions.len() || *i >= normals.len() || *i >= uvs.len())
+        {
+            continue;
+        }
+
+        let [p0, p1, p2] = face.map(|i| positions[i]);
+        let [uv0, uv1, uv2] = face.map(|i| uvs[i]);
+
+        let e1 = p1 - p0;
+        let e2 = p2 - p0;
+        let duv1 = uv1 - uv0;
+        let duv2 = uv2 - uv0;
+
+        // Skip faces with degenerate UVs since the tangent direction is undefined.
+        let r = duv1.x * duv2.y - duv2.x * duv1.y;
+        if r.abs() < 1e-12 {
+            continue;
+        }
+        let face_tangent = (e1 * duv2.y - e2 * duv1.y) / r;
+        let face_bitangent = (e2 * duv1.x - e1 * duv2.x) / r;
+
+        for corner in 0..3 {
+            let i = face[corner];
+            let p = positions[i];
+            let a = positions[face[(corner + 1) % 3]] - p;
+            let b = positions[face[(corner + 2) % 3]] - p;
+            let angle = a.angle_between(b);
+            if !angle.is_finite() {
+                continue;
+            }
+
+            // Project onto the tangent plane before accumulating like MikkTSpace.
+            let n = normals[i].xyz().normalize_or_zero();
+            let t = (face_tangent - n * n.dot(face_tangent)).normalize_or_zero();
+            let b = (face_bitangent - n * n.dot(face_bitangent)).normalize_or_zero();
+            tangents[i] += t * angle;
+            bitangents[i] += b * angle;
+        }
+    }
+
+    tangents
+        .iter()
+        .zip(&bitangents)
+        .zip(normals)
+        .map(|((t, b), n)| {
+            let n = n.xyz().normalize_or_zero();
+
+            // Gram-Schmidt orthogonalization.
+            let mut t = (*t - n * n.dot(*t)).normalize_or_zero();
+            if t == Vec3::ZERO {
+                t = n.any_orthonormal_vector();
+            }
+
+            // Preserve the handedness of the UV mapping.
+            let sign = if n.cross(t).dot(*b) < 0.0 { -1.0 } else { 1.0 };
+            let b = n.cross(t) * sign;
+
+            (t.extend(1.0), b.extend(1.0))
+        })
+        .unzip()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use glam::{vec2, vec3, vec4};
+
+    fn assert_vec4_eq(expected: Vec4, actual: &[Vec4]) {
+        for a in actual {
+            assert!(a.abs_diff_eq(expected, 0.0001), "{a:?} != {expected:?}");
+        }
+    }
+
+    fn assert_vec3_eq(expected: Vec3, actual: Vec3) {
+        assert!(
+            actual.abs_diff_eq(expected, 0.0001),
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    // Two faces meeting at a right angle with split vertices along the shared edge.
+    const FOLDED_POSITIONS: [Vec3; 6] = [
+        vec3(0.0, 0.0, 0.0),
+        vec3(1.0, 0.0, 0.0),
+        vec3(0.0, 0.0, -1.0),
+        vec3(0.0, 0.0, 0.0),
+        vec3(0.0, 1.0, 0.0),
+        vec3(1.0, 0.0, 0.0),
+    ];
+    const FOLDED_INDICES: [u16; 6] = [0, 1, 2, 3, 5, 4];
+
+    #[test]
+    fn smooth_normals_empty() {
+        assert!(calculate_smooth_normals(&[], &[], None).is_empty());
+    }
+
+    #[test]
+    fn smooth_normals_split_vertices() {
+        let normals = calculate_smooth_normals(&FOLDED_POSITIONS, &FOLDED_INDICES, None);
+        let shared = vec3(0.0, 1.0, 1.0).normalize();
+        assert_vec3_eq(shared, normals[0]);
+        assert_vec3_eq(shared, normals[1]);
+        assert_vec3_eq(vec3(0.0, 1.0, 0.0), normals[2]);
+        assert_vec3_eq(shared, normals[3]);
+        assert_vec3_eq(vec3(0.0, 0.0, 1.0), normals[4]);
+        assert_vec3_eq(shared, normals[5]);
+    }
+
+    #[test]
+    fn smooth_normals_angle_threshold() {
+        let normals =
+            calculate_smooth_normals(&FOLDED_POSITIONS, &FOLDED_INDICES, Some(30f32.to_radians()));
+        for n in &normals[..3] {
+            assert_vec3_eq(vec3(0.0, 1.0, 0.0), *n);
+        }
+        for n in &normals[3..] {
+            assert_vec3_eq(vec3(0.0, 0.0, 1.0), *n);
+        }
+    }
+
+    #[test]
+    fn tangents_bitangents_empty() {
+        assert_eq!(
+            (Vec::new(), Vec::new()),
+            calculate_tangents_bitangents(&[], &[], &[], &[])
+        );
+    }
+
+    #[test]
+    fn tangents_bitangents_quad() {
+        let positions = [
+            vec3(0.0, 0.0, 0.0),
+            vec3(1.0, 0.0, 0.0),
+            vec3(1.0, 1.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+        ];
+        let normals = [vec4(0.0, 0.0, 1.0, 1.0); 4];
+        let uvs = [
+            vec2(0.0, 0.0),
+            vec2(1.0, 0.0),
+            vec2(1.0, 1.0),
+            vec2(0.0, 1.0),
+        ];
+        let (tangents, bitangents) =
+            calculate_tangents_bitangents(&positions, &normals, &uvs, &[0, 1, 2, 0, 2, 3]);
+        assert_eq!(4, tangents.len());
+        assert_vec4_eq(vec4(1.0, 0.0, 0.0, 1.0), &tangents);
+        assert_vec4_eq(vec4(0.0, 1.0, 0.0, 1.0), &bitangents);
+    }
+
+    #[test]
+    fn tangents_bitangents_mirrored_uvs() {
+        let positions = [
+            vec3(0.0, 0.0, 0.0),
+            vec3(1.0, 0.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+        ];
+        let normals = [vec4(0.0, 0.0, 1.0, 1.0); 3];
+        let uvs = [vec2(1.0, 0.0), vec2(0.0, 0.0), vec2(1.0, 1.0)];
+        let (tangents, bitangents) =
+            calculate_tangents_bitangents(&positions, &normals, &uvs, &[0, 1, 2]);
+        assert_eq!(3, tangents.len());
+        assert_vec4_eq(vec4(-1.0, 0.0, 0.0, 1.0), &tangents);
+        assert_vec4_eq(vec4(0.0, 1.0, 0.0, 1.0), &bitangents);
+    }
+}
@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+
+use crate::{NudMesh, PrimitiveType, vertex::VertexCountError};
+
+/// The number of vertices before and after [NudMesh::weld_vertices].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct WeldStats {
+    pub vertex_count_before: usize,
+    pub vertex_count_after: usize,
+}
+
+/// The average cache miss ratio (ACMR) before and after [NudMesh::optimize_vertex_cache].
+///
+/// The ACMR is the number of transformed vertices per triangle for a FIFO cache of `cache_size`.
+/// Lower values are better with a minimum of `0.5` for large meshes.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct VertexCacheStats {
+    pub cache_size: usize,
+    pub acmr_before: f32,
+    pub acmr_after: f32,
+}
+
+impl NudMesh {
+    /// Combine vertices with identical values for all attributes and update the vertex indices.
+    ///
+    /// Vertices are ordered by their first use in the original vertices.
+    pub fn weld_vertices(&mut self) -> Result<WeldStats, VertexCountError> {
+        let vertex_count_before = self.vertex_count()?;
+
+        let mut unique_vertices = HashMap::new();
+        let mut unique_indices = Vec::new();
+        let remap: Vec<_> = (0..vertex_count_before)
+            .map(|i| {
+                *unique_vertices
+                    .entry(self.vertices.vertex_bits(i))
+                    .or_insert_with(|| {
+                        unique_indices.push(i);
+                        unique_indices.len() - 1
+                    })
+            })
+            .collect();
+
+        self.vertices = self.vertices.select(&unique_indices);
+        // Preserve primitive restart for triangle strips.
+        for index in self.vertex_indices.iter_mut().filter(|i| **i != u16::MAX) {
+            if let Some(new_index) = remap.get(*index as usize) {
+                *index = *new_index as u16;
+            }
+        }
+
+        Ok(WeldStats {
+            vertex_count_before,
+            vertex_count_after: unique_indices.len(),
+        })
+    }
+
+    /// Reorder triangles to improve the vertex cache hit rate using the Tipsify algorithm
+    /// and reorder vertices by their first use in the new vertex indices.
+    ///
+    /// Triangle strips are converted to triangle lists.
+    pub fn optimize_vertex_cache(
+        &mut self,
+        cache_size: usize,
+    ) -> Result<VertexCacheStats, VertexCountError> {
+        let vertex_count = self.vertex_count()?;
+        let indices = self.triangle_list_indices().into_owned();
+        let acmr_before = average_cache_miss_ratio(&indices, cache_size);
+
+        let indices = tipsify(&indices, vertex_count, cache_size);
+
+        // Order vertices by first use to also improve memory locality.
+        let mut remap = vec![None; vertex_count];
+        let mut new_order = Vec::new();
+        let indices: Vec<_> = indices
+            .iter()
+            .map(|i| {
+                *remap[*i as usize].get_or_insert_with(|| {
+                    new_order.push(*i as usize);
+                    (new_order.len() - 1) as u16
+                })
+            })
+            .collect();
+        // Keep unused vertices at the end.
+        new_order.extend((0..vertex_count).filter(|i| remap[*i].is_none()));
+
+        self.vertices = self.vertices.select(&new_order);
+        self.vertex_indices = indices;
+        self.primitive_type = PrimitiveType::TriangleList;
+
+        Ok(VertexCacheStats {
+            cache_size,
+            acmr_before,
+            acmr_after: average_cache_miss_ratio(&self.vertex_indices, cache_size),
+        })
+    }
+}
+
+/// Calculate the average cache miss ratio (ACMR) for triangle list `indices` and a FIFO cache of `cache_size`.
+pub fn average_cache_miss_ratio(indices: &[u16], cache_size: usize) -> f32 {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return 0.0;
+    }
+
+    let mut cache = std::collections::VecDeque::new();
+    let mut misses = 0;
+    for i in indices {
+        if !cache.contains(i) {
+            misses += 1;
+            cache.push_back(*i);
+            if cache.len() > cache_size {
+                cache.pop_front();
+            }
+        }
+    }
+    misses as f32 / triangle_count as f32
+}
+
+// "Fast Triangle Reordering for Vertex Locality and Reduced Overdraw"
+// Sander, Nehab, Barczak 2007
+fn tipsify(indices: &[u16], vertex_count: usize, cache_size: usize) -> Vec<u16> {
+    let triangles: Vec<[usize; 3]> = indices
+        .chunks_exact(3)
+        .map(|t| [t[0], t[1], t[2]].map(|i| i as usize))
+        .filter(|t| t.iter().all(|i| *i < vertex_count))
+        .collect();
+
+    let mut adjacency = vec![Vec::new(); vertex_count];
+    for (t, triangle) in triangles.iter().enumerate() {
+        for i in triangle {
+            adjacency[*i].push(t);
+        }
+    }
+
+    let mut live_triangles: Vec<_> = adjacency.iter().map(|a| a.len()).collect();
+    let mut cache_times = vec![0; vertex_count];
+    let mut dead_end = Vec::new();
+    let mut is_emitted = vec![false; triangles.len()];
+
+    let mut new_indices = Vec::with_capacity(indices.len());
+    let mut time = cache_size + 1;
+    let mut cursor = 0;
+    let mut fanning_vertex = (vertex_count > 0).then_some(0);
+
+    while let Some(f) = fanning_vertex {
+        let mut candidates = Vec::new();
+        for t in &adjacency[f] {
+            if !is_emitted[*t] {
+                for v in triangles[*t] {
+                    new_indices.push(v as u16);
+                    dead_end.push(v);
+                    candidates.push(v);
+                    live_triangles[v] -= 1;
+                    if time - cache_times[v] > cache_size {
+                        cache_times[v] = time;
+                        time += 1;
+                    }
+                }
+                is_emitted[*t] = true;
+            }
+        }
+
+        // Choose the next vertex that is likely to still be in the cache.
+        let mut best: Option<(usize, usize)> = None;
+        for v in candidates {
+            if live_triangles[v] > 0 {
+                let mut priority = 0;
+                if time - cache_times[v] + 2 * live_triangles[v] <= cache_size {
+                    priority = time - cache_times[v];
+                }
+                if best.map(|(_, p)| priority > p).unwrap_or(true) {
+                    best = Some((v, priority));
+                }
+            }
+        }
+
+        fanning_vertex = match best {
+            Some((v, _)) => Some(v),
+            None => skip_dead_end(&live_triangles, &mut dead_end, &mut cursor),
+        };
+    }
+
+    new_indices
+}
+
+fn skip_dead_end(
+    live_triangles: &[usize],
+    dead_end: &mut Vec<usize>,
+    cursor: &mut usize,
+) -> Option<usize> {
+    while let Some(v) = dead_end.pop() {
+        if live_triangles[v] > 0 {
+            return Some(v);
+        }
+    }
+    while *cursor < live_triangles.len() {
+        if live_triangles[*cursor] > 0 {
+            return Some(*cursor);
+        }
+        *cursor += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::vertex::{Normals, Uvs, Vertices};
+    use glam::{Vec3, vec3};
+
+    fn mesh(positions: Vec<Vec3>, vertex_indices: Vec<u16>) -> NudMesh {
+        NudMesh {
+            vertices: Vertices {
+                normals: Normals::None(vec![0.0; positions.len()]),
+                positions,
+                bones: None,
+                colors: None,
+                uvs: Uvs::Float16(Vec::new()),
+            },
+            vertex_indices,
+            primitive_type: PrimitiveType::TriangleList,
+            material1: None,
+            material2: None,
+            material3: None,
+            material4: None,
+        }
+    }
+
+    fn triangles(mesh: &NudMesh) -> Vec<[Vec3; 3]> {
+        let positions = &mesh.vertices.positions;
+        let mut triangles: Vec<_> = mesh
+            .triangle_list_indices()
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]].map(|i| positions[i as usize]))
+            .collect();
+        triangles.sort_by(|a, b| format!("{a:?}").cmp(&format!("{b:?}")));
+        triangles
+    }
+
+    fn grid_mesh(size: u16) -> NudMesh {
+        let positions = (0..=size)
+            .flat_map(|y| (0..=size).map(move |x| vec3(x as f32, y as f32, 0.0)))
+            .collect();
+        let mut indices = Vec::new();
+        // Scatter the quads for poor cache locality.
+        let quad_count = size * size;
+        for q in 0..quad_count {
+            let q = (q as u32 * 97 % quad_count as u32) as u16;
+            let i = (q / size) * (size + 1) + q % size;
+            let j = i + size + 1;
+            indices.extend([i, i + 1, j, j, i + 1, j + 1]);
+        }
+        mesh(positions, indices)
+    }
+
+    #[test]
+    fn weld_vertices_duplicates() {
+        let mut mesh = mesh(
+            vec![
+                vec3(0.0, 0.0, 0.0),
+                vec3(1.0, 0.0, 0.0),
+                vec3(0.0, 1.0, 0.0),
+                vec3(0.0, 1.0, 0.0),
+                vec3(1.0, 0.0, 0.0),
+                vec3(1.0, 1.0, 0.0),
+            ],
+            vec![0, 1, 2, 3, 4, 5],
+        );
+        let expected = triangles(&mesh);
+
+        assert_eq!(
+            WeldStats {
+                vertex_count_before: 6,
+                vertex_count_after: 4
+            },
+            mesh.weld_vertices().unwrap()
+        );
+        assert_eq!(vec![0, 1, 2, 2, 1, 3], mesh.vertex_indices);
+        assert_eq!(expected, triangles(&mesh));
+    }
+
+    #[test]
+    fn weld_vertices_different_attributes() {
+        let mut mesh = mesh(vec![Vec3::ZERO; 3], vec![0, 1, 2]);
+        mesh.vertices.normals = Normals::None(vec![0.0, 1.0, 0.0]);
+
+        assert_eq!(
+            WeldStats {
+                vertex_count_before: 3,
+                vertex_count_after: 2
+            },
+            mesh.weld_vertices().unwrap()
+        );
+        assert_eq!(vec![0, 1, 0], mesh.vertex_indices);
+    }
+
+    #[test]
+    fn weld_vertices_invalid_attributes() {
+        let mut mesh = mesh(vec![Vec3::ZERO; 3], vec![0, 1, 2]);
+        mesh.vertices.normals = Normals::None(vec![0.0; 2]);
+        assert_eq!(
+            Err(VertexCountError::Attribute {
+                attribute: "normals",
+                count: 2,
+                vertex_count: 3
+            }),
+            mesh.weld_vertices()
+        );
+    }
+
+    #[test]
+    fn optimize_vertex_cache_grid() {
+        let mut mesh = grid_mesh(16);
+        let expected = triangles(&mesh);
+
+        let stats = mesh.optimize_vertex_cache(8).unwrap();
+        assert!(stats.acmr_after < stats.acmr_before);
+        assert_eq!(expected, triangles(&mesh));
+    }
+
+    #[test]
+    fn optimize_vertex_cache_invalid_indices() {
+        let mut mesh = mesh(vec![Vec3::ZERO; 3], vec![0, 1, 3]);
+        assert_eq!(
+            Err(VertexCountError::Index {
+                index: 3,
+                vertex_count: 3
+            }),
+            mesh.optimize_vertex_cache(8)
+        );
+    }
+
+    #[test]
+    fn average_cache_miss_ratio_empty() {
+        assert_eq!(0.0, average_cache_miss_ratio(&[], 16));
+    }
+}
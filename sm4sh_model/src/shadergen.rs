@@ -0,0 +1,1039 @@
+//! Generate WGSL or GLSL code for the fragment outputs of a [ShaderProgram].
+//!
+//! The generated code is a list of assignments for each expression
+//! followed by assignments to the channels of [OUT_VAR].
+//! [fragment_function] wraps this code in a standalone function with helper functions.
+//!
+//! # Inputs
+//! The generated code expects the following names to be defined.
+//!
+//! Attributes are passed as parameters to [fragment_function]
+//! and should be in the same space as the light and eye vectors.
+//! * `a_Position`, `a_Normal`, `a_Tangent`, `a_Binormal`, `a_Color`,
+//!   `a_TexCoord0`, `a_TexCoord1`, `a_TexCoord2`: vertex attributes as `vec4`
+//! * `eye`: the normalized vector from the position to the camera
+//! * `light_position`: the position transformed by `FB0.ShadowMapMatrix` after division by w
+//! * `light_map_position`: the position transformed by `FB1.LightMapMatrix` offset by `FB0.LightMapPos`
+//! * `bitangent_sign`: the sign of the bitangent as a scalar
+//! * `local_to_world_matrix`: the model matrix
+//!
+//! Uniform buffers and textures are accessed as globals.
+//! WGSL uses `uniforms` and `effect_uniforms` for the `MC` and `MC_EFFECT` material parameters,
+//! `fb0`, `fb1`, `fb3`, `fb5` for global parameters, and `camera` with fields
+//! `view`, `projection`, and `view_projection`.
+//! Uniform fields and textures use snake case like `uniforms.color_gain`
+//! or `textureSample(color_texture, color_sampler, uv)` for `colorSampler`.
+//!
+//! GLSL uses the in game names for uniform block instances like `MC.NU_colorGain`, `FB0.Eye`,
+//! or `PerView.WorldToViewMatrix` and sampler uniforms like `colorSampler`.
+//!
+//! The helper functions in [fragment_function] always reference the view matrix,
+//! so `camera` for WGSL or `PerView` for GLSL must be declared even if `program` does not use them.
+use std::{collections::BTreeSet, fmt::Write};
+
+use case::CaseExt;
+use log::error;
+use smol_str::{SmolStr, format_smolstr};
+
+use crate::database::{
+    ChannelXyz, Operation, OperationXyz, OutputExpr, OutputExprXyz, Parameter, ShaderProgram,
+    Texture, Value, ValueXyz,
+};
+
+/// The name of the `vec4` output color variable assigned by the generated code.
+pub const OUT_VAR: &str = "out_color";
+const VAR_PREFIX: &str = "VAR_";
+const VAR_PREFIX_XYZ: &str = "VAR_XYZ_";
+
+const WGSL_FUNCTIONS: &str = include_str!("shadergen/functions.wgsl");
+const GLSL_FUNCTIONS: &str = include_str!("shadergen/functions.glsl");
+
+/// Attribute parameters for [fragment_function] with a 4 component vector type.
+const VEC4_ATTRIBUTES: &[&str] = &[
+    "a_Position",
+    "a_Normal",
+    "a_Tangent",
+    "a_Binormal",
+    "a_Color",
+    "a_TexCoord0",
+    "a_TexCoord1",
+    "a_TexCoord2",
+    "eye",
+    "light_position",
+    "light_map_position",
+];
+
+/// The language for generated shader code.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum ShaderLanguage {
+    /// GLSL version 330.
+    Glsl,
+    Wgsl,
+}
+
+/// Generated code for the fragment outputs of a [ShaderProgram].
+#[derive(Debug, Hash, Clone, PartialEq, Eq)]
+pub struct FragmentCode {
+    /// Variable assignments for each used expression.
+    pub assignments: String,
+    /// Assignments to the channels of [OUT_VAR].
+    pub outputs: String,
+}
+
+impl FragmentCode {
+    pub fn new(program: &ShaderProgram, language: ShaderLanguage) -> Self {
+        Self {
+            assignments: generate_assignments(program, language),
+            outputs: generate_outputs(program),
+        }
+    }
+}
+
+/// Generate helper functions and a function `fragment_color`
+/// that returns the output color for `program` from the attributes described in the [module](self) docs.
+pub fn fragment_function(program: &ShaderProgram, language: ShaderLanguage) -> String {
+    let code = FragmentCode::new(program, language);
+
+    let mut source = String::new();
+    match language {
+        ShaderLanguage::Glsl => {
+            writeln!(&mut source, "{GLSL_FUNCTIONS}").unwrap();
+            let parameters: Vec<_> = VEC4_ATTRIBUTES
+                .iter()
+                .map(|a| format!("vec4 {a}"))
+                .chain([
+                    "float bitangent_sign".to_string(),
+                    "mat4 local_to_world_matrix".to_string(),
+                ])
+                .collect();
+            writeln!(
+                &mut source,
+                "vec4 fragment_color({}) {{",
+                parameters.join(", ")
+            )
+            .unwrap();
+            writeln!(&mut source, "vec4 {OUT_VAR} = vec4(0.0);").unwrap();
+        }
+        ShaderLanguage::Wgsl => {
+            writeln!(&mut source, "{WGSL_FUNCTIONS}").unwrap();
+            let parameters: Vec<_> = VEC4_ATTRIBUTES
+                .iter()
+                .map(|a| format!("{a}: vec4<f32>"))
+                .chain([
+                    "bitangent_sign: f32".to_string(),
+                    "local_to_world_matrix: mat4x4<f32>".to_string(),
+                ])
+                .collect();
+            writeln!(
+                &mut source,
+                "fn fragment_color({}) -> vec4<f32> {{",
+                parameters.join(", ")
+            )
+            .unwrap();
+            writeln!(&mut source, "var {OUT_VAR} = vec4(0.0);").unwrap();
+        }
+    }
+    source.push_str(&code.assignments);
+    source.push_str(&code.outputs);
+    writeln!(&mut source, "return {OUT_VAR};").unwrap();
+    writeln!(&mut source, "}}").unwrap();
+
+    source
+}
+
+fn generate_assignments(program: &ShaderProgram, language: ShaderLanguage) -> String {
+    let mut source = String::new();
+
+    // TODO: Reindex the used exprs in a separate preprocessing step?
+    let mut used_exprs = BTreeSet::new();
+    let mut used_exprs_xyz = BTreeSet::new();
+    if let Some(xyz) = program
+        .output_dependencies_xyz
+        .get(&SmolStr::from("out_attr0.xyz"))
+    {
+        visit_exprs_xyz(
+            *xyz,
+            &program.exprs,
+            &program.exprs_xyz,
+            &mut used_exprs,
+            &mut used_exprs_xyz,
+        );
+
+        if let Some(xyz) = program
+            .output_dependencies
+            .get(&SmolStr::from("out_attr0.w"))
+        {
+            visit_exprs(*xyz, &program.exprs, &mut used_exprs);
+        }
+    } else {
+        // If XYZ merging isn't possible, there shouldn't be any used exprs.
+        used_exprs = (0..program.exprs.len()).collect();
+    }
+
+    for (i, expr) in program.exprs.iter().enumerate() {
+        if used_exprs.contains(&i) {
+            let mut value = String::new();
+            let ty = if write_expr(&mut value, expr, language).is_some() {
+                glsl_type(expr)
+            } else {
+                value = "0.0".to_string();
+                "float"
+            };
+            write_declaration(&mut source, language, ty, VAR_PREFIX, i, &value);
+        }
+    }
+
+    for (i, expr) in program.exprs_xyz.iter().enumerate() {
+        let mut value = String::new();
+        let ty = if write_expr_xyz(&mut value, expr, language).is_some() {
+            glsl_type_xyz(expr)
+        } else {
+            value = "vec3(0.0)".to_string();
+            "vec3"
+        };
+        write_declaration(&mut source, language, ty, VAR_PREFIX_XYZ, i, &value);
+    }
+    source
+}
+
+fn write_declaration(
+    source: &mut String,
+    language: ShaderLanguage,
+    glsl_type: &str,
+    prefix: &str,
+    i: usize,
+    value: &str,
+) {
+    match language {
+        ShaderLanguage::Glsl => writeln!(source, "{glsl_type} {prefix}{i} = {value};").unwrap(),
+        ShaderLanguage::Wgsl => writeln!(source, "let {prefix}{i} = {value};").unwrap(),
+    }
+}
+
+// WGSL infers types, but GLSL variables need an explicit type.
+fn glsl_type(expr: &OutputExpr<Operation>) -> &'static str {
+    match expr {
+        OutputExpr::Value(Value::Int(_)) => "int",
+        OutputExpr::Func { op, .. } => match op {
+            Operation::Equal
+            | Operation::NotEqual
+            | Operation::Less
+            | Operation::Greater
+            | Operation::LessEqual
+            | Operation::GreaterEqual => "bool",
+            Operation::FloatBitsToInt => "int",
+            _ => "float",
+        },
+        OutputExpr::Value(_) => "float",
+    }
+}
+
+fn glsl_type_xyz(expr: &OutputExprXyz<OperationXyz>) -> &'static str {
+    match expr {
+        OutputExprXyz::Func { op, .. } => match op {
+            OperationXyz::Equal
+            | OperationXyz::NotEqual
+            | OperationXyz::Less
+            | OperationXyz::Greater
+            | OperationXyz::LessEqual
+            | OperationXyz::GreaterEqual => "bvec3",
+            OperationXyz::FloatBitsToInt => "ivec3",
+            _ => "vec3",
+        },
+        _ => "vec3",
+    }
+}
+
+fn visit_exprs(i: usize, exprs: &[OutputExpr<Operation>], visited: &mut BTreeSet<usize>) {
+    if visited.insert(i) {
+        match &exprs[i] {
+            OutputExpr::Value(Value::Texture(t)) => {
+                for arg in &t.texcoords {
+                    visit_exprs(*arg, exprs, visited);
+                }
+            }
+            OutputExpr::Func { args, .. } => {
+                for arg in args {
+                    visit_exprs(*arg, exprs, visited);
+                }
+            }
+            OutputExpr::Value(_) => (),
+        }
+    }
+}
+
+fn visit_exprs_xyz(
+    xyz: usize,
+    exprs: &[OutputExpr<Operation>],
+    exprs_xyz: &[OutputExprXyz<OperationXyz>],
+    visited: &mut BTreeSet<usize>,
+    visited_xyz: &mut BTreeSet<usize>,
+) {
+    if visited_xyz.insert(xyz) {
+        match &exprs_xyz[xyz] {
+            OutputExprXyz::Value(ValueXyz::Texture(t)) => {
+                for arg in &t.texcoords {
+                    visit_exprs(*arg, exprs, visited);
+                }
+            }
+            OutputExprXyz::Func { args, .. } => {
+                for arg in args {
+                    visit_exprs_xyz(*arg, exprs, exprs_xyz, visited, visited_xyz);
+                }
+            }
+            OutputExprXyz::Value(_) => (),
+        }
+    }
+}
+
+fn write_expr(
+    source: &mut String,
+    expr: &OutputExpr<Operation>,
+    language: ShaderLanguage,
+) -> Option<()> {
+    match expr {
+        OutputExpr::Value(value) => write_value(source, value, language),
+        OutputExpr::Func { op, args } => write_func(source, op, args, language),
+    }
+}
+
+fn write_value(source: &mut String, value: &Value, language: ShaderLanguage) -> Option<()> {
+    match value {
+        Value::Int(i) => {
+            write!(source, "{i:?}").unwrap();
+            Some(())
+        }
+        Value::Float(f) => {
+            write!(source, "{f:?}").unwrap();
+            Some(())
+        }
+        Value::Parameter(p) => write_parameter(source, p, language),
+        Value::Texture(t) => write_texture(source, t, language),
+        Value::Attribute(a) => {
+            if is_attribute(&a.name) {
+                write!(source, "{}", a.name).unwrap();
+                write_channel(source, a.channel);
+                Some(())
+            } else {
+                error!("Unrecognized attribute {a}");
+                None
+            }
+        }
+    }
+}
+
+fn is_attribute(name: &str) -> bool {
+    // Some "attributes" are the simplified result of queries like the eye vector.
+    name.starts_with("a_")
+        || matches!(
+            name,
+            "eye" | "light_position" | "light_map_position" | "bitangent_sign"
+        )
+}
+
+fn write_texture(source: &mut String, t: &Texture, language: ShaderLanguage) -> Option<()> {
+    write_texture_inner(source, &t.name, &t.texcoords, language)?;
+    write_channel(source, t.channel);
+    Some(())
+}
+
+fn write_texture_inner(
+    source: &mut String,
+    name: &str,
+    texcoords: &[usize],
+    language: ShaderLanguage,
+) -> Option<()> {
+    match name {
+        "g_PCFTextureSampler"
+        | "sampler0"
+        | "Sampler11"
+        | "samplerA"
+        | "samplerB"
+        | "multiplicationSampler"
+        | "frameSampler" => None,
+        _ => match language {
+            ShaderLanguage::Glsl => write_texture_glsl(source, name, texcoords),
+            ShaderLanguage::Wgsl => write_texture_wgsl(source, name, texcoords),
+        },
+    }
+}
+
+fn write_texture_glsl(source: &mut String, name: &str, texcoords: &[usize]) -> Option<()> {
+    let a = VAR_PREFIX;
+    match texcoords {
+        [u, v] => write!(source, "texture({name}, vec2({a}{u}, {a}{v}))").unwrap(),
+        [u, v, w] => write!(source, "texture({name}, vec3({a}{u}, {a}{v}, {a}{w}))").unwrap(),
+        _ => return None,
+    }
+    Some(())
+}
+
+fn write_texture_wgsl(source: &mut String, name: &str, texcoords: &[usize]) -> Option<()> {
+    let a = VAR_PREFIX;
+    match name {
+        "colorSampler2" | "colorSampler3" => None, // TODO: load all color textures
+        "reflectionSampler" => write_sampler_2d_or_cube(
+            source,
+            "reflection_texture",
+            "reflection_texture_cube",
+            "reflection_sampler",
+            texcoords,
+        ),
+        "reflectionCubeSampler" => write_sampler_2d_or_cube(
+            source,
+            "reflection_cube_texture_2d",
+            "reflection_cube_texture",
+            "reflection_cube_sampler",
+            texcoords,
+        ),
+        "g_VSMTextureSampler" => {
+            write!(
+                source,
+                "textureSample(g_vsm_texture, g_vsm_sampler, vec2({a}{}, {a}{}))",
+                texcoords.first()?,
+                texcoords.get(1)?,
+            )
+            .unwrap();
+            Some(())
+        }
+        _ => {
+            write!(
+                source,
+                "textureSample({}, {}, vec2({a}{}, {a}{}))",
+                name.to_snake().replace("_sampler", "_texture"),
+                name.to_snake(),
+                texcoords.first()?,
+                texcoords.get(1)?,
+            )
+            .unwrap();
+            Some(())
+        }
+    }
+}
+
+fn write_sampler_2d_or_cube(
+    source: &mut String,
+    name_2d: &str,
+    name_cube: &str,
+    name_sampler: &str,
+    texcoords: &[usize],
+) -> Option<()> {
+    match texcoords {
+        [u, v] => {
+            write!(
+                source,
+                "textureSample({}, {}, vec2({VAR_PREFIX}{u}, {VAR_PREFIX}{v}))",
+                name_2d, name_sampler,
+            )
+            .unwrap();
+            Some(())
+        }
+        [u, v, w] => {
+            // Assume 3D textures aren't used, so UVW coordinates should always be a cube map.
+            write!(
+                source,
+                "textureSample({}, {}, vec3({VAR_PREFIX}{u}, {VAR_PREFIX}{v}, {VAR_PREFIX}{w}))",
+                name_cube, name_sampler,
+            )
+            .unwrap();
+            Some(())
+        }
+        _ => None,
+    }
+}
+
+fn write_parameter(source: &mut String, p: &Parameter, language: ShaderLanguage) -> Option<()> {
+    if write_parameter_inner(source, &p.name, &p.field, p.index, language).is_none() {
+        error!("Unrecognized uniform {p}");
+        None
+    } else {
+        write_channel(source, p.channel);
+        Some(())
+    }
+}
+
+fn write_parameter_inner(
+    source: &mut String,
+    name: &str,
+    field: &str,
+    index: Option<usize>,
+    language: ShaderLanguage,
+) -> Option<()> {
+    if name == "PerDraw" && field == "LocalToWorldMatrix" {
+        write!(source, "local_to_world_matrix").unwrap();
+        write_index(source, index);
+        return Some(());
+    }
+    match language {
+        ShaderLanguage::Glsl => write_parameter_glsl(source, name, field, index),
+        ShaderLanguage::Wgsl => write_parameter_wgsl(source, name, field, index),
+    }
+}
+
+fn write_parameter_glsl(
+    source: &mut String,
+    name: &str,
+    field: &str,
+    index: Option<usize>,
+) -> Option<()> {
+    match name {
+        "MC" | "MC_EFFECT" | "FB0" | "FB1" | "FB3" | "FB4" | "FB5" | "PerDraw" | "PerView" => {
+            write!(source, "{name}.{field}").unwrap();
+            write_index(source, index);
+            Some(())
+        }
+        _ => None,
+    }
+}
+
+fn write_parameter_wgsl(
+    source: &mut String,
+    name: &str,
+    field: &str,
+    index: Option<usize>,
+) -> Option<()> {
+    // TODO: just convert case instead of matching names?
+    match name {
+        "MC" => write_parameter_inner2(source, "uniforms", field, index),
+        "MC_EFFECT" => write_parameter_inner2(source, "effect_uniforms", field, index),
+        "FB0" => write_parameter_inner2(source, "fb0", field, index),
+        "FB1" => write_parameter_inner2(source, "fb1", field, index),
+        "FB3" => write_parameter_inner2(source, "fb3", field, index),
+        // TODO: fix shader handling for effect_light_entry
+        // "FB4" => write_parameter_inner2(source, "fb4", field, index),
+        "FB5" => write_parameter_inner2(source, "fb5", field, index),
+        "PerDraw" => match field {
+            "LocalToViewMatrix" => write_parameter_inner2(source, "camera", "view", index),
+            "LocalToProjectionMatrix" => {
+                write_parameter_inner2(source, "camera", "view_projection", index)
+            }
+            _ => {
+                return None;
+            }
+        },
+        "PerView" => match field {
+            "WorldToProjectionMatrix" => {
+                write_parameter_inner2(source, "camera", "view_projection", index)
+            }
+            "WorldToViewMatrix" => write_parameter_inner2(source, "camera", "view", index),
+            "ViewToProjectionMatrix" => {
+                write_parameter_inner2(source, "camera", "projection", index)
+            }
+            _ => {
+                return None;
+            }
+        },
+        _ => {
+            return None;
+        }
+    }
+    Some(())
+}
+
+fn write_parameter_inner2(
+    source: &mut String,
+    buffer_name: &str,
+    field: &str,
+    index: Option<usize>,
+) {
+    write!(source, "{buffer_name}.{}", field.to_snake()).unwrap();
+    write_index(source, index);
+}
+
+fn write_func(
+    source: &mut String,
+    op: &Operation,
+    args: &[usize],
+    language: ShaderLanguage,
+) -> Option<()> {
+    let arg0 = args.first();
+    let arg1 = args.get(1);
+    let arg2 = args.get(2);
+    let arg3 = args.get(3);
+    let arg4 = args.get(4);
+    let arg5 = args.get(5);
+    let arg6 = args.get(6);
+    let arg7 = args.get(7);
+    let arg8 = args.get(8);
+    let arg9 = args.get(9);
+    let arg10 = args.get(10);
+
+    let a = VAR_PREFIX;
+    let (inverse_sqrt, float) = match language {
+        ShaderLanguage::Glsl => ("inversesqrt", "float"),
+        ShaderLanguage::Wgsl => ("inverseSqrt", "f32"),
+    };
+    match op {
+        Operation::Unk => return None,
+        Operation::Add => write!(source, "{a}{} + {a}{}", arg0?, arg1?).unwrap(),
+        Operation::Sub => write!(source, "{a}{} - {a}{}", arg0?, arg1?).unwrap(),
+        Operation::Mul => write!(source, "{a}{} * {a}{}", arg0?, arg1?).unwrap(),
+        Operation::Div => write!(source, "{a}{} / {a}{}", arg0?, arg1?).unwrap(),
+        Operation::Mix => write!(source, "mix({a}{}, {a}{}, {a}{})", arg0?, arg1?, arg2?).unwrap(),
+        Operation::Clamp => write!(source, "clamp({a}{}, {a}{}, {a}{})", arg0?, arg1?, arg2?).unwrap(),
+        Operation::Min => write!(source, "min({a}{}, {a}{})", arg0?, arg1?).unwrap(),
+        Operation::Max => write!(source, "max({a}{}, {a}{})", arg0?, arg1?).unwrap(),
+        Operation::Abs => write!(source, "abs({a}{})", arg0?).unwrap(),
+        Operation::Floor => write!(source, "floor({a}{})", arg0?).unwrap(),
+        Operation::Power => write!(source, "pow({a}{}, {a}{})", arg0?, arg1?).unwrap(),
+        Operation::Sqrt => write!(source, "sqrt({a}{})", arg0?).unwrap(),
+        Operation::InverseSqrt => write!(source, "{inverse_sqrt}({a}{})", arg0?).unwrap(),
+        Operation::Fma => write!(source, "{a}{} * {a}{} + {a}{}", arg0?, arg1?, arg2?).unwrap(),
+        Operation::Dot3 => write!(source,
+            "dot(vec3({a}{}, {a}{}, {a}{}), vec3({a}{}, {a}{}, {a}{}))",
+            arg0?, arg1?, arg2?, arg3?, arg4?, arg5?
+        ).unwrap(),
+        Operation::Dot4 => write!(source,
+            "dot(vec4({a}{}, {a}{}, {a}{}, {a}{}), vec4({a}{}, {a}{}, {a}{}, {a}{}))",
+            arg0?, arg1?, arg2?, arg3?, arg4?, arg5?, arg6?, arg7?
+        ).unwrap(),
+        Operation::Sin => write!(source, "sin({a}{})", arg0?).unwrap(),
+        Operation::Cos => write!(source, "cos({a}{})", arg0?).unwrap(),
+        Operation::Exp2 => write!(source, "exp2({a}{})", arg0?).unwrap(),
+        Operation::Log2 => write!(source, "log2({a}{})", arg0?).unwrap(),
+        Operation::Select => write!(source,
+            "mix({a}{}, {a}{}, {float}({a}{}))",
+            arg2?, arg1?, arg0?
+        ).unwrap(),
+        Operation::Negate => write!(source, "-({a}{})", arg0?).unwrap(),
+        Operation::Equal => write!(source, "{a}{} == {a}{}", arg0?, arg1?).unwrap(),
+        Operation::NotEqual => write!(source, "{a}{} != {a}{}", arg0?, arg1?).unwrap(),
+        Operation::Less => write!(source, "{a}{} < {a}{}", arg0?, arg1?).unwrap(),
+        Operation::Greater => write!(source, "{a}{} > {a}{}", arg0?, arg1?).unwrap(),
+        Operation::LessEqual => write!(source, "{a}{} <= {a}{}", arg0?, arg1?).unwrap(),
+        Operation::GreaterEqual => write!(source, "{a}{} >= {a}{}", arg0?, arg1?).unwrap(),
+        Operation::Fract => write!(source, "fract({a}{})", arg0?).unwrap(),
+        Operation::IntBitsToFloat => match language {
+            ShaderLanguage::Glsl => write!(source, "intBitsToFloat(int({a}{}))", arg0?).unwrap(),
+            ShaderLanguage::Wgsl => write!(source, "bitcast<f32>({a}{})", arg0?).unwrap(),
+        },
+        Operation::FloatBitsToInt => match language {
+            ShaderLanguage::Glsl => write!(source, "floatBitsToInt({a}{})", arg0?).unwrap(),
+            ShaderLanguage::Wgsl => write!(source, "bitcast<i32>({a}{})", arg0?).unwrap(),
+        },
+        Operation::NormalMapX => write!(source,
+            "apply_normal_map(vec3({a}{}, {a}{}, {a}{}), a_Tangent.xyz, a_Binormal.xyz, a_Normal.xyz).x",
+            arg0?, arg1?, arg2?
+        ).unwrap(),
+        Operation::NormalMapY => write!(source,
+            "apply_normal_map(vec3({a}{}, {a}{}, {a}{}), a_Tangent.xyz, a_Binormal.xyz, a_Normal.xyz).y",
+            arg0?, arg1?, arg2?
+        ).unwrap(),
+        Operation::NormalMapZ => write!(source,
+            "apply_normal_map(vec3({a}{}, {a}{}, {a}{}), a_Tangent.xyz, a_Binormal.xyz, a_Normal.xyz).z",
+            arg0?, arg1?, arg2?
+        ).unwrap(),
+        Operation::NormalizeX => write!(source,
+            "normalize(vec3({a}{}, {a}{}, {a}{})).x",
+            arg0?, arg1?, arg2?
+        ).unwrap(),
+        Operation::NormalizeY => write!(source,
+            "normalize(vec3({a}{}, {a}{}, {a}{})).y",
+            arg0?, arg1?, arg2?
+        ).unwrap(),
+        Operation::NormalizeZ => write!(source,
+            "normalize(vec3({a}{}, {a}{}, {a}{})).z",
+            arg0?, arg1?, arg2?
+        ).unwrap(),
+        Operation::SphereMapCoordX => write!(source,
+            "sphere_map_coords(a_Position.xyz, a_Normal.xyz, {a}{}).x",
+            arg0?,
+        ).unwrap(),
+        Operation::SphereMapCoordY => write!(source,
+            "sphere_map_coords(a_Position.xyz, a_Normal.xyz, {a}{}).y",
+            arg0?,
+        ).unwrap(),
+        // TODO: Don't assume attributes are already in world space.
+        Operation::LocalToWorldPointX => write!(source, "{a}{}", arg0?).unwrap(),
+        Operation::LocalToWorldPointY => write!(source, "{a}{}", arg1?).unwrap(),
+        Operation::LocalToWorldPointZ => write!(source, "{a}{}", arg2?).unwrap(),
+        Operation::LocalToWorldVectorX => write!(source, "{a}{}", arg0?).unwrap(),
+        Operation::LocalToWorldVectorY => write!(source, "{a}{}", arg1?).unwrap(),
+        Operation::LocalToWorldVectorZ => write!(source, "{a}{}", arg2?).unwrap(),
+        Operation::VarianceShadow => write!(source,
+            "variance_shadow({a}{}, {a}{}, {a}{}, {a}{})",
+            arg0?, arg1?, arg2?, arg3?
+        ).unwrap(),
+        Operation::BlinnPhongSpecular => write!(source,
+            "blinn_phong_spec(vec3({a}{}, {a}{}, {a}{}), vec3({a}{}, {a}{}, {a}{}), vec3({a}{}, {a}{}, {a}{}), {a}{})",
+            arg0?, arg1?, arg2?, arg3?, arg4?, arg5?, arg6?, arg7?, arg8?, arg9?
+        ).unwrap(),
+        Operation::AnisotropicSpecular => write!(source,
+            "anisotropic_spec(vec3({a}{}, {a}{}, {a}{}), vec3({a}{}, {a}{}, {a}{}), vec3({a}{}, {a}{}, {a}{}), vec2({a}{}, {a}{}))",
+            arg0?, arg1?, arg2?, arg3?, arg4?, arg5?, arg6?, arg7?, arg8?, arg9?, arg10?
+        ).unwrap(),
+        Operation::Fresnel => write!(source,
+            "fresnel(vec3({a}{}, {a}{}, {a}{}), vec3({a}{}, {a}{}, {a}{}), {a}{})",
+            arg0?, arg1?, arg2?, arg3?, arg4?, arg5?, arg6?,
+        ).unwrap(),
+        Operation::TintColorX => write!(source,
+            "tint_color(vec3({a}{}, {a}{}, {a}{}), {a}{}).x",
+            arg0?, arg1?, arg2?, arg3?,
+        ).unwrap(),
+        Operation::TintColorY => write!(source,
+            "tint_color(vec3({a}{}, {a}{}, {a}{}), {a}{}).y",
+            arg0?, arg1?, arg2?, arg3?,
+        ).unwrap(),
+        Operation::TintColorZ => write!(source,
+            "tint_color(vec3({a}{}, {a}{}, {a}{}), {a}{}).z",
+            arg0?, arg1?, arg2?, arg3?,
+        ).unwrap(),
+        Operation::NegReflectX => write!(source,
+            "-reflect(vec3({a}{}, {a}{}, {a}{}), vec3({a}{}, {a}{}, {a}{})).x",
+            arg0?, arg1?, arg2?, arg3?, arg4?, arg5?
+        ).unwrap(),
+        Operation::NegReflectY => write!(source,
+            "-reflect(vec3({a}{}, {a}{}, {a}{}), vec3({a}{}, {a}{}, {a}{})).y",
+            arg0?, arg1?, arg2?, arg3?, arg4?, arg5?
+        ).unwrap(),
+        Operation::NegReflectZ => write!(source,
+            "-reflect(vec3({a}{}, {a}{}, {a}{}), vec3({a}{}, {a}{}, {a}{})).z",
+            arg0?, arg1?, arg2?, arg3?, arg4?, arg5?
+        ).unwrap(),
+    }
+    Some(())
+}
+
+fn write_index(source: &mut String, i: Option<usize>) {
+    if let Some(i) = i {
+        write!(source, "[{i}]").unwrap();
+    }
+}
+
+fn write_channel(source: &mut String, c: Option<char>) {
+    if let Some(c) = c {
+        write!(source, ".{c}").unwrap();
+    }
+}
+
+fn generate_outputs(program: &ShaderProgram) -> String {
+    let mut source = String::new();
+    // TODO: Discard the scalar expressions that aren't actually used?
+
+    // Assume there is only one fragment output.
+    // Most shaders can merge XYZ channels for easier to read code.
+    if let Some(xyz) = program
+        .output_dependencies_xyz
+        .get(&SmolStr::from("out_attr0.xyz"))
+    {
+        for c in "xyz".chars() {
+            writeln!(&mut source, "{OUT_VAR}.{c} = {VAR_PREFIX_XYZ}{xyz}.{c};").unwrap()
+        }
+    } else {
+        for c in "xyz".chars() {
+            if let Some(i) = program
+                .output_dependencies
+                .get(&format_smolstr!("out_attr0.{c}"))
+            {
+                writeln!(&mut source, "{OUT_VAR}.{c} = {VAR_PREFIX}{i};").unwrap()
+            }
+        }
+    }
+    // Alpha code is always handled separately as scalar expressions.
+    if let Some(i) = program
+        .output_dependencies
+        .get(&SmolStr::from("out_attr0.w"))
+    {
+        writeln!(&mut source, "{OUT_VAR}.w = {VAR_PREFIX}{i};").unwrap()
+    }
+
+    source
+}
+
+fn write_expr_xyz(
+    source: &mut String,
+    value: &OutputExprXyz<OperationXyz>,
+    language: ShaderLanguage,
+) -> Option<()> {
+    match value {
+        OutputExprXyz::Func { op, args, channel } => {
+            write_func_xyz(source, op, args, *channel, language)
+        }
+        OutputExprXyz::Value(v) => write_value_xyz(source, v, language),
+    }
+}
+
+fn write_func_xyz(
+    source: &mut String,
+    op: &OperationXyz,
+    args: &[usize],
+    channel: Option<ChannelXyz>,
+    language: ShaderLanguage,
+) -> Option<()> {
+    let arg0 = args.first();
+    let arg1 = args.get(1);
+    let arg2 = args.get(2);
+    let arg3 = args.get(3);
+    let arg4 = args.get(4);
+    let arg5 = args.get(5);
+    let arg6 = args.get(6);
+    let arg7 = args.get(7);
+
+    // TODO: Will these operations all work with xyz inputs?
+    let a = VAR_PREFIX_XYZ;
+    let (inverse_sqrt, vec3) = match language {
+        ShaderLanguage::Glsl => ("inversesqrt", "vec3"),
+        ShaderLanguage::Wgsl => ("inverseSqrt", "vec3<f32>"),
+    };
+    match op {
+        OperationXyz::Unk => return None,
+        OperationXyz::Add => write!(source, "({a}{} + {a}{})", arg0?, arg1?).unwrap(),
+        OperationXyz::Sub => write!(source, "({a}{} - {a}{})", arg0?, arg1?).unwrap(),
+        OperationXyz::Mul => write!(source, "({a}{} * {a}{})", arg0?, arg1?).unwrap(),
+        OperationXyz::Div => write!(source, "({a}{} / {a}{})", arg0?, arg1?).unwrap(),
+        OperationXyz::Mix => write!(source, "mix({a}{}, {a}{}, {a}{})", arg0?, arg1?, arg2?).unwrap(),
+        OperationXyz::Clamp => write!(source, "clamp({a}{}, {a}{}, {a}{})", arg0?, arg1?, arg2?).unwrap(),
+        OperationXyz::Min => write!(source, "min({a}{}, {a}{})", arg0?, arg1?).unwrap(),
+        OperationXyz::Max => write!(source, "max({a}{}, {a}{})", arg0?, arg1?).unwrap(),
+        OperationXyz::Abs => write!(source, "abs({a}{})", arg0?).unwrap(),
+        OperationXyz::Floor => write!(source, "floor({a}{})", arg0?).unwrap(),
+        OperationXyz::Power => write!(source, "pow({a}{}, {a}{})", arg0?, arg1?).unwrap(),
+        OperationXyz::Sqrt => write!(source, "sqrt({a}{})", arg0?).unwrap(),
+        OperationXyz::InverseSqrt => write!(source, "{inverse_sqrt}({a}{})", arg0?).unwrap(),
+        OperationXyz::Fma => write!(source, "({a}{} * {a}{} + {a}{})", arg0?, arg1?, arg2?).unwrap(),
+        OperationXyz::Dot => write!(
+            source,
+            "vec3(dot(vec4({a}{}.x, {a}{}.x, {a}{}.x, {a}{}.x), vec4({a}{}.x, {a}{}.x, {a}{}.x, {a}{}.x)))",
+            arg0?, arg1?, arg2?, arg3?, arg4?, arg5?, arg6?, arg7?
+        )
+        .unwrap(),
+        OperationXyz::Sin => write!(source, "sin({a}{})", arg0?).unwrap(),
+        OperationXyz::Cos => write!(source, "cos({a}{})", arg0?).unwrap(),
+        OperationXyz::Exp2 => write!(source, "exp2({a}{})", arg0?).unwrap(),
+        OperationXyz::Log2 => write!(source, "log2({a}{})", arg0?).unwrap(),
+        OperationXyz::Select => {
+            write!(source, "mix({a}{}, {a}{}, {vec3}({a}{}))", arg2?, arg1?, arg0?).unwrap()
+        }
+        OperationXyz::Negate => write!(source, "-({a}{})", arg0?).unwrap(),
+        // GLSL comparison operators return a single bool for vectors.
+        OperationXyz::Equal => write_comparison_xyz(source, language, "==", "equal", args)?,
+        OperationXyz::NotEqual => write_comparison_xyz(source, language, "!=", "notEqual", args)?,
+        OperationXyz::Less => write_comparison_xyz(source, language, "<", "lessThan", args)?,
+        OperationXyz::Greater => write_comparison_xyz(source, language, ">", "greaterThan", args)?,
+        OperationXyz::LessEqual => write_comparison_xyz(source, language, "<=", "lessThanEqual", args)?,
+        OperationXyz::GreaterEqual => write_comparison_xyz(source, language, ">=", "greaterThanEqual", args)?,
+        OperationXyz::Fract => write!(source, "fract({a}{})", arg0?).unwrap(),
+        OperationXyz::IntBitsToFloat => match language {
+            ShaderLanguage::Glsl => write!(source, "intBitsToFloat(ivec3({a}{}))", arg0?).unwrap(),
+            ShaderLanguage::Wgsl => write!(source, "bitcast<f32>({a}{})", arg0?).unwrap(),
+        },
+        OperationXyz::FloatBitsToInt => match language {
+            ShaderLanguage::Glsl => write!(source, "floatBitsToInt({a}{})", arg0?).unwrap(),
+            ShaderLanguage::Wgsl => write!(source, "bitcast<i32>({a}{})", arg0?).unwrap(),
+        },
+        OperationXyz::NormalMap => write!(
+            source,
+            "apply_normal_map({a}{}, a_Tangent.xyz, a_Binormal.xyz, a_Normal.xyz)",
+            arg0?
+        )
+        .unwrap(),
+        OperationXyz::Normalize => write!(source, "normalize({a}{})", arg0?).unwrap(),
+        // TODO: Don't assume attributes are already in world space.
+        OperationXyz::LocalToWorldPoint => write!(source, "{a}{}", arg0?).unwrap(),
+        OperationXyz::LocalToWorldVector => write!(source, "{a}{}", arg0?).unwrap(),
+        OperationXyz::VarianceShadow => write!(
+            source,
+            "vec3(variance_shadow({a}{}.x, {a}{}.x, {a}{}.x, {a}{}.x))",
+            arg0?, arg1?, arg2?, arg3?
+        )
+        .unwrap(),
+        OperationXyz::BlinnPhongSpecular => write!(
+            source,
+            "vec3(blinn_phong_spec({a}{}, {a}{}, {a}{}, {a}{}.x))",
+            arg0?, arg1?, arg2?, arg3?,
+        )
+        .unwrap(),
+        OperationXyz::AnisotropicSpecular => write!(
+            source,
+            "vec3(anisotropic_spec({a}{}, {a}{}, {a}{}, vec2({a}{}.x, {a}{}.x)))",
+            arg0?, arg1?, arg2?, arg3?, arg4?
+        )
+        .unwrap(),
+        OperationXyz::Fresnel => {
+            write!(source, "vec3(fresnel({a}{}, {a}{}, {a}{}.x))", arg0?, arg1?, arg2?).unwrap()
+        }
+        OperationXyz::TintColor => {
+            write!(source, "tint_color({a}{}, {a}{}.x)", arg0?, arg1?).unwrap()
+        }
+        OperationXyz::NegReflect => {
+            write!(source, "-reflect({a}{}, {a}{})", arg0?, arg1?).unwrap()
+        }
+    }
+    write_channel_xyz(source, channel);
+    Some(())
+}
+
+fn write_comparison_xyz(
+    source: &mut String,
+    language: ShaderLanguage,
+    operator: &str,
+    glsl_func: &str,
+    args: &[usize],
+) -> Option<()> {
+    let a = VAR_PREFIX_XYZ;
+    let (arg0, arg1) = (args.first()?, args.get(1)?);
+    match language {
+        ShaderLanguage::Glsl => write!(source, "{glsl_func}({a}{arg0}, {a}{arg1})").unwrap(),
+        ShaderLanguage::Wgsl => write!(source, "({a}{arg0} {operator} {a}{arg1})").unwrap(),
+    }
+    Some(())
+}
+
+// TODO: share code with scalar?
+fn write_value_xyz(source: &mut String, value: &ValueXyz, language: ShaderLanguage) -> Option<()> {
+    match value {
+        ValueXyz::Texture(t) => {
+            write_texture_inner(source, &t.name, &t.texcoords, language)?;
+            write_channel_xyz(source, t.channel);
+            Some(())
+        }
+        ValueXyz::Attribute(a) => {
+            if is_attribute(&a.name) {
+                write!(source, "{}", a.name).unwrap();
+                write_channel_xyz(source, a.channel);
+                Some(())
+            } else {
+                error!("Unrecognized attribute {a}");
+                None
+            }
+        }
+        ValueXyz::Parameter(p) => {
+            if write_parameter_inner(source, &p.name, &p.field, p.index, language).is_none() {
+                error!("Unrecognized parameter {p}");
+                None
+            } else {
+                write_channel_xyz(source, p.channel);
+                Some(())
+            }
+        }
+        ValueXyz::Float(f) => {
+            if f.iter().all(|f| f.is_finite()) {
+                write!(source, "vec3({:?}, {:?}, {:?})", f[0], f[1], f[2]).unwrap();
+                Some(())
+            } else {
+                error!("Unsupported float literals {f:?}");
+                None
+            }
+        }
+    }
+}
+
+fn write_channel_xyz(source: &mut String, c: Option<ChannelXyz>) {
+    if let Some(c) = c {
+        match c {
+            ChannelXyz::Xyz => write!(source, ".xyz").unwrap(),
+            ChannelXyz::X => write!(source, ".xxx").unwrap(),
+            ChannelXyz::Y => write!(source, ".yyy").unwrap(),
+            ChannelXyz::Z => write!(source, ".zzz").unwrap(),
+            ChannelXyz::W => write!(source, ".www").unwrap(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::database::test_utils::{attribute, float, func, program, texture};
+
+    fn test_program() -> ShaderProgram {
+        program(
+            vec![
+                attribute("a_TexCoord0", 'x'),
+                attribute("a_TexCoord0", 'y'),
+                texture("colorSampler", &[0, 1], 'x'),
+                float(0.5),
+                func(Operation::InverseSqrt, &[2]),
+                func(Operation::Less, &[2, 3]),
+            ],
+            &[("out_attr0.x", 4), ("out_attr0.w", 3)],
+        )
+    }
+
+    #[test]
+    fn fragment_code_wgsl() {
+        assert_eq!(
+            FragmentCode {
+                assignments: concat!(
+                    "let VAR_0 = a_TexCoord0.x;\n",
+                    "let VAR_1 = a_TexCoord0.y;\n",
+                    "let VAR_2 = textureSample(color_texture, color_sampler, vec2(VAR_0, VAR_1)).x;\n",
+                    "let VAR_3 = 0.5;\n",
+                    "let VAR_4 = inverseSqrt(VAR_2);\n",
+                    "let VAR_5 = VAR_2 < VAR_3;\n",
+                )
+                .to_string(),
+                outputs: "out_color.x = VAR_4;\nout_color.w = VAR_3;\n".to_string()
+            },
+            FragmentCode::new(&test_program(), ShaderLanguage::Wgsl)
+        );
+    }
+
+    #[test]
+    fn fragment_code_glsl() {
+        assert_eq!(
+            concat!(
+                "float VAR_0 = a_TexCoord0.x;\n",
+                "float VAR_1 = a_TexCoord0.y;\n",
+                "float VAR_2 = texture(colorSampler, vec2(VAR_0, VAR_1)).x;\n",
+                "float VAR_3 = 0.5;\n",
+                "float VAR_4 = inversesqrt(VAR_2);\n",
+                "bool VAR_5 = VAR_2 < VAR_3;\n",
+            ),
+            FragmentCode::new(&test_program(), ShaderLanguage::Glsl).assignments
+        );
+    }
+
+    #[test]
+    fn fragment_function_glsl() {
+        let source = fragment_function(&test_program(), ShaderLanguage::Glsl);
+        assert!(source.starts_with(GLSL_FUNCTIONS));
+        assert!(source.contains("vec4 fragment_color(vec4 a_Position, "));
+        assert!(source.ends_with("out_color.w = VAR_3;\nreturn out_color;\n}\n"));
+    }
+
+    #[test]
+    fn fragment_function_wgsl_valid() {
+        let source = format!(
+            "struct Camera {{
+                view: mat4x4<f32>,
+                projection: mat4x4<f32>,
+                view_projection: mat4x4<f32>,
+            }}
+            @group(0) @binding(0) var<uniform> camera: Camera;
+            @group(0) @binding(1) var color_texture: texture_2d<f32>;
+            @group(0) @binding(2) var color_sampler: sampler;
+            {}",
+            fragment_function(&test_program(), ShaderLanguage::Wgsl)
+        );
+        let module = naga::front::wgsl::parse_str(&source).unwrap();
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&module)
+        .unwrap();
+    }
+
+    #[test]
+    fn fragment_function_glsl_valid() {
+        let source = format!(
+            "#version 330
+            layout(std140) uniform PerViewBlock {{
+                mat4 WorldToViewMatrix;
+            }} PerView;
+            uniform sampler2D colorSampler;
+            {}
+            void main() {{}}",
+            fragment_function(&test_program(), ShaderLanguage::Glsl)
+        );
+        // naga only parses GLSL 440 and later, so only the version is changed for parsing.
+        // This doesn't detect newer features, but the declarations avoid layouts like binding.
+        naga::front::glsl::Frontend::default()
+            .parse(
+                &naga::front::glsl::Options::from(naga::ShaderStage::Fragment),
+                &source.replace("#version 330", "#version 450"),
+            )
+            .unwrap();
+    }
+}
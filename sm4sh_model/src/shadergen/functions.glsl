@@ -0,0 +1,66 @@
+vec3 apply_normal_map(vec3 normal_map, vec3 tangent, vec3 bitangent, vec3 normal) {
+    // Normal mapping is a change of basis using the TBN vectors.
+    // Adapted from texas_cross.105.frag.
+    float offset = 1.0 / (255.0 * 2.0);
+    float x = (normal_map.x + offset) * 2.0 - 1.0;
+    float y = (normal_map.y + offset) * 2.0 - 1.0;
+    float z = (normal_map.z + offset) * 2.0 - 1.0;
+    return normalize(tangent * x + bitangent * y + normal * z);
+}
+
+vec4 sphere_map_coords(vec3 position, vec3 normal, float param) {
+    vec3 adjusted_normal = normalize(normal) + position * param;
+    vec3 view_normal = (PerView.WorldToViewMatrix * vec4(adjusted_normal, 0.0)).xyz;
+    // The in game shaders use 1.0 - view_normal.y due to graphics API differences.
+    vec2 coords = vec2(view_normal.x, view_normal.y);
+    return vec4((-param * 0.25 + 0.5) * coords + 0.5, 0.0, 0.0);
+}
+
+float variance_shadow(float m1, float m2, float light_position_z, float offset) {
+    // Variance shadow mapping adapted from texas_cross.35.frag.
+    float current_depth = min(light_position_z, 1.0);
+    float sigma2 = clamp(m2 - m1 * m1 + offset, 0.0, 1.0);
+    float tDif = max(current_depth - m1, 0.0);
+    float shadow = sigma2 / (sigma2 + tDif * tDif);
+    return pow(shadow, 4.0);
+}
+
+float blinn_phong_spec(vec3 normal, vec3 light_dir, vec3 eye, float exponent) {
+    // Blinn-phong specular reflections adapted from texas_cross.64.frag.
+    vec3 h = normalize(eye - light_dir);
+    float spec = max(dot(normal, h), 0.001);
+    return pow(spec, exponent);
+}
+
+float anisotropic_spec(vec3 normal, vec3 tangent, vec3 eye, vec2 params) {
+    // Anisotropic specular from texas_cross.105.frag.
+    float param_x = params.x * 3.0;
+    float param_x2 = param_x * param_x;
+
+    float param_y = params.y * 3.0;
+    float param_y2 = param_y * param_y;
+
+    float dot_eye_n = dot(eye, normal);
+    float dot_eye_n2 = dot_eye_n * dot_eye_n;
+
+    vec3 b = normalize(eye - normal * dot_eye_n);
+    float dot_tangent_b = dot(tangent, b);
+    float dot_tangent_b2 = dot_tangent_b * dot_tangent_b;
+
+    float x_term = dot_tangent_b2 / param_x2;
+    float y_term = (1.0 - dot_tangent_b2) / param_y2;
+
+    return exp((dot_eye_n2 - 1.0) / dot_eye_n2 * (x_term + y_term));
+}
+
+float fresnel(vec3 normal, vec3 eye, float param) {
+    // Fresnel edge lighting adapted from texas_cross.64.frag.
+    float value = 1.0 - clamp(dot(eye.xyz, normal.xyz), 0.0, 1.0);
+    return pow(value, 1.0 + param);
+}
+
+vec3 tint_color(vec3 color, float amount) {
+    // Diffuse color tint from texas_cross.105.frag.
+    float max_component = max(max(color.x, color.y), color.z);
+    return (color - max_component) * amount + 1.0;
+}
@@ -0,0 +1,76 @@
+fn apply_normal_map(normal_map: vec3<f32>, tangent: vec3<f32>, bitangent: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    // Normal mapping is a change of basis using the TBN vectors.
+    // Adapted from texas_cross.105.frag.
+    let offset = 1.0 / (255.0 * 2.0);
+    let x = (normal_map.x + offset) * 2.0 - 1.0;
+    let y = (normal_map.y + offset) * 2.0 - 1.0;
+    let z = (normal_map.z + offset) * 2.0 - 1.0;
+    return normalize(tangent * x + bitangent * y + normal * z);
+}
+
+fn sphere_map_coords(position: vec3<f32>, normal: vec3<f32>, param: f32) -> vec4<f32> {
+    let adjusted_normal = normalize(normal) + position * param;
+    let view_normal = (camera.view * vec4(adjusted_normal, 0.0)).xyz;
+    // The in game shaders use 1.0 - view_normal.y due to graphics API differences.
+    let coords = vec2(view_normal.x, view_normal.y);
+    return vec4((-param * 0.25 + 0.5) * coords + 0.5, 0.0, 0.0);
+}
+
+fn variance_shadow(m1: f32, m2: f32, light_position_z: f32, offset: f32) -> f32 {
+    // Variance shadow mapping adapted from texas_cross.35.frag.
+    let current_depth = min(light_position_z, 1.0);
+
+    // The offset is FB1.ShadowMapParam.x.
+    let sigma2 = clamp(m2 - m1 * m1 + offset, 0.0, 1.0);
+    let tDif = max(current_depth - m1, 0.0);
+    // Approximate Pr(x >= t) using one of Chebychev's inqequalities.
+    var shadow = sigma2 / (sigma2 + tDif * tDif);
+    // TODO: Why is there a pow(shadow, 4.0) in game?
+    shadow = pow(shadow, 4.0);
+    return shadow;
+}
+
+fn blinn_phong_spec(normal: vec3<f32>, light_dir: vec3<f32>, eye: vec3<f32>, exponent: f32) -> f32 {
+    // Blinn-phong specular reflections adapted from texas_cross.64.frag.
+    // The exponent is MC.specularParams.y.
+    let h = normalize(eye - light_dir); // TODO: why is this sub and not add?
+    let spec = max(dot(normal, h), 0.001);
+    return pow(spec, exponent);
+}
+
+fn anisotropic_spec(normal: vec3<f32>, tangent: vec3<f32>, eye: vec3<f32>, params: vec2<f32>) -> f32 {
+    // Anisotropic specular from texas_cross.105.frag.
+    // This appears to be a slightly modified Ward BRDF BRDF.
+    // TODO: Where is the lighting direction?
+    let param_x = params.x * 3.0;
+    let param_x2 = param_x * param_x;
+
+    let param_y = params.y * 3.0;
+    let param_y2 = param_y * param_y;
+
+    let dot_eye_n = dot(eye, normal);
+    let dot_eye_n2 = dot_eye_n * dot_eye_n;
+
+    // Orthogonalize eye to normal using the Gram-Schmidt process.
+    let b = normalize(eye - normal * dot_eye_n);
+    let dot_tangent_b = dot(tangent, b);
+    let dot_tangent_b2 = dot_tangent_b * dot_tangent_b;
+
+    let x_term = dot_tangent_b2 / param_x2;
+    let y_term = (1.0 - dot_tangent_b2) / param_y2;
+
+    return exp((dot_eye_n2 - 1.0) / dot_eye_n2 * (x_term + y_term));
+}
+
+fn fresnel(normal: vec3<f32>, eye: vec3<f32>, param: f32) -> f32 {
+    // Fresnel edge lighting adapted from texas_cross.64.frag.
+    // The param is MC.fresnelParams.x.
+    let fresnel = 1.0 - clamp(dot(eye.xyz, normal.xyz), 0.0, 1.0);
+    return pow(fresnel, 1.0 + param);
+}
+
+fn tint_color(color: vec3<f32>, amount: f32) -> vec3<f32> {
+    // Diffuse color tint from texas_cross.105.frag.
+    let max_component = max(max(color.x, color.y), color.z);
+    return (color - max_component) * amount + 1.0;
+}
@@ -1,8 +1,8 @@
-use glam::Vec4;
+use glam::{Mat4, Vec3, Vec4, Vec4Swizzles};
 use half::f16;
 use log::error;
 
-use crate::vertex::BoneElementType;
+use crate::{NudMesh, NudMeshGroup, vertex::BoneElementType};
 
 #[derive(Debug, PartialEq)]
 pub struct Influence {
@@ -147,6 +147,115 @@ impl SkinWeights {
     }
 }
 
+/// Vertex attributes for a [NudMesh] after applying skinning.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PosedVertices {
+    pub positions: Vec<Vec3>,
+    /// Normalized vertex normals or `None` if the mesh has no normals.
+    pub normals: Option<Vec<Vec3>>,
+}
+
+/// Apply skinning to the vertices of `mesh` using the same logic as the sm4sh_wgpu vertex shader.
+///
+/// Billboarding for `BILLBOARD` groups is not applied since it depends on the camera.
+///
+/// The `bone_transforms` are the animated model space transforms for each bone
+/// and the `skinning_transforms` are the animated transforms with the inverse bind transforms applied.
+pub fn posed_vertices(
+    group: &NudMeshGroup,
+    mesh: &NudMesh,
+    bone_transforms: &[Mat4],
+    skinning_transforms: &[Mat4],
+) -> PosedVertices {
+    let positions = &mesh.vertices.positions;
+    let normals = mesh.vertices.normals.normals();
+
+    let transform = |transforms: &[Mat4], i: usize| {
+        // TODO: How should this handle of out range indices?
+        transforms.get(i).copied().unwrap_or(Mat4::IDENTITY)
+    };
+
+    if let Some(parent_bone_index) = group.parent_bone_index {
+        let (transform, normal_transform) = if group.name.contains("NSC") {
+            // Parenting with the parent transform.
+            // The renderer transforms normals without the inverse transpose in this case.
+            let transform = transform(bone_transforms, parent_bone_index);
+            (transform, transform)
+        } else {
+            // Parenting that assumes the base parent transform is already applied.
+            let transform = transform(skinning_transforms, parent_bone_index);
+            (transform, transform.inverse().transpose())
+        };
+
+        PosedVertices {
+            positions: positions
+                .iter()
+                .map(|p| transform.transform_point3(*p))
+                .collect(),
+            normals: normals.map(|normals| {
+                normals
+                    .iter()
+                    .map(|n| {
+                        normal_transform
+                            .transform_vector3(n.xyz())
+                            .normalize_or_zero()
+                    })
+                    .collect()
+            }),
+        }
+    } else if let Some(bones) = &mesh.vertices.bones {
+        let normal_transforms: Vec<_> = skinning_transforms
+            .iter()
+            .map(|t| t.inverse().transpose())
+            .collect();
+
+        // Linear blend skinning with up to 4 influences per vertex.
+        let blend = |i: usize, f: &dyn Fn(usize) -> Vec3| {
+            let indices = bones.bone_indices.get(i).copied().unwrap_or_default();
+            let weights = bones.weights.get(i).copied().unwrap_or_default();
+            indices
+                .iter()
+                .zip(weights.to_array())
+                .map(|(bone_index, weight)| weight * f(*bone_index as usize))
+                .sum::<Vec3>()
+        };
+
+        PosedVertices {
+            positions: positions
+                .iter()
+                .enumerate()
+                .map(|(i, p)| {
+                    blend(i, &|b| {
+                        transform(skinning_transforms, b).transform_point3(*p)
+                    })
+                })
+                .collect(),
+            normals: normals.map(|normals| {
+                normals
+                    .iter()
+                    .enumerate()
+                    .map(|(i, n)| {
+                        blend(i, &|b| {
+                            transform(&normal_transforms, b).transform_vector3(n.xyz())
+                        })
+                        .normalize_or_zero()
+                    })
+                    .collect()
+            }),
+        }
+    } else {
+        PosedVertices {
+            positions: positions.clone(),
+            normals: normals.map(|normals| {
+                normals
+                    .iter()
+                    .map(|n| n.xyz().normalize_or_zero())
+                    .collect()
+            }),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,8 +1,125 @@
-use image_dds::image;
-use sm4sh_lib::nut::NutFormat;
+use std::{borrow::Cow, path::Path};
+
+use image_dds::{ImageFormat, Mipmaps, Surface, SurfaceRgba8, image, mip_dimension};
+use sm4sh_lib::nut::{NutFormat, swap_red_blue_bgr5a1};
+use thiserror::Error;
+
+pub use image_dds::Quality;
+
+mod hashes;
+pub use hashes::{
+    MergeNutError, TextureHashError, TextureHashRange, is_global_texture_hash, merge_nuts,
+    merge_textures,
+};
 
 use crate::ImageTexture;
 
+/// Options for [ImageTexture::from_image] and [ImageTexture::from_cube_faces].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ImageTextureOptions {
+    /// Generate a full mipmap chain or use only the base level.
+    /// DDS files use their existing mipmaps and must have more than one mipmap if this is `true`.
+    pub mipmaps: bool,
+    /// Interpret the image as the 6 faces of a cube map stacked vertically.
+    /// DDS files use their existing array layers and must have 6 layers if this is `true`.
+    pub cube_map: bool,
+    pub quality: Quality,
+}
+
+impl Default for ImageTextureOptions {
+    fn default() -> Self {
+        Self {
+            mipmaps: true,
+            cube_map: false,
+            quality: Quality::Normal,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum LoadImageTextureError {
+    #[error("error reading file")]
+    Io(#[from] std::io::Error),
+
+    #[error("error loading image")]
+    Image(#[from] image::ImageError),
+
+    #[error("error reading DDS")]
+    Dds(#[from] image_dds::ddsfile::Error),
+
+    #[error("error encoding surface")]
+    Surface(#[from] image_dds::SurfaceError),
+
+    #[error("error converting image format")]
+    Format(#[from] sm4sh_lib::nut::CreateSurfaceError),
+
+    #[error("error converting NUT data")]
+    Nut(#[from] sm4sh_lib::nut::CreateNutError),
+
+    #[error(
+        "cube map strip dimensions {width}x{height} are not a vertical strip of 6 square faces"
+    )]
+    CubeMapStrip { width: u32, height: u32 },
+
+    #[error(
+        "cube map face {face} has dimensions {width}x{height} but expected {expected_width}x{expected_height}"
+    )]
+    CubeMapFace {
+        face: usize,
+        width: u32,
+        height: u32,
+        expected_width: u32,
+        expected_height: u32,
+    },
+
+    #[error(
+        "DDS with {layers} layers and {mipmaps} mipmaps does not match mipmaps: {mipmaps_option}, cube_map: {cube_map_option}"
+    )]
+    DdsOptions {
+        layers: u32,
+        mipmaps: u32,
+        mipmaps_option: bool,
+        cube_map_option: bool,
+    },
+}
+
+#[derive(Debug, Error)]
+pub enum SaveImageTextureError {
+    #[error("error writing file")]
+    Io(#[from] std::io::Error),
+
+    #[error("error saving image")]
+    Image(#[from] image::ImageError),
+
+    #[error("error creating surface")]
+    Surface(#[from] sm4sh_lib::nut::CreateSurfaceError),
+
+    #[error("error decoding surface")]
+    Decode(#[from] image_dds::SurfaceError),
+
+    #[error("error creating DDS")]
+    CreateDds(#[from] image_dds::CreateDdsError),
+
+    #[error("error writing DDS")]
+    Dds(#[from] image_dds::ddsfile::Error),
+
+    #[error("mipmap {mipmap} is out of range for {mipmap_count} mipmaps")]
+    MipmapOutOfRange { mipmap: u32, mipmap_count: u32 },
+
+    #[error("image data is too small for the texture dimensions")]
+    ImageDataSize,
+}
+
+/// How to arrange the array layers of cube maps when saving.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum CubeMapLayout {
+    /// A single image with faces stacked vertically like the global textures.
+    #[default]
+    VerticalStrip,
+    /// A separate image for each face with the face index appended to the file name.
+    Faces,
+}
+
 pub fn global_textures() -> Vec<ImageTexture> {
     vec![
         solid_color_texture(0x10000001, [238, 28, 36, 255]),
@@ -41,3 +158,535 @@ fn load_png_cube_image(hash_id: u32, bytes: &[u8]) -> ImageTexture {
         image_data: image.into_rgba8().into_raw(),
     }
 }
+
+impl ImageTexture {
+    /// Load an image from `path` and encode it to `format`.
+    ///
+    /// DDS files are decoded and reencoded if the format does not match `format`.
+    /// The existing mipmaps and layers for DDS files are preserved,
+    /// so this returns an error if they don't match the `options`.
+    /// Other files like PNG or TGA are loaded with the image crate.
+    ///
+    /// The `hash_id` is not allocated automatically since this doesn't know the other textures in the NUT.
+    /// Use [TextureHashRange::hash] or [crate::NudModel::allocate_texture_hashes]
+    /// to choose a `hash_id` that doesn't collide with textures for other fighters and slots.
+    pub fn from_image<P: AsRef<Path>>(
+        hash_id: u32,
+        path: P,
+        format: NutFormat,
+        options: &ImageTextureOptions,
+    ) -> Result<Self, LoadImageTextureError> {
+        let path = path.as_ref();
+
+        let is_dds = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("dds"));
+        if is_dds {
+            let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+            let dds = image_dds::ddsfile::Dds::read(&mut reader)?;
+            let surface = image_dds::Surface::from_dds(&dds)?;
+            Self::from_dds_surface(hash_id, surface, format, options)
+        } else {
+            let image = image::open(path)?.into_rgba8();
+            Self::from_rgba8_image(hash_id, &image, format, options)
+        }
+    }
+
+    fn from_dds_surface<T: AsRef<[u8]>>(
+        hash_id: u32,
+        surface: Surface<T>,
+        format: NutFormat,
+        options: &ImageTextureOptions,
+    ) -> Result<Self, LoadImageTextureError> {
+        if options.mipmaps != (surface.mipmaps > 1) || options.cube_map != (surface.layers == 6) {
+            return Err(LoadImageTextureError::DdsOptions {
+                layers: surface.layers,
+                mipmaps: surface.mipmaps,
+                mipmaps_option: options.mipmaps,
+                cube_map_option: options.cube_map,
+            });
+        }
+
+        let image_format: ImageFormat = format.try_into()?;
+        if surface.image_format == image_format {
+            Ok(Self::from_surface(hash_id, surface)?)
+        } else {
+            let rgba = surface.decode_rgba8()?;
+            let surface = rgba.encode(image_format, options.quality, Mipmaps::FromSurface)?;
+            Ok(Self::from_surface(hash_id, surface)?)
+        }
+    }
+
+    /// Load the 6 faces of a cube map in the order +X, -X, +Y, -Y, +Z, -Z and encode it to `format`.
+    pub fn from_cube_faces<P: AsRef<Path>>(
+        hash_id: u32,
+        paths: &[P; 6],
+        format: NutFormat,
+        options: &ImageTextureOptions,
+    ) -> Result<Self, LoadImageTextureError> {
+        let faces = paths
+            .iter()
+            .map(|p| Ok(image::open(p)?.into_rgba8()))
+            .collect::<Result<Vec<_>, LoadImageTextureError>>()?;
+
+        let (width, height) = faces[0].dimensions();
+        for (face, image) in faces.iter().enumerate() {
+            if image.dimensions() != (width, height) {
+                return Err(LoadImageTextureError::CubeMapFace {
+                    face,
+                    width: image.width(),
+                    height: image.height(),
+                    expected_width: width,
+                    expected_height: height,
+                });
+            }
+        }
+
+        let surface = SurfaceRgba8 {
+            width,
+            height,
+            depth: 1,
+            layers: 6,
+            mipmaps: 1,
+            data: faces
+                .iter()
+                .flat_map(|f| f.as_raw())
+                .copied()
+                .collect::<Vec<_>>(),
+        };
+        encode_rgba8(hash_id, surface, format, options)
+    }
+
+    /// Encode an RGBA8 image to `format`.
+    ///
+    /// Cube maps use faces stacked vertically like the global textures.
+    pub fn from_rgba8_image(
+        hash_id: u32,
+        image: &image::RgbaImage,
+        format: NutFormat,
+        options: &ImageTextureOptions,
+    ) -> Result<Self, LoadImageTextureError> {
+        let surface = if options.cube_map {
+            let (width, height) = image.dimensions();
+            if width == 0 || height != width * 6 {
+                return Err(LoadImageTextureError::CubeMapStrip { width, height });
+            }
+            SurfaceRgba8::from_image_layers(image, 6)
+        } else {
+            SurfaceRgba8::from_image(image)
+        };
+        encode_rgba8(hash_id, surface, format, options)
+    }
+}
+
+fn encode_rgba8<T: AsRef<[u8]>>(
+    hash_id: u32,
+    surface: SurfaceRgba8<T>,
+    format: NutFormat,
+    options: &ImageTextureOptions,
+) -> Result<ImageTexture, LoadImageTextureError> {
+    let mipmaps = if options.mipmaps {
+        Mipmaps::GeneratedAutomatic
+    } else {
+        Mipmaps::Disabled
+    };
+    let surface = surface.encode(format.try_into()?, options.quality, mipmaps)?;
+    Ok(ImageTexture::from_surface(hash_id, surface)?)
+}
+
+impl ImageTexture {
+    /// Decode the given `mipmap` for all array layers stacked vertically.
+    ///
+    /// [NutFormat::R32Float] decodes to RGBA32F and all other formats decode to RGBA8.
+    pub fn to_image(&self, mipmap: u32) -> Result<image::DynamicImage, SaveImageTextureError> {
+        let layers: Vec<_> = (0..self.layers).collect();
+        self.decode_layers(mipmap, &layers)
+    }
+
+    /// Decode the given `mipmap` for each array layer as a separate image.
+    ///
+    /// Cube maps use the face order +X, -X, +Y, -Y, +Z, -Z.
+    pub fn to_cube_faces(
+        &self,
+        mipmap: u32,
+    ) -> Result<Vec<image::DynamicImage>, SaveImageTextureError> {
+        (0..self.layers)
+            .map(|layer| self.decode_layers(mipmap, &[layer]))
+            .collect()
+    }
+
+    /// Decode the given `mipmap` and save it to `path` as PNG.
+    ///
+    /// RGBA32F images are saved as 16-bit PNG to preserve more precision.
+    /// [CubeMapLayout::Faces] saves each face to a file like `texture_0.png`.
+    pub fn save_png<P: AsRef<Path>>(
+        &self,
+        path: P,
+        mipmap: u32,
+        layout: CubeMapLayout,
+    ) -> Result<(), SaveImageTextureError> {
+        let path = path.as_ref();
+        match layout {
+            CubeMapLayout::VerticalStrip => save_png(&self.to_image(mipmap)?, path),
+            CubeMapLayout::Faces if self.layers > 1 => {
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                for (i, face) in self.to_cube_faces(mipmap)?.iter().enumerate() {
+                    save_png(face, &path.with_file_name(format!("{stem}_{i}.png")))?;
+                }
+                Ok(())
+            }
+            CubeMapLayout::Faces => save_png(&self.to_image(mipmap)?, path),
+        }
+    }
+
+    /// Save all layers and mipmaps to `path` as DDS without decoding.
+    ///
+    /// Formats not supported by DDS like [NutFormat::B5G6R5Unorm] are decoded to RGBA8.
+    pub fn save_dds<P: AsRef<Path>>(&self, path: P) -> Result<(), SaveImageTextureError> {
+        let dds = self.dds_surface()?.to_dds()?;
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        dds.write(&mut writer)?;
+        Ok(())
+    }
+
+    /// A surface with channels in the order expected by image_dds.
+    fn dds_surface(&self) -> Result<Surface<Cow<'_, [u8]>>, SaveImageTextureError> {
+        let (image_format, data) = match self.image_format {
+            NutFormat::B5G6R5Unorm => (
+                ImageFormat::Rgba8Unorm,
+                Cow::Owned(decode_b5g6r5(&self.image_data)),
+            ),
+            NutFormat::Rgb5A1Unorm => {
+                let mut data = self.image_data.clone();
+                swap_red_blue_bgr5a1(&mut data);
+                (ImageFormat::Bgr5A1Unorm, Cow::Owned(data))
+            }
+            format => (
+                format.try_into()?,
+                Cow::Borrowed(self.image_data.as_slice()),
+            ),
+        };
+        Ok(Surface {
+            width: self.width,
+            height: self.height,
+            depth: 1,
+            layers: self.layers,
+            mipmaps: self.mipmap_count,
+            image_format,
+            data,
+        })
+    }
+
+    fn decode_layers(
+        &self,
+        mipmap: u32,
+        layers: &[u32],
+    ) -> Result<image::DynamicImage, SaveImageTextureError> {
+        if mipmap >= self.mipmap_count {
+            return Err(SaveImageTextureError::MipmapOutOfRange {
+                mipmap,
+                mipmap_count: self.mipmap_count,
+            });
+        }
+
+        let width = mip_dimension(self.width, mipmap);
+        let height = mip_dimension(self.height, mipmap) * layers.len() as u32;
+
+        let surface = self.dds_surface()?;
+        if self.image_format == NutFormat::R32Float {
+            let data = surface.decode_rgbaf32()?.data;
+            let data = self.layer_data(&data, mipmap, layers)?;
+            image::Rgba32FImage::from_raw(width, height, data)
+                .map(Into::into)
+                .ok_or(SaveImageTextureError::ImageDataSize)
+        } else {
+            let data = surface.decode_rgba8()?.data;
+            let data = self.layer_data(&data, mipmap, layers)?;
+            image::RgbaImage::from_raw(width, height, data)
+                .map(Into::into)
+                .ok_or(SaveImageTextureError::ImageDataSize)
+        }
+    }
+
+    /// Select the RGBA values for `mipmap` from each of the `layers` in decoded `data`.
+    fn layer_data<T: Copy>(
+        &self,
+        data: &[T],
+        mipmap: u32,
+        layers: &[u32],
+    ) -> Result<Vec<T>, SaveImageTextureError> {
+        // Decoded data stores all mipmaps for each layer in order.
+        let mip_sizes: Vec<_> = (0..self.mipmap_count)
+            .map(|m| {
+                mip_dimension(self.width, m) as usize * mip_dimension(self.height, m) as usize * 4
+            })
+            .collect();
+        let layer_size: usize = mip_sizes.iter().sum();
+        let mip_offset: usize = mip_sizes[..mipmap as usize].iter().sum();
+        let mip_size = mip_sizes[mipmap as usize];
+
+        let mut layer_data = Vec::new();
+        for layer in layers {
+            let start = *layer as usize * layer_size + mip_offset;
+            let values = data
+                .get(start..start + mip_size)
+                .ok_or(SaveImageTextureError::ImageDataSize)?;
+            layer_data.extend_from_slice(values);
+        }
+        Ok(layer_data)
+    }
+}
+
+fn save_png(image: &image::DynamicImage, path: &Path) -> Result<(), SaveImageTextureError> {
+    match image {
+        image::DynamicImage::ImageRgba32F(_) => image.to_rgba16().save(path)?,
+        _ => image.save(path)?,
+    }
+    Ok(())
+}
+
+/// Decode [NutFormat::B5G6R5Unorm] data to RGBA8.
+pub(crate) fn decode_b5g6r5(data: &[u8]) -> Vec<u8> {
+    // TODO: Check the channel order against in game textures using sm4sh_test --b5g6r5-png.
+    // Least significant bit -> BBBBBGGGGGGRRRRR -> most significant bit.
+    data.chunks_exact(2)
+        .flat_map(|c| {
+            let value = u16::from_le_bytes([c[0], c[1]]);
+            let b = value & 0x1F;
+            let g = (value >> 5) & 0x3F;
+            let r = (value >> 11) & 0x1F;
+            [
+                (r as u32 * 255 / 31) as u8,
+                (g as u32 * 255 / 63) as u8,
+                (b as u32 * 255 / 31) as u8,
+                255,
+            ]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkerboard(width: u32, height: u32) -> image::RgbaImage {
+        image::RgbaImage::from_fn(width, height, |x, y| {
+            if (x + y) % 2 == 0 {
+                image::Rgba([255, 255, 255, 255])
+            } else {
+                image::Rgba([0, 0, 0, 255])
+            }
+        })
+    }
+
+    #[test]
+    fn from_rgba8_image_mipmaps() {
+        let texture = ImageTexture::from_rgba8_image(
+            0x40000001,
+            &checkerboard(8, 4),
+            NutFormat::Rgba8Unorm,
+            &ImageTextureOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(0x40000001, texture.hash_id);
+        assert_eq!((8, 4, 1), (texture.width, texture.height, texture.layers));
+        assert_eq!(4, texture.mipmap_count);
+        assert_eq!((32 + 8 + 2 + 1) * 4, texture.image_data.len());
+    }
+
+    #[test]
+    fn from_rgba8_image_cube_map_strip() {
+        let texture = ImageTexture::from_rgba8_image(
+            0x40000001,
+            &checkerboard(4, 24),
+            NutFormat::Rgba8Unorm,
+            &ImageTextureOptions {
+                mipmaps: false,
+                cube_map: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!((4, 4, 6), (texture.width, texture.height, texture.layers));
+        assert_eq!(1, texture.mipmap_count);
+        assert_eq!(4 * 4 * 4 * 6, texture.image_data.len());
+    }
+
+    #[test]
+    fn from_rgba8_image_bc1() {
+        let texture = ImageTexture::from_rgba8_image(
+            0x40000001,
+            &checkerboard(8, 8),
+            NutFormat::BC1Unorm,
+            &ImageTextureOptions {
+                mipmaps: false,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(NutFormat::BC1Unorm, texture.image_format);
+        assert_eq!(4 * 8, texture.image_data.len());
+    }
+
+    #[test]
+    fn from_rgba8_image_invalid_cube_map_strip() {
+        let result = ImageTexture::from_rgba8_image(
+            0x40000001,
+            &checkerboard(4, 16),
+            NutFormat::Rgba8Unorm,
+            &ImageTextureOptions {
+                cube_map: true,
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            result,
+            Err(LoadImageTextureError::CubeMapStrip {
+                width: 4,
+                height: 16
+            })
+        ));
+    }
+
+    fn rgba8_surface(layers: u32, mipmaps: u32) -> Surface<Vec<u8>> {
+        let size: u32 = (0..mipmaps).map(|m| mip_dimension(4, m).pow(2) * 4).sum();
+        Surface {
+            width: 4,
+            height: 4,
+            depth: 1,
+            layers,
+            mipmaps,
+            image_format: ImageFormat::Rgba8Unorm,
+            data: vec![0; (size * layers) as usize],
+        }
+    }
+
+    #[test]
+    fn from_dds_surface_cube_map_mipmaps() {
+        let texture = ImageTexture::from_dds_surface(
+            0x40000001,
+            rgba8_surface(6, 3),
+            NutFormat::Rgba8Unorm,
+            &ImageTextureOptions {
+                mipmaps: true,
+                cube_map: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!((4, 4, 6), (texture.width, texture.height, texture.layers));
+        assert_eq!(3, texture.mipmap_count);
+    }
+
+    #[test]
+    fn from_dds_surface_invalid_options() {
+        let result = ImageTexture::from_dds_surface(
+            0x40000001,
+            rgba8_surface(1, 1),
+            NutFormat::Rgba8Unorm,
+            &ImageTextureOptions {
+                mipmaps: true,
+                cube_map: true,
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            result,
+            Err(LoadImageTextureError::DdsOptions {
+                layers: 1,
+                mipmaps: 1,
+                mipmaps_option: true,
+                cube_map_option: true
+            })
+        ));
+    }
+
+    fn rgba8_texture(width: u32, height: u32, layers: u32, mipmap_count: u32) -> ImageTexture {
+        let size: u32 = (0..mipmap_count)
+            .map(|m| mip_dimension(width, m) * mip_dimension(height, m) * 4)
+            .sum();
+        ImageTexture {
+            hash_id: 0x40000001,
+            width,
+            height,
+            mipmap_count,
+            layers,
+            image_format: NutFormat::Rgba8Unorm,
+            // Fill each layer with its index to check the layer order.
+            image_data: (0..layers)
+                .flat_map(|l| std::iter::repeat_n(l as u8, size as usize))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn to_image_mipmap() {
+        let texture = rgba8_texture(8, 4, 1, 2);
+        let image = texture.to_image(1).unwrap();
+        assert_eq!((4, 2), (image.width(), image.height()));
+        assert!(matches!(image, image::DynamicImage::ImageRgba8(_)));
+    }
+
+    #[test]
+    fn to_image_mipmap_out_of_range() {
+        let texture = rgba8_texture(8, 4, 1, 2);
+        assert!(matches!(
+            texture.to_image(2),
+            Err(SaveImageTextureError::MipmapOutOfRange {
+                mipmap: 2,
+                mipmap_count: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn to_image_cube_map_strip() {
+        let texture = rgba8_texture(4, 4, 6, 3);
+        let image = texture.to_image(1).unwrap().into_rgba8();
+        assert_eq!((2, 12), image.dimensions());
+        for face in 0..6 {
+            assert_eq!(face as u8, image.get_pixel(0, face * 2)[0]);
+        }
+    }
+
+    #[test]
+    fn to_cube_faces_mipmap() {
+        let texture = rgba8_texture(4, 4, 6, 3);
+        let faces = texture.to_cube_faces(2).unwrap();
+        assert_eq!(6, faces.len());
+        for (i, face) in faces.iter().enumerate() {
+            assert_eq!((1, 1), (face.width(), face.height()));
+            assert_eq!(i as u8, face.to_rgba8().get_pixel(0, 0)[0]);
+        }
+    }
+
+    #[test]
+    fn to_image_b5g6r5() {
+        let texture = ImageTexture {
+            hash_id: 0x40000001,
+            width: 1,
+            height: 1,
+            mipmap_count: 1,
+            layers: 1,
+            image_format: NutFormat::B5G6R5Unorm,
+            image_data: 0b11111_000000_00000u16.to_le_bytes().to_vec(),
+        };
+        let image = texture.to_image(0).unwrap().into_rgba8();
+        assert_eq!([255, 0, 0, 255], image.get_pixel(0, 0).0);
+    }
+
+    #[test]
+    fn to_image_r32_float() {
+        let texture = ImageTexture {
+            hash_id: 0x40000001,
+            width: 1,
+            height: 1,
+            mipmap_count: 1,
+            layers: 1,
+            image_format: NutFormat::R32Float,
+            image_data: 0.5f32.to_le_bytes().to_vec(),
+        };
+        let image = texture.to_image(0).unwrap();
+        assert_eq!(0.5, image.into_rgba32f().get_pixel(0, 0)[0]);
+    }
+}
@@ -0,0 +1,387 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use sm4sh_lib::nut::Nut;
+use thiserror::Error;
+
+use crate::{
+    CreateImageTextureError, CreateNutError, ImageTexture, NudModel, create_nut, nut_textures,
+};
+
+/// The hashes for fighter textures like `0x40XXYYZZ`
+/// for fighter `XX`, costume slot `YY`, and texture `ZZ`.
+///
+/// This layout is a modding convention based on the hashes in fighter NUT files
+/// and is not confirmed to be how the game assigns hashes.
+/// Using a unique range for each slot prevents collisions when loading multiple costumes.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TextureHashRange {
+    pub fighter_id: u8,
+    pub slot: u8,
+}
+
+impl TextureHashRange {
+    /// The hash for the texture at `index` in this range.
+    pub fn hash(&self, index: u8) -> u32 {
+        0x40000000 | ((self.fighter_id as u32) << 16) | ((self.slot as u32) << 8) | index as u32
+    }
+
+    pub fn contains(&self, hash: u32) -> bool {
+        hash & 0xFFFFFF00 == self.hash(0)
+    }
+
+    /// The first hash in this range not in `used` or `None` if all hashes are used.
+    pub fn allocate(&self, used: &BTreeSet<u32>) -> Option<u32> {
+        (0..=u8::MAX)
+            .map(|i| self.hash(i))
+            .find(|h| !used.contains(h))
+    }
+}
+
+/// Returns `true` if `hash` is reserved for global textures provided by the game like `0x10000001`.
+pub fn is_global_texture_hash(hash: u32) -> bool {
+    hash & 0xFF000000 == 0x10000000
+}
+
+#[derive(Debug, Error)]
+pub enum TextureHashError {
+    #[error("no unused hashes remaining in {0:?}")]
+    RangeFull(TextureHashRange),
+
+    #[error("texture {0:08X} has the same hash as a different texture")]
+    Collision(u32),
+}
+
+#[derive(Debug, Error)]
+pub enum MergeNutError {
+    #[error("error converting NUT textures")]
+    Texture(#[from] CreateImageTextureError),
+
+    #[error("error creating NUT")]
+    Nut(#[from] CreateNutError),
+
+    #[error("error merging textures")]
+    Hash(#[from] TextureHashError),
+}
+
+impl NudModel {
+    /// The hashes for all textures referenced by materials.
+    pub fn referenced_texture_hashes(&self) -> BTreeSet<u32> {
+        self.groups
+            .iter()
+            .flat_map(|g| &g.meshes)
+            .flat_map(|m| [&m.material1, &m.material2, &m.material3, &m.material4])
+            .flatten()
+            .flat_map(|m| &m.textures)
+            .map(|t| t.hash)
+            .collect()
+    }
+
+    /// Replace the hashes for textures and material references using `remap` from old hash to new hash.
+    ///
+    /// Hashes not in `remap` are unchanged.
+    pub fn remap_texture_hashes(&mut self, remap: &BTreeMap<u32, u32>) {
+        for texture in &mut self.textures {
+            if let Some(hash) = remap.get(&texture.hash_id) {
+                texture.hash_id = *hash;
+            }
+        }
+
+        let materials = self
+            .groups
+            .iter_mut()
+            .flat_map(|g| &mut g.meshes)
+            .flat_map(|m| {
+                [
+                    &mut m.material1,
+                    &mut m.material2,
+                    &mut m.material3,
+                    &mut m.material4,
+                ]
+            })
+            .flatten();
+        for material in materials {
+            for texture in &mut material.textures {
+                if let Some(hash) = remap.get(&texture.hash) {
+                    texture.hash = *hash;
+                }
+            }
+        }
+    }
+
+    /// Move all textures not already in `range` to unused hashes in `range`
+    /// and update material references.
+    ///
+    /// Returns the mapping from old hash to new hash.
+    /// Global textures and material references to global textures are not changed.
+    pub fn allocate_texture_hashes(
+        &mut self,
+        range: TextureHashRange,
+    ) -> Result<BTreeMap<u32, u32>, TextureHashError> {
+        let mut used: BTreeSet<_> = self.textures.iter().map(|t| t.hash_id).collect();
+
+        let mut remap = BTreeMap::new();
+        for texture in &self.textures {
+            if !range.contains(texture.hash_id)
+                && !is_global_texture_hash(texture.hash_id)
+                && !remap.contains_key(&texture.hash_id)
+            {
+                let hash = range
+                    .allocate(&used)
+                    .ok_or(TextureHashError::RangeFull(range))?;
+                used.insert(hash);
+                remap.insert(texture.hash_id, hash);
+            }
+        }
+
+        self.remap_texture_hashes(&remap);
+        Ok(remap)
+    }
+
+    /// Remove textures that are not referenced by any material.
+    ///
+    /// Returns the hashes of the removed textures.
+    pub fn remove_unused_textures(&mut self) -> Vec<u32> {
+        let referenced = self.referenced_texture_hashes();
+        let mut removed = Vec::new();
+        self.textures.retain(|t| {
+            let is_used = referenced.contains(&t.hash_id);
+            if !is_used {
+                removed.push(t.hash_id);
+            }
+            is_used
+        });
+        removed
+    }
+
+    /// Remove textures with identical image data and update material references to the first texture.
+    ///
+    /// Returns the mapping from removed hash to remaining hash.
+    pub fn remove_duplicate_textures(&mut self) -> BTreeMap<u32, u32> {
+        let mut remap = BTreeMap::new();
+        let mut unique: Vec<ImageTexture> = Vec::new();
+        for texture in std::mem::take(&mut self.textures) {
+            match unique.iter().find(|t| is_same_image(t, &texture)) {
+                Some(existing) => {
+                    if existing.hash_id != texture.hash_id {
+                        remap.insert(texture.hash_id, existing.hash_id);
+                    }
+                }
+                None => unique.push(texture),
+            }
+        }
+        self.textures = unique;
+
+        self.remap_texture_hashes(&remap);
+        remap
+    }
+}
+
+fn is_same_image(a: &ImageTexture, b: &ImageTexture) -> bool {
+    a.width == b.width
+        && a.height == b.height
+        && a.mipmap_count == b.mipmap_count
+        && a.layers == b.layers
+        && a.image_format == b.image_format
+        && a.image_data == b.image_data
+}
+
+/// Combine `textures` into a single list in order.
+///
+/// Identical textures with the same hash are only included once.
+/// Different textures with the same hash return an error.
+pub fn merge_textures<'a>(
+    textures: impl IntoIterator<Item = &'a [ImageTexture]>,
+) -> Result<Vec<ImageTexture>, TextureHashError> {
+    let mut merged: Vec<ImageTexture> = Vec::new();
+    for texture in textures.into_iter().flatten() {
+        match merged.iter().find(|t| t.hash_id == texture.hash_id) {
+            Some(existing) => {
+                if !is_same_image(existing, texture) {
+                    return Err(TextureHashError::Collision(texture.hash_id));
+                }
+            }
+            None => merged.push(texture.clone()),
+        }
+    }
+    Ok(merged)
+}
+
+/// Combine the textures from `nuts` into a single NUT using the same rules as [merge_textures].
+pub fn merge_nuts(nuts: &[Nut]) -> Result<Nut, MergeNutError> {
+    let textures = nuts
+        .iter()
+        .map(nut_textures)
+        .collect::<Result<Vec<_>, _>>()?;
+    let merged = merge_textures(textures.iter().map(|t| t.as_slice()))?;
+    Ok(create_nut(&merged)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use glam::Vec4;
+
+    use crate::{
+        MagFilter, MapMode, MinFilter, MipDetail, NudMaterial, NudMesh, NudMeshGroup, NudTexture,
+        NutFormat, PrimitiveType, WrapMode,
+        vertex::{Normals, Uvs, Vertices},
+    };
+
+    fn texture(hash_id: u32, value: u8) -> ImageTexture {
+        ImageTexture {
+            hash_id,
+            width: 4,
+            height: 4,
+            mipmap_count: 1,
+            layers: 1,
+            image_format: NutFormat::Rgba8Unorm,
+            image_data: vec![value; 4 * 4 * 4],
+        }
+    }
+
+    fn material_texture(hash: u32) -> NudTexture {
+        NudTexture {
+            hash,
+            map_mode: MapMode::TexCoord,
+            wrap_mode_s: WrapMode::Repeat,
+            wrap_mode_t: WrapMode::Repeat,
+            min_filter: MinFilter::Linear,
+            mag_filter: MagFilter::Linear,
+            mip_detail: MipDetail::OneMipLevelAnisotropicOff,
+        }
+    }
+
+    fn model(textures: Vec<ImageTexture>, hashes: &[u32]) -> NudModel {
+        NudModel {
+            groups: vec![NudMeshGroup {
+                name: "a".to_string(),
+                meshes: vec![NudMesh {
+                    vertices: Vertices {
+                        positions: Vec::new(),
+                        normals: Normals::None(Vec::new()),
+                        bones: None,
+                        colors: None,
+                        uvs: Uvs::Float16(Vec::new()),
+                    },
+                    vertex_indices: Vec::new(),
+                    primitive_type: PrimitiveType::TriangleList,
+                    material1: Some(NudMaterial {
+                        shader_id: 0,
+                        src_factor: crate::SrcFactor::One,
+                        dst_factor: crate::DstFactor::Zero,
+                        alpha_func: crate::AlphaFunc::Disabled,
+                        alpha_test_ref: 0,
+                        cull_mode: crate::CullMode::Disabled,
+                        textures: hashes.iter().copied().map(material_texture).collect(),
+                        properties: Vec::new(),
+                    }),
+                    material2: None,
+                    material3: None,
+                    material4: None,
+                }],
+                sort_bias: 0.0,
+                bounding_sphere: Vec4::ZERO,
+                parent_bone_index: None,
+            }],
+            textures,
+            bounding_sphere: Vec4::ZERO,
+            skeleton: None,
+        }
+    }
+
+    fn material_hashes(model: &NudModel) -> Vec<u32> {
+        model.groups[0].meshes[0]
+            .material1
+            .as_ref()
+            .unwrap()
+            .textures
+            .iter()
+            .map(|t| t.hash)
+            .collect()
+    }
+
+    #[test]
+    fn texture_hash_range() {
+        let range = TextureHashRange {
+            fighter_id: 0x1B,
+            slot: 0x02,
+        };
+        assert_eq!(0x401B0203, range.hash(3));
+        assert!(range.contains(0x401B02FF));
+        assert!(!range.contains(0x401B0300));
+        assert_eq!(
+            Some(0x401B0201),
+            range.allocate(&[0x401B0200].into_iter().collect())
+        );
+    }
+
+    #[test]
+    fn global_texture_hashes() {
+        assert!(is_global_texture_hash(0x10000001));
+        assert!(is_global_texture_hash(0x10104FFF));
+        assert!(!is_global_texture_hash(0x40010000));
+    }
+
+    #[test]
+    fn allocate_texture_hashes_slot() {
+        let range = TextureHashRange {
+            fighter_id: 1,
+            slot: 2,
+        };
+        let mut model = model(
+            vec![texture(0x40010000, 0), texture(0x40010201, 1)],
+            &[0x40010000, 0x40010201, 0x10080000],
+        );
+
+        let remap = model.allocate_texture_hashes(range).unwrap();
+        assert_eq!(BTreeMap::from([(0x40010000, 0x40010200)]), remap);
+        assert_eq!(
+            vec![0x40010200, 0x40010201, 0x10080000],
+            material_hashes(&model)
+        );
+        assert_eq!(0x40010200, model.textures[0].hash_id);
+    }
+
+    #[test]
+    fn remove_unused_duplicate_textures() {
+        let mut model = model(
+            vec![
+                texture(0x40000000, 0),
+                texture(0x40000001, 0),
+                texture(0x40000002, 1),
+            ],
+            &[0x40000000, 0x40000001],
+        );
+
+        assert_eq!(
+            BTreeMap::from([(0x40000001, 0x40000000)]),
+            model.remove_duplicate_textures()
+        );
+        assert_eq!(vec![0x40000000, 0x40000000], material_hashes(&model));
+
+        assert_eq!(vec![0x40000002], model.remove_unused_textures());
+        assert_eq!(1, model.textures.len());
+    }
+
+    #[test]
+    fn merge_textures_dedupe() {
+        let a = [texture(0x40000000, 0), texture(0x40000001, 1)];
+        let b = [texture(0x40000001, 1), texture(0x40000002, 2)];
+        let merged = merge_textures([a.as_slice(), b.as_slice()]).unwrap();
+        assert_eq!(
+            vec![0x40000000, 0x40000001, 0x40000002],
+            merged.iter().map(|t| t.hash_id).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn merge_textures_collision() {
+        let a = [texture(0x40000000, 0)];
+        let b = [texture(0x40000000, 1)];
+        assert!(matches!(
+            merge_textures([a.as_slice(), b.as_slice()]),
+            Err(TextureHashError::Collision(0x40000000))
+        ));
+    }
+}
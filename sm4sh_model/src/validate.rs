@@ -0,0 +1,644 @@
+use std::collections::BTreeSet;
+
+use thiserror::Error;
+
+use crate::{
+    NudMesh, NudModel, PrimitiveType, database::ShaderDatabase, material::PropertyError,
+    texture::is_global_texture_hash, vertex::VertexCountError,
+};
+
+/// The maximum difference from `1.0` for the sum of vertex skin weights.
+/// This allows for the rounding of [BoneElementType::Byte](crate::vertex::BoneElementType::Byte) weights.
+pub const WEIGHT_SUM_TOLERANCE: f32 = 0.01;
+
+/// A potential problem with a [NudModel] found by [NudModel::validate].
+///
+/// Most of these problems cause crashes or rendering errors in game.
+/// Mesh diagnostics use the index of the group in [NudModel::groups]
+/// and the index of the mesh in [NudMeshGroup::meshes](crate::NudMeshGroup::meshes).
+/// Vertex diagnostics only report the first affected vertex and the total number of affected vertices.
+#[derive(Debug, PartialEq, Clone, Error)]
+pub enum Diagnostic {
+    #[error("group {group_index} has a parent bone and skin weights")]
+    ParentBoneWithSkinning { group_index: usize },
+
+    #[error(
+        "group {group_index} has parent bone {bone_index} but the skeleton has {bone_count} bones"
+    )]
+    ParentBoneOutOfRange {
+        group_index: usize,
+        bone_index: usize,
+        bone_count: usize,
+    },
+
+    #[error("group {group_index} uses bones but the model has no skeleton")]
+    MissingSkeleton { group_index: usize },
+
+    #[error(
+        "mesh {mesh_index} in group {group_index} has {count} vertices with bone indices beyond the skeleton length {bone_count} starting at vertex {vertex_index} with bone {bone_index}"
+    )]
+    BoneIndexOutOfRange {
+        group_index: usize,
+        mesh_index: usize,
+        vertex_index: usize,
+        bone_index: u32,
+        bone_count: usize,
+        count: usize,
+    },
+
+    #[error(
+        "mesh {mesh_index} in group {group_index} has {count} vertices with skin weights that do not sum to 1 starting at vertex {vertex_index} with sum {sum}"
+    )]
+    WeightSum {
+        group_index: usize,
+        mesh_index: usize,
+        vertex_index: usize,
+        sum: f32,
+        count: usize,
+    },
+
+    #[error("mesh {mesh_index} in group {group_index}: {error}")]
+    VertexCount {
+        group_index: usize,
+        mesh_index: usize,
+        error: VertexCountError,
+    },
+
+    #[error(
+        "mesh {mesh_index} in group {group_index} has {count} vertex indices out of range for {vertex_count} vertices starting with index {index}"
+    )]
+    VertexIndexOutOfRange {
+        group_index: usize,
+        mesh_index: usize,
+        index: u16,
+        vertex_count: usize,
+        count: usize,
+    },
+
+    #[error(
+        "mesh {mesh_index} in group {group_index} has {count} vertices with non finite {attribute:?} values starting at vertex {vertex_index}"
+    )]
+    NonFiniteVertex {
+        group_index: usize,
+        mesh_index: usize,
+        attribute: VertexAttribute,
+        vertex_index: usize,
+        count: usize,
+    },
+
+    #[error(
+        "material {material_index} for mesh {mesh_index} in group {group_index} has non finite values for property {name:?}"
+    )]
+    NonFiniteProperty {
+        group_index: usize,
+        mesh_index: usize,
+        material_index: usize,
+        name: String,
+    },
+
+    #[error(
+        "material {material_index} for mesh {mesh_index} in group {group_index} references texture {hash:08X} not in the model textures"
+    )]
+    MissingTexture {
+        group_index: usize,
+        mesh_index: usize,
+        material_index: usize,
+        texture_index: usize,
+        hash: u32,
+    },
+
+    #[error(
+        "material {material_index} for mesh {mesh_index} in group {group_index} has shader {shader_id:08X} not in the shader database"
+    )]
+    MissingShader {
+        group_index: usize,
+        mesh_index: usize,
+        material_index: usize,
+        shader_id: u32,
+    },
+
+    #[error(
+        "material {material_index} for mesh {mesh_index} in group {group_index} has {texture_count} textures but shader {shader_id:08X} has {sampler_count} samplers"
+    )]
+    TextureCount {
+        group_index: usize,
+        mesh_index: usize,
+        material_index: usize,
+        shader_id: u32,
+        texture_count: usize,
+        sampler_count: usize,
+    },
+
+    #[error("material {material_index} for mesh {mesh_index} in group {group_index}: {error}")]
+    Property {
+        group_index: usize,
+        mesh_index: usize,
+        material_index: usize,
+        error: PropertyError,
+    },
+
+    #[error("bone {bone_index} has non finite transform values")]
+    NonFiniteBone { bone_index: usize },
+}
+
+/// A vertex attribute in [Vertices](crate::vertex::Vertices).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum VertexAttribute {
+    Position,
+    Normal,
+    BoneWeights,
+    Color,
+    /// The UV layer at the given index.
+    Uv(usize),
+}
+
+impl NudModel {
+    /// Check the model for problems before exporting.
+    ///
+    /// Material shaders and textures are only checked against the shader samplers if `database` is not `None`.
+    /// Global textures provided by the game like `0x10080000` are not reported as missing.
+    /// See [is_global_texture_hash].
+    pub fn validate(&self, database: Option<&ShaderDatabase>) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        let bone_count = self.skeleton.as_ref().map(|s| s.bones.len());
+
+        let texture_hashes: BTreeSet<_> = self.textures.iter().map(|t| t.hash_id).collect();
+
+        if let Some(skeleton) = &self.skeleton {
+            for (i, bone) in skeleton.bones.iter().enumerate() {
+                if !(bone.translation.is_finite()
+                    && bone.rotation.is_finite()
+                    && bone.scale.is_finite())
+                {
+                    diagnostics.push(Diagnostic::NonFiniteBone { bone_index: i });
+                }
+            }
+        }
+
+        for (group_index, group) in self.groups.iter().enumerate() {
+            let has_weights = group.meshes.iter().any(|m| m.vertices.bones.is_some());
+
+            if group.parent_bone_index.is_some() && has_weights {
+                diagnostics.push(Diagnostic::ParentBoneWithSkinning { group_index });
+            }
+
+            match (group.parent_bone_index, bone_count) {
+                (Some(bone_index), Some(bone_count)) if bone_index >= bone_count => {
+                    diagnostics.push(Diagnostic::ParentBoneOutOfRange {
+                        group_index,
+                        bone_index,
+                        bone_count,
+                    });
+                }
+                (Some(_), None) => diagnostics.push(Diagnostic::MissingSkeleton { group_index }),
+                (None, None) if has_weights => {
+                    diagnostics.push(Diagnostic::MissingSkeleton { group_index })
+                }
+                _ => (),
+            }
+
+            for (mesh_index, mesh) in group.meshes.iter().enumerate() {
+                let mut mesh_diagnostics = MeshDiagnostics {
+                    group_index,
+                    mesh_index,
+                    diagnostics: &mut diagnostics,
+                };
+                mesh_diagnostics.validate_vertices(mesh, bone_count);
+                mesh_diagnostics.validate_materials(mesh, &texture_hashes, database);
+            }
+        }
+
+        diagnostics
+    }
+}
+
+struct MeshDiagnostics<'a> {
+    group_index: usize,
+    mesh_index: usize,
+    diagnostics: &'a mut Vec<Diagnostic>,
+}
+
+impl MeshDiagnostics<'_> {
+    fn validate_vertices(&mut self, mesh: &NudMesh, bone_count: Option<usize>) {
+        let vertices = &mesh.vertices;
+        let vertex_count = vertices.positions.len();
+
+        // Use the same rules as NudMesh::vertex_count.
+        let is_restart =
+            |i: u16| mesh.primitive_type == PrimitiveType::TriangleStrip && i == u16::MAX;
+
+        let invalid_indices: Vec<_> = mesh
+            .vertex_indices
+            .iter()
+            .filter(|i| !is_restart(**i) && **i as usize >= vertex_count)
+            .collect();
+        if let Some(index) = invalid_indices.first() {
+            self.diagnostics.push(Diagnostic::VertexIndexOutOfRange {
+                group_index: self.group_index,
+                mesh_index: self.mesh_index,
+                index: **index,
+                vertex_count,
+                count: invalid_indices.len(),
+            });
+        }
+
+        self.check_non_finite(
+            VertexAttribute::Position,
+            vertices.positions.iter().map(|p| p.is_finite()),
+        );
+
+        if let Err(error) = vertices.vertex_count() {
+            self.diagnostics.push(Diagnostic::VertexCount {
+                group_index: self.group_index,
+                mesh_index: self.mesh_index,
+                error,
+            });
+        }
+
+        if let Some(normals) = vertices.normals.normals() {
+            self.check_non_finite(
+                VertexAttribute::Normal,
+                normals.iter().map(|n| n.is_finite()),
+            );
+        }
+
+        if let Some(bones) = &vertices.bones {
+            self.check_non_finite(
+                VertexAttribute::BoneWeights,
+                bones.weights.iter().map(|w| w.is_finite()),
+            );
+
+            // Only check influences that actually affect the vertex.
+            if let Some(bone_count) = bone_count {
+                let invalid_bones: Vec<_> = bones
+                    .bone_indices
+                    .iter()
+                    .zip(&bones.weights)
+                    .enumerate()
+                    .filter_map(|(i, (indices, weights))| {
+                        indices
+                            .iter()
+                            .zip(weights.to_array())
+                            .find(|(b, w)| *w != 0.0 && **b as usize >= bone_count)
+                            .map(|(b, _)| (i, *b))
+                    })
+                    .collect();
+                if let Some((vertex_index, bone_index)) = invalid_bones.first() {
+                    self.diagnostics.push(Diagnostic::BoneIndexOutOfRange {
+                        group_index: self.group_index,
+                        mesh_index: self.mesh_index,
+                        vertex_index: *vertex_index,
+                        bone_index: *bone_index,
+                        bone_count,
+                        count: invalid_bones.len(),
+                    });
+                }
+            }
+
+            let invalid_sums: Vec<_> = bones
+                .weights
+                .iter()
+                .enumerate()
+                .map(|(i, w)| (i, w.element_sum()))
+                .filter(|(_, sum)| (sum - 1.0).abs() > WEIGHT_SUM_TOLERANCE)
+                .collect();
+            if let Some((vertex_index, sum)) = invalid_sums.first() {
+                self.diagnostics.push(Diagnostic::WeightSum {
+                    group_index: self.group_index,
+                    mesh_index: self.mesh_index,
+                    vertex_index: *vertex_index,
+                    sum: *sum,
+                    count: invalid_sums.len(),
+                });
+            }
+        }
+
+        if let Some(colors) = &vertices.colors {
+            self.check_non_finite(
+                VertexAttribute::Color,
+                colors.colors.iter().map(|c| c.is_finite()),
+            );
+        }
+
+        for (i, uvs) in vertices.uvs.uvs().iter().enumerate() {
+            self.check_non_finite(VertexAttribute::Uv(i), uvs.iter().map(|uv| uv.is_finite()));
+        }
+    }
+
+    fn validate_materials(
+        &mut self,
+        mesh: &NudMesh,
+        texture_hashes: &BTreeSet<u32>,
+        database: Option<&ShaderDatabase>,
+    ) {
+        let materials = [
+            &mesh.material1,
+            &mesh.material2,
+            &mesh.material3,
+            &mesh.material4,
+        ];
+        for (material_index, material) in materials.into_iter().enumerate() {
+            let Some(material) = material else {
+                continue;
+            };
+
+            for (texture_index, texture) in material.textures.iter().enumerate() {
+                if !texture_hashes.contains(&texture.hash) && !is_global_texture_hash(texture.hash)
+                {
+                    self.diagnostics.push(Diagnostic::MissingTexture {
+                        group_index: self.group_index,
+                        mesh_index: self.mesh_index,
+                        material_index,
+                        texture_index,
+                        hash: texture.hash,
+                    });
+                }
+            }
+
+            for error in material.property_errors() {
+                self.diagnostics.push(Diagnostic::Property {
+                    group_index: self.group_index,
+                    mesh_index: self.mesh_index,
+                    material_index,
+                    error,
+                });
+            }
+
+            for property in &material.properties {
+                if property.values.iter().any(|v| !v.is_finite()) {
+                    self.diagnostics.push(Diagnostic::NonFiniteProperty {
+                        group_index: self.group_index,
+                        mesh_index: self.mesh_index,
+                        material_index,
+                        name: property.name.clone(),
+                    });
+                }
+            }
+
+            if let Some(database) = database {
+                match database.get_shader(material.shader_id) {
+                    Some(program) => {
+                        if program.samplers.len() != material.textures.len() {
+                            self.diagnostics.push(Diagnostic::TextureCount {
+                                group_index: self.group_index,
+                                mesh_index: self.mesh_index,
+                                material_index,
+                                shader_id: material.shader_id,
+                                texture_count: material.textures.len(),
+                                sampler_count: program.samplers.len(),
+                            });
+                        }
+                    }
+                    None => self.diagnostics.push(Diagnostic::MissingShader {
+                        group_index: self.group_index,
+                        mesh_index: self.mesh_index,
+                        material_index,
+                        shader_id: material.shader_id,
+                    }),
+                }
+            }
+        }
+    }
+
+    fn check_non_finite(
+        &mut self,
+        attribute: VertexAttribute,
+        is_finite: impl Iterator<Item = bool>,
+    ) {
+        let invalid: Vec<_> = is_finite
+            .enumerate()
+            .filter_map(|(i, finite)| (!finite).then_some(i))
+            .collect();
+        if let Some(vertex_index) = invalid.first() {
+            self.diagnostics.push(Diagnostic::NonFiniteVertex {
+                group_index: self.group_index,
+                mesh_index: self.mesh_index,
+                attribute,
+                vertex_index: *vertex_index,
+                count: invalid.len(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use glam::{Vec3, Vec4, vec4};
+
+    use crate::{
+        AlphaFunc, BoneType, CullMode, DstFactor, MagFilter, MapMode, MinFilter, MipDetail,
+        NudMaterial, NudMeshGroup, NudProperty, NudTexture, PrimitiveType, SrcFactor, VbnBone,
+        VbnSkeleton, WrapMode,
+        vertex::{BoneElementType, Bones, Normals, Uvs, Vertices},
+    };
+
+    fn model() -> NudModel {
+        NudModel {
+            groups: vec![NudMeshGroup {
+                name: "a".to_string(),
+                meshes: vec![NudMesh {
+                    vertices: Vertices {
+                        positions: vec![Vec3::ZERO; 3],
+                        normals: Normals::None(vec![0.0; 3]),
+                        bones: Some(Bones {
+                            bone_indices: vec![[0, 0, 0, 0]; 3],
+                            weights: vec![vec4(1.0, 0.0, 0.0, 0.0); 3],
+                            element_type: BoneElementType::Byte,
+                        }),
+                        colors: None,
+                        uvs: Uvs::Float16(Vec::new()),
+                    },
+                    vertex_indices: vec![0, 1, 2],
+                    primitive_type: PrimitiveType::TriangleList,
+                    material1: Some(NudMaterial {
+                        shader_id: 0,
+                        src_factor: SrcFactor::One,
+                        dst_factor: DstFactor::Zero,
+                        alpha_func: AlphaFunc::Disabled,
+                        alpha_test_ref: 0,
+                        cull_mode: CullMode::Disabled,
+                        textures: vec![NudTexture {
+                            hash: 0x10080000,
+                            map_mode: MapMode::TexCoord,
+                            wrap_mode_s: WrapMode::Repeat,
+                            wrap_mode_t: WrapMode::Repeat,
+                            min_filter: MinFilter::Linear,
+                            mag_filter: MagFilter::Linear,
+                            mip_detail: MipDetail::OneMipLevelAnisotropicOff,
+                        }],
+                        properties: vec![NudProperty {
+                            name: "NU_colorGain".to_string(),
+                            values: vec![1.0; 4],
+                        }],
+                    }),
+                    material2: None,
+                    material3: None,
+                    material4: None,
+                }],
+                sort_bias: 0.0,
+                bounding_sphere: Vec4::ZERO,
+                parent_bone_index: None,
+            }],
+            textures: Vec::new(),
+            bounding_sphere: Vec4::ZERO,
+            skeleton: Some(VbnSkeleton {
+                bones: vec![VbnBone {
+                    name: "a".to_string(),
+                    hash: 0,
+                    parent_bone_index: None,
+                    bone_type: BoneType::Normal,
+                    translation: Vec3::ZERO,
+                    rotation: Vec3::ZERO,
+                    scale: Vec3::ONE,
+                }],
+            }),
+        }
+    }
+
+    #[test]
+    fn validate_valid_model() {
+        assert!(model().validate(None).is_empty());
+    }
+
+    #[test]
+    fn validate_bone_indices_weights() {
+        let mut model = model();
+        let bones = model.groups[0].meshes[0].vertices.bones.as_mut().unwrap();
+        bones.bone_indices[1] = [0, 3, 0, 0];
+        bones.weights[1] = vec4(0.5, 0.5, 0.0, 0.0);
+        bones.weights[2] = vec4(0.5, 0.0, 0.0, 0.0);
+        // Unused influences should not be reported.
+        bones.bone_indices[0] = [0, 5, 0, 0];
+
+        assert_eq!(
+            vec![
+                Diagnostic::BoneIndexOutOfRange {
+                    group_index: 0,
+                    mesh_index: 0,
+                    vertex_index: 1,
+                    bone_index: 3,
+                    bone_count: 1,
+                    count: 1
+                },
+                Diagnostic::WeightSum {
+                    group_index: 0,
+                    mesh_index: 0,
+                    vertex_index: 2,
+                    sum: 0.5,
+                    count: 1
+                }
+            ],
+            model.validate(None)
+        );
+    }
+
+    #[test]
+    fn validate_attribute_lengths_nan() {
+        let mut model = model();
+        let vertices = &mut model.groups[0].meshes[0].vertices;
+        vertices.positions[2].x = f32::NAN;
+        vertices.bones.as_mut().unwrap().weights.pop();
+
+        assert_eq!(
+            vec![
+                Diagnostic::NonFiniteVertex {
+                    group_index: 0,
+                    mesh_index: 0,
+                    attribute: VertexAttribute::Position,
+                    vertex_index: 2,
+                    count: 1
+                },
+                Diagnostic::VertexCount {
+                    group_index: 0,
+                    mesh_index: 0,
+                    error: VertexCountError::Attribute {
+                        attribute: "bone weights",
+                        count: 2,
+                        vertex_count: 3
+                    }
+                }
+            ],
+            model.validate(None)
+        );
+    }
+
+    #[test]
+    fn validate_parent_bone_skinning_missing_texture() {
+        let mut model = model();
+        model.groups[0].parent_bone_index = Some(0);
+        let material = model.groups[0].meshes[0].material1.as_mut().unwrap();
+        material.textures[0].hash = 0x40001000;
+        material.properties[0].values[0] = f32::INFINITY;
+
+        assert_eq!(
+            vec![
+                Diagnostic::ParentBoneWithSkinning { group_index: 0 },
+                Diagnostic::MissingTexture {
+                    group_index: 0,
+                    mesh_index: 0,
+                    material_index: 0,
+                    texture_index: 0,
+                    hash: 0x40001000
+                },
+                Diagnostic::NonFiniteProperty {
+                    group_index: 0,
+                    mesh_index: 0,
+                    material_index: 0,
+                    name: "NU_colorGain".to_string()
+                }
+            ],
+            model.validate(None)
+        );
+    }
+
+    #[test]
+    fn validate_missing_skeleton() {
+        let mut model = model();
+        model.skeleton = None;
+
+        assert_eq!(
+            vec![Diagnostic::MissingSkeleton { group_index: 0 }],
+            model.validate(None)
+        );
+    }
+
+    #[test]
+    fn validate_shader_database() {
+        let model = model();
+        let database = ShaderDatabase::from_programs(Default::default());
+
+        assert_eq!(
+            vec![Diagnostic::MissingShader {
+                group_index: 0,
+                mesh_index: 0,
+                material_index: 0,
+                shader_id: 0
+            }],
+            model.validate(Some(&database))
+        );
+    }
+
+    #[test]
+    fn validate_property_errors() {
+        let mut model = model();
+        let material = model.groups[0].meshes[0].material1.as_mut().unwrap();
+        material.properties[0].values.pop();
+
+        assert_eq!(
+            vec![Diagnostic::Property {
+                group_index: 0,
+                mesh_index: 0,
+                material_index: 0,
+                error: PropertyError::ValueCount {
+                    name: "NU_colorGain".to_string(),
+                    expected: 4,
+                    actual: 3
+                }
+            }],
+            model.validate(None)
+        );
+    }
+}
@@ -1,9 +1,10 @@
-use std::io::Cursor;
+use std::{collections::BTreeMap, io::Cursor};
 
 use bilge::prelude::*;
 use binrw::{BinRead, BinReaderExt, BinResult, BinWrite, BinWriterExt, VecArgs};
 use glam::{Vec2, Vec3, Vec4, vec2};
 use half::f16;
+use thiserror::Error;
 
 use sm4sh_lib::nud::{BoneType, ColorType, NormalType, UvType, VertexFlags};
 
@@ -19,7 +20,157 @@ pub struct Vertices {
     pub uvs: Uvs,
 }
 
+/// An error for vertex attributes or vertex indices that don't match the number of vertices.
+#[derive(Debug, PartialEq, Eq, Clone, Error)]
+pub enum VertexCountError {
+    #[error("{attribute} has {count} values, but there are {vertex_count} positions")]
+    Attribute {
+        attribute: &'static str,
+        count: usize,
+        vertex_count: usize,
+    },
+
+    #[error("vertex index {index} is out of range for {vertex_count} vertices")]
+    Index { index: u16, vertex_count: usize },
+}
+
 impl Vertices {
+    /// The number of vertices if all attributes have a value for each position.
+    pub fn vertex_count(&self) -> Result<usize, VertexCountError> {
+        let vertex_count = self.positions.len();
+        let check = |attribute, count| {
+            if count == vertex_count {
+                Ok(())
+            } else {
+                Err(VertexCountError::Attribute {
+                    attribute,
+                    count,
+                    vertex_count,
+                })
+            }
+        };
+
+        let normal_count = match &self.normals {
+            Normals::None(items) => items.len(),
+            Normals::NormalsFloat32(items) => items.len(),
+            Normals::NormalsTangentBitangentFloat32(items) => items.len(),
+            Normals::NormalsFloat16(items) => items.len(),
+            Normals::NormalsTangentBitangentFloat16(items) => items.len(),
+        };
+        check("normals", normal_count)?;
+
+        if let Some(bones) = &self.bones {
+            check("bone indices", bones.bone_indices.len())?;
+            check("bone weights", bones.weights.len())?;
+        }
+
+        if let Some(colors) = &self.colors {
+            check("colors", colors.colors.len())?;
+        }
+
+        match &self.uvs {
+            Uvs::Float16(items) => items.iter().try_for_each(|uvs| check("uvs", uvs.len()))?,
+            Uvs::Float32(items) => items.iter().try_for_each(|uvs| check("uvs", uvs.len()))?,
+        }
+
+        Ok(vertex_count)
+    }
+
+    /// The bits of all attribute values for the vertex at `index` for finding identical vertices.
+    ///
+    /// This assumes [Self::vertex_count] succeeded and `index` is in range.
+    pub(crate) fn vertex_bits(&self, index: usize) -> Vec<u32> {
+        let f16_bits = |v: &[f16]| v.iter().map(|f| f.to_bits() as u32).collect::<Vec<_>>();
+        let f32_bits = |v: &[f32]| v.iter().map(|f| f.to_bits()).collect::<Vec<_>>();
+
+        let mut bits = f32_bits(&self.positions[index].to_array());
+
+        match &self.normals {
+            Normals::None(items) => bits.extend(f32_bits(&items[index..index + 1])),
+            Normals::NormalsFloat32(items) => {
+                bits.push(items[index].unk1.to_bits());
+                bits.extend(f32_bits(&items[index].normal));
+            }
+            Normals::NormalsTangentBitangentFloat32(items) => {
+                bits.push(items[index].unk1.to_bits());
+                bits.extend(f32_bits(&items[index].normal));
+                bits.extend(f32_bits(&items[index].bitangent));
+                bits.extend(f32_bits(&items[index].tangent));
+            }
+            Normals::NormalsFloat16(items) => bits.extend(f16_bits(&items[index].normal)),
+            Normals::NormalsTangentBitangentFloat16(items) => {
+                bits.extend(f16_bits(&items[index].normal));
+                bits.extend(f16_bits(&items[index].bitangent));
+                bits.extend(f16_bits(&items[index].tangent));
+            }
+        }
+
+        if let Some(bones) = &self.bones {
+            bits.extend(bones.bone_indices[index]);
+            bits.extend(f32_bits(&bones.weights[index].to_array()));
+        }
+
+        if let Some(colors) = &self.colors {
+            bits.extend(f32_bits(&colors.colors[index].to_array()));
+        }
+
+        match &self.uvs {
+            Uvs::Float16(items) => {
+                for uvs in items {
+                    bits.extend(f16_bits(&[uvs[index].u, uvs[index].v]));
+                }
+            }
+            Uvs::Float32(items) => {
+                for uvs in items {
+                    bits.extend(f32_bits(&[uvs[index].u, uvs[index].v]));
+                }
+            }
+        }
+
+        bits
+    }
+
+    /// Create new vertices from the vertex at each of the given `indices`.
+    ///
+    /// This assumes [Self::vertex_count] succeeded and all `indices` are in range.
+    pub(crate) fn select(&self, indices: &[usize]) -> Self {
+        fn select<T: Clone>(items: &[T], indices: &[usize]) -> Vec<T> {
+            indices.iter().map(|i| items[*i].clone()).collect()
+        }
+
+        Self {
+            positions: select(&self.positions, indices),
+            normals: match &self.normals {
+                Normals::None(items) => Normals::None(select(items, indices)),
+                Normals::NormalsFloat32(items) => Normals::NormalsFloat32(select(items, indices)),
+                Normals::NormalsTangentBitangentFloat32(items) => {
+                    Normals::NormalsTangentBitangentFloat32(select(items, indices))
+                }
+                Normals::NormalsFloat16(items) => Normals::NormalsFloat16(select(items, indices)),
+                Normals::NormalsTangentBitangentFloat16(items) => {
+                    Normals::NormalsTangentBitangentFloat16(select(items, indices))
+                }
+            },
+            bones: self.bones.as_ref().map(|b| Bones {
+                bone_indices: select(&b.bone_indices, indices),
+                weights: select(&b.weights, indices),
+                element_type: b.element_type,
+            }),
+            colors: self.colors.as_ref().map(|c| Colors {
+                colors: select(&c.colors, indices),
+                element_type: c.element_type,
+            }),
+            uvs: match &self.uvs {
+                Uvs::Float16(items) => {
+                    Uvs::Float16(items.iter().map(|uvs| select(uvs, indices)).collect())
+                }
+                Uvs::Float32(items) => {
+                    Uvs::Float32(items.iter().map(|uvs| select(uvs, indices)).collect())
+                }
+            },
+        }
+    }
+
     fn color_type(&self) -> ColorType {
         match self.colors.as_ref().map(|c| c.element_type) {
             None => ColorType::None,
@@ -85,6 +236,138 @@ impl Normals {
         }
     }
 
+    /// Replace the XYZ components of the normals while preserving any other values.
+    /// Zero vectors in `normals` leave the existing normal unchanged.
+    pub fn with_normals(&self, normals: &[Vec3]) -> Self {
+        let set_normal = |normal: [f32; 4], n: &Vec3| {
+            if *n == Vec3::ZERO {
+                normal
+            } else {
+                n.extend(normal[3]).to_array()
+            }
+        };
+        let set_normal_f16 = |normal: [f16; 4], n: &Vec3| {
+            if *n == Vec3::ZERO {
+                normal
+            } else {
+                let [x, y, z] = n.to_array().map(f16::from_f32);
+                [x, y, z, normal[3]]
+            }
+        };
+
+        let mut new_normals = self.clone();
+        match &mut new_normals {
+            Normals::None(_) => (),
+            Normals::NormalsFloat32(items) => {
+                for (i, n) in items.iter_mut().zip(normals) {
+                    i.normal = set_normal(i.normal, n);
+                }
+            }
+            Normals::NormalsTangentBitangentFloat32(items) => {
+                for (i, n) in items.iter_mut().zip(normals) {
+                    i.normal = set_normal(i.normal, n);
+                }
+            }
+            Normals::NormalsFloat16(items) => {
+                for (i, n) in items.iter_mut().zip(normals) {
+                    i.normal = set_normal_f16(i.normal, n);
+                }
+            }
+            Normals::NormalsTangentBitangentFloat16(items) => {
+                for (i, n) in items.iter_mut().zip(normals) {
+                    i.normal = set_normal_f16(i.normal, n);
+                }
+            }
+        }
+        new_normals
+    }
+
+    /// Replace the tangents and bitangents or convert to the variant with tangents and bitangents.
+    /// This preserves the precision of the existing normals.
+    /// [Normals::None] does not have normals and is returned unchanged.
+    ///
+    /// Returns an error if `tangents` or `bitangents` do not have a value for each normal.
+    pub fn with_tangents_bitangents(
+        &self,
+        tangents: &[Vec4],
+        bitangents: &[Vec4],
+    ) -> Result<Self, VertexCountError> {
+        let vertex_count = match self {
+            Normals::None(items) => return Ok(Normals::None(items.clone())),
+            Normals::NormalsFloat32(items) => items.len(),
+            Normals::NormalsTangentBitangentFloat32(items) => items.len(),
+            Normals::NormalsFloat16(items) => items.len(),
+            Normals::NormalsTangentBitangentFloat16(items) => items.len(),
+        };
+        for (attribute, count) in [
+            ("tangents", tangents.len()),
+            ("bitangents", bitangents.len()),
+        ] {
+            if count != vertex_count {
+                return Err(VertexCountError::Attribute {
+                    attribute,
+                    count,
+                    vertex_count,
+                });
+            }
+        }
+
+        let to_f16 = |v: Vec4| v.to_array().map(f16::from_f32);
+        Ok(match self {
+            Normals::None(items) => Normals::None(items.clone()),
+            Normals::NormalsFloat32(items) => Normals::NormalsTangentBitangentFloat32(
+                items
+                    .iter()
+                    .zip(tangents.iter().zip(bitangents))
+                    .map(|(i, (t, b))| NormalsTangentBitangentFloat32 {
+                        unk1: i.unk1,
+                        normal: i.normal,
+                        bitangent: b.to_array(),
+                        tangent: t.to_array(),
+                    })
+                    .collect(),
+            ),
+            Normals::NormalsTangentBitangentFloat32(items) => {
+                Normals::NormalsTangentBitangentFloat32(
+                    items
+                        .iter()
+                        .zip(tangents.iter().zip(bitangents))
+                        .map(|(i, (t, b))| NormalsTangentBitangentFloat32 {
+                            unk1: i.unk1,
+                            normal: i.normal,
+                            bitangent: b.to_array(),
+                            tangent: t.to_array(),
+                        })
+                        .collect(),
+                )
+            }
+            Normals::NormalsFloat16(items) => Normals::NormalsTangentBitangentFloat16(
+                items
+                    .iter()
+                    .zip(tangents.iter().zip(bitangents))
+                    .map(|(i, (t, b))| NormalsTangentBitangentFloat16 {
+                        normal: i.normal,
+                        bitangent: to_f16(*b),
+                        tangent: to_f16(*t),
+                    })
+                    .collect(),
+            ),
+            Normals::NormalsTangentBitangentFloat16(items) => {
+                Normals::NormalsTangentBitangentFloat16(
+                    items
+                        .iter()
+                        .zip(tangents.iter().zip(bitangents))
+                        .map(|(i, (t, b))| NormalsTangentBitangentFloat16 {
+                            normal: i.normal,
+                            bitangent: to_f16(*b),
+                            tangent: to_f16(*t),
+                        })
+                        .collect(),
+                )
+            }
+        })
+    }
+
     // TODO: "constructor" for each variant using attribute arrays?
     // TODO: Just redo the variants to work like this instead?
     // structs <-> attribute arrays
@@ -793,6 +1076,92 @@ pub fn triangle_strip_to_list(indices: &[u16]) -> Vec<u16> {
     new_indices
 }
 
+/// How to join separate strips in [triangle_list_to_strip].
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum StripJoin {
+    /// Separate strips with a primitive restart index of `0xFFFF`.
+    #[default]
+    PrimitiveRestart,
+    /// Connect strips by repeating indices to create zero area triangles.
+    DegenerateTriangles,
+}
+
+/// Convert triangle list `indices` to triangle strip indices
+/// that produce the same triangles with the same winding order using [triangle_strip_to_list].
+///
+/// Triangles with repeated indices are removed since they have zero area.
+pub fn triangle_list_to_strip(indices: &[u16], join: StripJoin) -> Vec<u16> {
+    let triangles: Vec<[u16; 3]> = indices
+        .chunks_exact(3)
+        .map(|t| [t[0], t[1], t[2]])
+        .filter(|[a, b, c]| a != b && b != c && a != c)
+        .collect();
+
+    // Map each directed edge to the triangles containing that edge in their winding order.
+    let mut edge_triangles: BTreeMap<(u16, u16), Vec<usize>> = BTreeMap::new();
+    for (i, [a, b, c]) in triangles.iter().enumerate() {
+        for edge in [(*a, *b), (*b, *c), (*c, *a)] {
+            edge_triangles.entry(edge).or_default().push(i);
+        }
+    }
+
+    let mut is_used = vec![false; triangles.len()];
+    let mut strips = Vec::new();
+    for start in 0..triangles.len() {
+        if is_used[start] {
+            continue;
+        }
+        is_used[start] = true;
+
+        // Greedily extend the strip using adjacent triangles.
+        let mut strip = triangles[start].to_vec();
+        loop {
+            let n = strip.len();
+            // Strip indices 0 1 2 3 4 generate triangles (0 1 2) (2 1 3) (2 3 4).
+            let edge = if (n - 2) % 2 == 0 {
+                (strip[n - 2], strip[n - 1])
+            } else {
+                (strip[n - 1], strip[n - 2])
+            };
+
+            let next = edge_triangles
+                .get(&edge)
+                .and_then(|ts| ts.iter().copied().find(|t| !is_used[*t]));
+            match next {
+                Some(t) => {
+                    is_used[t] = true;
+                    let third = triangles[t]
+                        .into_iter()
+                        .find(|i| *i != edge.0 && *i != edge.1)
+                        .unwrap();
+                    strip.push(third);
+                }
+                None => break,
+            }
+        }
+        strips.push(strip);
+    }
+
+    let mut new_indices: Vec<u16> = Vec::new();
+    for strip in strips {
+        if let Some(last) = new_indices.last().copied() {
+            match join {
+                StripJoin::PrimitiveRestart => new_indices.push(u16::MAX),
+                StripJoin::DegenerateTriangles => {
+                    // Each strip should start on an even triangle to preserve winding order.
+                    new_indices.push(last);
+                    if new_indices.len() % 2 == 0 {
+                        new_indices.push(last);
+                    }
+                    new_indices.push(strip[0]);
+                }
+            }
+        }
+        new_indices.extend(strip);
+    }
+    new_indices
+}
+
 // TODO: Attribute with buffer index, relative offset, data type?
 // flags -> attributes -> position, uv, color, normal, bone data?
 // TODO: Add tests for rebuilding vertex data
@@ -1048,4 +1417,99 @@ mod tests {
             triangle_strip_to_list(&[0, 1, 2, u16::MAX, 2, 3, 4, 5])
         );
     }
+
+    #[test]
+    fn normals_with_normals_preserve_values() {
+        let normals = Normals::NormalsFloat32(vec![
+            NormalsFloat32 {
+                unk1: 2.0,
+                normal: [1.0, 0.0, 0.0, 0.5],
+            },
+            NormalsFloat32 {
+                unk1: 3.0,
+                normal: [1.0, 0.0, 0.0, 1.0],
+            },
+        ]);
+        assert_eq!(
+            Normals::NormalsFloat32(vec![
+                NormalsFloat32 {
+                    unk1: 2.0,
+                    normal: [0.0, 1.0, 0.0, 0.5],
+                },
+                NormalsFloat32 {
+                    unk1: 3.0,
+                    normal: [1.0, 0.0, 0.0, 1.0],
+                },
+            ]),
+            normals.with_normals(&[vec3(0.0, 1.0, 0.0), Vec3::ZERO])
+        );
+    }
+
+    fn non_degenerate_triangles(indices: &[u16]) -> Vec<[u16; 3]> {
+        // Normalize the starting index while preserving winding order.
+        let mut triangles: Vec<_> = indices
+            .chunks_exact(3)
+            .filter(|t| t[0] != t[1] && t[1] != t[2] && t[0] != t[2])
+            .map(|t| {
+                let min = (0..3).min_by_key(|i| t[*i]).unwrap();
+                [t[min], t[(min + 1) % 3], t[(min + 2) % 3]]
+            })
+            .collect();
+        triangles.sort();
+        triangles
+    }
+
+    #[test]
+    fn triangle_list_to_strip_empty() {
+        assert!(triangle_list_to_strip(&[], StripJoin::PrimitiveRestart).is_empty());
+    }
+
+    #[test]
+    fn triangle_list_to_strip_quad() {
+        let list = [0, 1, 2, 2, 1, 3];
+        let strip = triangle_list_to_strip(&list, StripJoin::PrimitiveRestart);
+        assert_eq!(vec![0, 1, 2, 3], strip);
+        assert_eq!(list.to_vec(), triangle_strip_to_list(&strip));
+    }
+
+    #[test]
+    fn triangle_list_to_strip_restart() {
+        let list = [0, 1, 2, 3, 4, 5, 5, 4, 6];
+        let strip = triangle_list_to_strip(&list, StripJoin::PrimitiveRestart);
+        assert_eq!(vec![0, 1, 2, u16::MAX, 3, 4, 5, 6], strip);
+        assert_eq!(
+            non_degenerate_triangles(&list),
+            non_degenerate_triangles(&triangle_strip_to_list(&strip))
+        );
+    }
+
+    #[test]
+    fn triangle_list_to_strip_degenerate() {
+        let list = [0, 1, 2, 2, 1, 3, 4, 5, 6, 7, 8, 9, 9, 8, 10, 10, 8, 11];
+        let strip = triangle_list_to_strip(&list, StripJoin::DegenerateTriangles);
+        assert!(!strip.contains(&u16::MAX));
+        assert_eq!(
+            non_degenerate_triangles(&list),
+            non_degenerate_triangles(&triangle_strip_to_list(&strip))
+        );
+    }
+
+    #[test]
+    fn with_tangents_bitangents_count_mismatch() {
+        let normals = Normals::NormalsFloat32(vec![
+            NormalsFloat32 {
+                unk1: 1.0,
+                normal: [0.0, 0.0, 1.0, 1.0],
+            };
+            2
+        ]);
+        assert_eq!(
+            Err(VertexCountError::Attribute {
+                attribute: "bitangents",
+                count: 1,
+                vertex_count: 2
+            }),
+            normals.with_tangents_bitangents(&[Vec4::X; 2], &[Vec4::Y])
+        );
+    }
 }
@@ -85,6 +85,20 @@ enum Commands {
         /// The name of the variable to analyze.
         var: String,
     },
+    /// Convert a shader database to JSON for reviewing or editing.
+    DatabaseToJson {
+        /// The input database.
+        input: String,
+        /// The output JSON file.
+        output: String,
+    },
+    /// Convert JSON from the database-to-json command to a shader database.
+    JsonToDatabase {
+        /// The input JSON file.
+        input: String,
+        /// The output database.
+        output: String,
+    },
 }
 
 fn main() -> anyhow::Result<()> {
@@ -128,6 +142,14 @@ fn main() -> anyhow::Result<()> {
             let source_out = glsl_dependencies(&source, var, channels.chars().next());
             std::fs::write(output, source_out).unwrap();
         }
+        Commands::DatabaseToJson { input, output } => {
+            let database = ShaderDatabase::from_file(input)?;
+            std::fs::write(output, database.to_json()?)?;
+        }
+        Commands::JsonToDatabase { input, output } => {
+            let json = std::fs::read_to_string(input)?;
+            ShaderDatabase::from_json(&json)?.save(output)?;
+        }
     }
     println!("Finished in {:?}", start.elapsed());
     Ok(())
@@ -10,12 +10,16 @@ use sm4sh_lib::{
     nsh::Nsh,
     nud::Nud,
     nut::{Ntp3TextureV1, Ntp3TextureV2, Nut},
-    omo::Omo,
+    omo::{Omo, PositionType, RotationType},
     pack::Pack,
+    param::Param,
     sb::Sb,
     vbn::Vbn,
 };
-use sm4sh_model::{NudModel, animation::Animation};
+use sm4sh_model::{
+    NudModel, NutFormat, animation::Animation, bone_name_hash, globals::RenderGlobals,
+    nut_textures, texture::merge_nuts,
+};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -36,6 +40,9 @@ struct Cli {
     #[arg(long)]
     pack: bool,
 
+    #[arg(long)]
+    omo: bool,
+
     #[arg(long)]
     mta: bool,
 
@@ -54,6 +61,14 @@ struct Cli {
     #[arg(long)]
     nud_model: bool,
 
+    /// Check stage lighting from light_set_param.bin files.
+    #[arg(long)]
+    light_set_param: bool,
+
+    /// Save B5G6R5 textures from Nut files to this folder as PNG to check the channel order.
+    #[arg(long)]
+    b5g6r5_png: Option<String>,
+
     /// Process all file types.
     #[arg(long)]
     all: bool,
@@ -85,6 +100,11 @@ fn main() {
         check_all(root, &["*.pac"], check_pack);
     }
 
+    if cli.omo || cli.all {
+        println!("Checking Omo files...");
+        check_all(root, &["*.omo"], check_omo);
+    }
+
     if cli.mta || cli.all {
         println!("Checking Mta files...");
         check_all(root, &["*.mta"], check_mta);
@@ -115,6 +135,19 @@ fn main() {
         check_all(root, &["*.nud"], check_nud_model);
     }
 
+    if cli.light_set_param || cli.all {
+        println!("Checking light_set_param.bin files...");
+        check_all(root, &["light_set_param.bin"], check_light_set_param);
+    }
+
+    if let Some(output) = &cli.b5g6r5_png {
+        println!("Saving B5G6R5 Nut textures...");
+        let output = Path::new(output);
+        check_all(root, &["*.nut"], |nut: Nut, path, _| {
+            save_b5g6r5_textures(nut, path, output)
+        });
+    }
+
     println!("Finished in {:?}", start.elapsed());
 }
 
@@ -158,6 +191,19 @@ fn check_nud_model(nud: Nud, path: &Path, _original_bytes: &[u8]) {
     let vbn = Vbn::from_file(path.with_file_name("model.vbn")).ok();
     match NudModel::from_nud(&nud, nut.as_ref(), vbn.as_ref()) {
         Ok(model) => {
+            // Report properties that don't match the material property registry.
+            let materials = model
+                .groups
+                .iter()
+                .flat_map(|g| &g.meshes)
+                .flat_map(|m| [&m.material1, &m.material2, &m.material3, &m.material4])
+                .flatten();
+            for material in materials {
+                for error in material.property_errors() {
+                    println!("Material {:X}: {error} for {path:?}", material.shader_id);
+                }
+            }
+
             // Check nud model conversions.
             let new_nud = model.to_nud().unwrap();
 
@@ -206,9 +252,18 @@ fn check_nut(nut: Nut, path: &Path, original_bytes: &[u8]) {
         println!("Nut read/write not 1:1 for {path:?}");
     }
 
+    // All textures should load and rebuild including formats not supported by image_dds.
+    if let Err(e) = merge_nuts(std::slice::from_ref(&nut)) {
+        println!("Error converting Nut textures for {path:?}: {e}");
+    }
+
     match nut {
         Nut::Ntwu(ntwu) => {
-            for texture in ntwu.textures {
+            for texture in ntwu
+                .textures
+                .iter()
+                .filter(|t| has_surface_format(t.header.format))
+            {
                 let _surface = texture.to_surface().unwrap();
                 // TODO: support swizzling at some point
             }
@@ -217,11 +272,15 @@ fn check_nut(nut: Nut, path: &Path, original_bytes: &[u8]) {
             // NTP3 nuts aren't tiled and should rebuild 1:1.
             match ntp3.inner {
                 sm4sh_lib::nut::Ntp3Inner::V1(v1) => {
-                    for texture in v1.textures {
+                    for texture in v1
+                        .textures
+                        .iter()
+                        .filter(|t| has_surface_format(t.header.format))
+                    {
                         let surface = texture.to_surface().unwrap();
                         let new_texture =
                             Ntp3TextureV1::from_surface(surface, texture.gidx.hash).unwrap();
-                        if new_texture != texture {
+                        if &new_texture != texture {
                             println!(
                                 "NTP3 V1 texture {:08X} from surface not 1:1 for {path:?}",
                                 texture.gidx.hash
@@ -230,11 +289,15 @@ fn check_nut(nut: Nut, path: &Path, original_bytes: &[u8]) {
                     }
                 }
                 sm4sh_lib::nut::Ntp3Inner::V2(v2) => {
-                    for texture in v2.textures {
+                    for texture in v2
+                        .textures
+                        .iter()
+                        .filter(|t| has_surface_format(t.header.format))
+                    {
                         let surface = texture.to_surface().unwrap();
                         let new_texture =
                             Ntp3TextureV2::from_surface(surface, texture.gidx.hash).unwrap();
-                        if new_texture != texture {
+                        if &new_texture != texture {
                             println!(
                                 "NTP3 V2 texture {:08X} from surface not 1:1 for {path:?}",
                                 texture.gidx.hash
@@ -249,10 +312,86 @@ fn check_nut(nut: Nut, path: &Path, original_bytes: &[u8]) {
     }
 }
 
+fn has_surface_format(format: NutFormat) -> bool {
+    // image_dds doesn't support B5G6R5, so these textures are only checked after loading.
+    format != NutFormat::B5G6R5Unorm
+}
+
+fn save_b5g6r5_textures(nut: Nut, path: &Path, output: &Path) {
+    // Compare the saved images with other tools like Smash Forge to check the channel order.
+    let is_b5g6r5 = |format| format == NutFormat::B5G6R5Unorm;
+    let hashes: Vec<_> = match &nut {
+        Nut::Ntwu(ntwu) => ntwu
+            .textures
+            .iter()
+            .filter(|t| is_b5g6r5(t.header.format))
+            .map(|t| t.gidx.hash)
+            .collect(),
+        Nut::Ntp3(ntp3) => match &ntp3.inner {
+            sm4sh_lib::nut::Ntp3Inner::V1(v1) => v1
+                .textures
+                .iter()
+                .filter(|t| is_b5g6r5(t.header.format))
+                .map(|t| t.gidx.hash)
+                .collect(),
+            sm4sh_lib::nut::Ntp3Inner::V2(v2) => v2
+                .textures
+                .iter()
+                .filter(|t| is_b5g6r5(t.header.format))
+                .map(|t| t.gidx.hash)
+                .collect(),
+        },
+    };
+    if hashes.is_empty() {
+        return;
+    }
+
+    match nut_textures(&nut) {
+        Ok(textures) => {
+            for texture in textures.iter().filter(|t| hashes.contains(&t.hash_id)) {
+                let name = format!("{:08X}.png", texture.hash_id);
+                match texture.to_image(0) {
+                    Ok(image) => {
+                        if let Err(e) = image.save(output.join(name)) {
+                            println!("Error saving {:08X} for {path:?}: {e}", texture.hash_id);
+                        }
+                    }
+                    Err(e) => println!("Error decoding {:08X} for {path:?}: {e}", texture.hash_id),
+                }
+            }
+        }
+        Err(e) => println!("Error loading Nut textures for {path:?}: {e}"),
+    }
+}
+
 fn check_vbn(vbn: Vbn, path: &Path, original_bytes: &[u8]) {
     if !write_le_bytes_equals(&vbn, original_bytes) {
         println!("Vbn read/write not 1:1 for {path:?}");
     }
+
+    let bones = match &vbn {
+        Vbn::Le(vbn) => &vbn.bones,
+        Vbn::Be(vbn) => &vbn.bones,
+    };
+
+    // Check the assumed hash algorithm for new bones.
+    for bone in bones {
+        let hash = bone_name_hash(&bone.name);
+        if bone.bone_id != hash {
+            println!(
+                "Bone {:?} has ID {:08X} instead of name hash {hash:08X} for {path:?}",
+                bone.name, bone.bone_id
+            );
+        }
+    }
+
+    // Check the assumed sorting by bone type.
+    if bones
+        .windows(2)
+        .any(|b| (b[1].bone_type as usize) < (b[0].bone_type as usize))
+    {
+        println!("Vbn bones not sorted by type for {path:?}");
+    }
 }
 
 fn check_pack(pack: Pack, path: &Path, original_bytes: &[u8]) {
@@ -284,8 +423,104 @@ fn check_omo(omo: Omo, path: &Path, original_bytes: &[u8]) {
         println!("Omo read/write not 1:1 for {path:?}");
     }
 
-    if let Err(e) = Animation::from_omo(&omo) {
-        println!("Error loading animation for {path:?}: {e}")
+    match Animation::from_omo(&omo) {
+        Ok(animation) => check_omo_nodes(&omo, &animation, path),
+        Err(e) => println!("Error loading animation for {path:?}: {e}"),
+    }
+}
+
+fn check_omo_nodes(omo: &Omo, animation: &Animation, path: &Path) {
+    // Report any flags or values that may not be decoded correctly.
+    for node in &omo.nodes {
+        let unk1 = node.flags.unk1().value();
+        let unk4 = node.flags.unk4().value();
+        if unk1 != 0 || unk4 != 0 {
+            println!(
+                "Omo node {:08X} has unknown flags unk1: {unk1:X}, unk4: {unk4:X} for {path:?}",
+                node.hash
+            );
+        }
+
+        let Some(anim_node) = animation.nodes.iter().find(|n| n.hash == node.hash) else {
+            println!("Omo node {:08X} not decoded for {path:?}", node.hash);
+            continue;
+        };
+
+        // Frame tracks store raw floats, so invalid values indicate the wrong key layout.
+        if node.flags.position() && node.flags.position_type() == PositionType::Frame {
+            let invalid = anim_node
+                .translation_keyframes
+                .iter()
+                .enumerate()
+                .find(|(_, t)| !t.is_some_and(|t| t.is_finite()));
+            if let Some((frame, t)) = invalid {
+                println!(
+                    "Omo node {:08X} has invalid frame translation {t:?} at frame {frame} for {path:?}",
+                    node.hash
+                );
+            }
+        }
+
+        if node.flags.rotation() && node.flags.rotation_type() == RotationType::Frame {
+            let invalid = anim_node
+                .rotation_keyframes
+                .iter()
+                .enumerate()
+                .find(|(_, r)| !r.is_some_and(|r| r.is_finite() && r.is_normalized()));
+            if let Some((frame, r)) = invalid {
+                println!(
+                    "Omo node {:08X} has invalid frame rotation {r:?} at frame {frame} for {path:?}",
+                    node.hash
+                );
+            }
+        }
+    }
+}
+
+fn check_light_set_param(param: Param, path: &Path, original_bytes: &[u8]) {
+    let mut writer = Cursor::new(Vec::new());
+    param.write(&mut writer).unwrap();
+    if writer.into_inner() != original_bytes {
+        println!("Param read/write not 1:1 for {path:?}");
+    }
+
+    // Check the assumed layout of lights in group 1 and fog colors in group 2.
+    let light_set_count = RenderGlobals::light_set_count(&param);
+    if light_set_count == 0 {
+        println!("No light sets for {path:?}");
+    }
+
+    let fog_count = param.groups.get(2).map(|g| g.entries.len());
+    if fog_count != Some(light_set_count + 1) {
+        println!(
+            "Fog entries {fog_count:?} do not match {light_set_count} light sets for {path:?}"
+        );
+    }
+
+    if let Some(lights) = param.groups.get(1) {
+        for (i, light) in lights.entries.iter().enumerate() {
+            let enabled = light.first().and_then(|v| v.as_f32());
+            if enabled != Some(0.0) && enabled != Some(1.0) {
+                println!("Light {i} has unexpected enabled value {enabled:?} for {path:?}");
+            }
+
+            let hsv = light
+                .get(1..4)
+                .map(|v| v.iter().map(|v| v.as_f32()).collect::<Vec<_>>());
+            let valid_hsv = hsv.as_ref().is_some_and(|hsv| match hsv[..] {
+                [Some(h), Some(s), Some(v)] => (0.0..=360.0).contains(&h) && s >= 0.0 && v >= 0.0,
+                _ => false,
+            });
+            if !valid_hsv {
+                println!("Light {i} has unexpected HSV color {hsv:?} for {path:?}");
+            }
+        }
+    }
+
+    for light_set in 0..light_set_count {
+        if let Err(e) = RenderGlobals::from_light_set_lights_fog(&param, light_set) {
+            println!("Error loading light set {light_set} for {path:?}: {e}");
+        }
     }
 }
 
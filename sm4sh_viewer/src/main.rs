@@ -5,10 +5,12 @@ use clap::Parser;
 use futures::executor::block_on;
 use glam::{Vec3, Vec4, vec3};
 use log::info;
+use sm4sh_lib::param::Param;
 use sm4sh_model::{
     NudModel,
     animation::{Animation, load_animations},
     database::ShaderDatabase,
+    globals::RenderGlobals,
 };
 use sm4sh_wgpu::{CameraData, Model, Renderer, SharedData, load_model};
 use winit::{
@@ -99,7 +101,12 @@ impl State {
         };
         surface.configure(&device, &config);
 
-        let renderer = Renderer::new(&device, size.width, size.height, config.format);
+        let mut renderer = Renderer::new(&device, size.width, size.height, config.format);
+        if let Some(path) = &cli.light_set_param {
+            let param = Param::from_file(path)?;
+            let globals = RenderGlobals::from_light_set_lights_fog(&param, cli.light_set)?;
+            renderer.update_globals(&queue, &globals);
+        }
 
         let shared_data = SharedData::new(&device, &queue, database);
         let model = load_model(&device, &queue, nud_model, &shared_data);
@@ -341,6 +348,12 @@ struct Cli {
     database: String,
     /// The animation file
     anim: Option<String>,
+    /// The stage light_set_param.bin file for lighting
+    #[arg(long)]
+    light_set_param: Option<String>,
+    /// The index of the light set in the light_set_param.bin file
+    #[arg(long, default_value_t = 0)]
+    light_set: usize,
 }
 
 struct App {
@@ -32,8 +32,14 @@ fn main() {
         "src/shader/bone.wgsl",
         format!("{out_dir}/bone.rs"),
     );
+    // Use the same helper functions as the generated shader code.
+    println!("cargo:rerun-if-changed=../sm4sh_model/src/shadergen/functions.wgsl");
+    let model_source = include_str!("src/shader/model.wgsl").replace(
+        "// SHADERGEN_FUNCTIONS",
+        include_str!("../sm4sh_model/src/shadergen/functions.wgsl"),
+    );
     write_shader(
-        include_str!("src/shader/model.wgsl"),
+        &model_source,
         "src/shader/model.wgsl",
         format!("{out_dir}/model.rs"),
     );
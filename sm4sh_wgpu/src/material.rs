@@ -2,7 +2,7 @@ use std::collections::BTreeMap;
 
 use log::error;
 
-use glam::{Vec4, vec4};
+use glam::Vec4;
 use sm4sh_model::NudMesh;
 
 use crate::{DeviceBufferExt, SharedData};
@@ -240,17 +240,5 @@ fn address_mode(m: sm4sh_model::WrapMode) -> wgpu::AddressMode {
 }
 
 fn get_parameter(mesh: &sm4sh_model::NudMesh, name: &str) -> Option<Vec4> {
-    let material = mesh.material1.as_ref()?;
-    material.properties.iter().find_map(|p| {
-        if p.name == name {
-            Some(vec4(
-                *p.values.first()?,
-                *p.values.get(1)?,
-                *p.values.get(2)?,
-                *p.values.get(3)?,
-            ))
-        } else {
-            None
-        }
-    })
+    mesh.material1.as_ref()?.property_vec4(name)
 }
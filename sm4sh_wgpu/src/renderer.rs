@@ -1,4 +1,7 @@
-use glam::{Mat4, Vec4, vec4};
+use encase::ShaderSize;
+use glam::{Mat4, Vec4};
+use sm4sh_model::globals::{Fb0, Fb1, Fb3, Fb4, Fb5, RenderGlobals};
+use wgpu::util::DeviceExt;
 
 use crate::{CameraData, DeviceBufferExt, Model, QueueBufferExt, skeleton::BoneRenderer};
 
@@ -14,8 +17,10 @@ pub struct Renderer {
     model_bind_group0: crate::shader::model::bind_groups::BindGroup0,
     textures: Textures,
     bone_renderer: BoneRenderer,
+    globals: RenderGlobals,
     fb0_buffer: wgpu::Buffer,
     fb1_buffer: wgpu::Buffer,
+    fb3_buffer: wgpu::Buffer,
 
     bloom_bright_pipeline: wgpu::RenderPipeline,
     bloom_add_pipeline: wgpu::RenderPipeline,
@@ -50,28 +55,16 @@ impl Renderer {
         };
         let camera_buffer = device.create_uniform_buffer("camera buffer", &camera.to_shader_data());
 
-        // Default values for all buffers taken from Rosalina c00 on Miiverse stage.
-        let fb0_buffer = device.create_uniform_buffer("FB0", &fb0(width as f32, height as f32));
-        let fb1_buffer = device.create_uniform_buffer("FB1", &fb1());
-        let fb3_buffer = device.create_uniform_buffer(
-            "FB3",
-            &crate::shader::model::Fb3 {
-                hdr_range: vec4(0.5, 2.0, 0.0, 0.0),
-                colr_hdr_range: Vec4::ZERO,
-            },
-        );
-        let fb4_buffer = device.create_uniform_buffer(
-            "FB4",
-            &crate::shader::model::Fb4 {
-                effect_light_entry: [Vec4::ZERO; 4096],
-            },
-        );
-        let fb5_buffer = device.create_uniform_buffer(
-            "FB5",
-            &crate::shader::model::Fb5 {
-                effect_light_area: [Vec4::ZERO; 4096],
-            },
-        );
+        let globals = RenderGlobals {
+            width,
+            height,
+            ..Default::default()
+        };
+        let fb0_buffer = global_buffer(device, "FB0", &globals.fb0());
+        let fb1_buffer = global_buffer(device, "FB1", &globals.fb1());
+        let fb3_buffer = global_buffer(device, "FB3", &globals.fb3());
+        let fb4_buffer = global_buffer(device, "FB4", &globals.fb4());
+        let fb5_buffer = global_buffer(device, "FB5", &globals.fb5());
 
         let shadow_map = create_texture(device, 1024, 1024, "shadow map", SHADOW_FORMAT);
         let variance_shadow_map = create_texture(
@@ -136,8 +129,10 @@ impl Renderer {
             model_bind_group0,
             textures,
             bone_renderer,
+            globals,
             fb0_buffer,
             fb1_buffer,
+            fb3_buffer,
             blit_pipeline,
             bloom_add_pipeline,
             bloom_bright_pipeline,
@@ -393,10 +388,31 @@ impl Renderer {
         queue.write_uniform_data(&self.camera_buffer, &camera.to_shader_data());
     }
 
+    /// The global lighting, fog, and reflection values used for rendering.
+    pub fn globals(&self) -> &RenderGlobals {
+        &self.globals
+    }
+
+    /// Update the global uniform values used for rendering.
+    ///
+    /// The resolution is always the current render size set by [Self::new] or [Self::resize].
+    pub fn update_globals(&mut self, queue: &wgpu::Queue, globals: &RenderGlobals) {
+        self.globals = RenderGlobals {
+            width: self.globals.width,
+            height: self.globals.height,
+            ..globals.clone()
+        };
+        queue.write_buffer(&self.fb0_buffer, 0, bytemuck::bytes_of(&self.globals.fb0()));
+        queue.write_buffer(&self.fb1_buffer, 0, bytemuck::bytes_of(&self.globals.fb1()));
+        queue.write_buffer(&self.fb3_buffer, 0, bytemuck::bytes_of(&self.globals.fb3()));
+    }
+
     pub fn resize(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, width: u32, height: u32) {
         // Update each resource that depends on window size.
         self.textures = Textures::new(device, width, height);
-        queue.write_uniform_data(&self.fb0_buffer, &fb0(width as f32, height as f32));
+        self.globals.width = width;
+        self.globals.height = height;
+        queue.write_buffer(&self.fb0_buffer, 0, bytemuck::bytes_of(&self.globals.fb0()));
     }
 }
 
@@ -583,105 +599,30 @@ fn model_shadow_depth_pipeline(device: &wgpu::Device) -> wgpu::RenderPipeline {
     })
 }
 
-fn fb0(width: f32, height: f32) -> crate::shader::model::Fb0 {
-    crate::shader::model::Fb0 {
-        depth_of_field0: vec4(0.0, 0.0, 0.0, 0.0),
-        depth_of_field1: vec4(0.0, 0.0, 0.0, 0.0),
-        depth_of_field_tex_size: vec4(0.0, 0.0, 0.0, 0.0),
-        proj_inv_matrix: Mat4::IDENTITY, // TODO: Fill in this value
-        refraction_param: vec4(0.0, 0.0, 0.0, 0.0),
-        proj_to_view: vec4(0.47635, 0.26795, 256.00, 0.00),
-        view_to_proj: vec4(1.04964, -1.86603, 0.00391, 0.00),
-        gi_buffer_size: vec4(width / 4.0, height / 4.0, 4.0 / width, 4.0 / height),
-        weight0: vec4(0.14374, 0.1258, 0.09635, 0.06459),
-        weight1: vec4(0.03789, 0.01945, 0.00874, 0.00344),
-        random_vector: [Vec4::ZERO; 31], // TODO: Fill in these values
-        reflection_param: vec4(0.0075, 2.50, 0.25, 0.00),
-        sun_shaft_light_param0: [vec4(0.0, 0.0, 0.0, 0.0), vec4(0.0, 0.0, 0.0, 0.0)],
-        sun_shaft_light_param1: [vec4(0.0, 0.0, 0.0, 0.0), vec4(0.0, 0.0, 0.0, 0.0)],
-        sun_shaft_blur_param: [
-            vec4(0.0, 0.0, 0.0, 0.0),
-            vec4(0.0, 0.0, 0.0, 0.0),
-            vec4(0.0, 0.0, 0.0, 0.0),
-            vec4(0.0, 0.0, 0.0, 0.0),
-        ],
-        sun_shaft_composite_param: [vec4(0.0, 0.0, 0.0, 0.0), vec4(0.0, 0.0, 0.0, 0.0)],
-        glare_abstract_param: vec4(1.0, 4.0, 0.0, 0.0),
-        render_target_tex_size: vec4(1.0 / width, 1.0 / height, 2.0 / width, 2.0 / height),
-        glare_fog_param: [vec4(0.0, 0.0, 0.0, 0.0), vec4(0.0, 0.0, 0.0, 0.0)],
-        glare_simple_color: vec4(0.0, 0.0, 0.0, 0.0),
-        pad0_fb0: vec4(0.0, 0.0, 0.0, 0.0),
-        lens_flare_param: vec4(0.0, 0.0, 0.0, 0.0),
-        outline_param: vec4(0.25, 0.00, 0.00, 0.00),
-        post_reflection_color: vec4(0.50, 0.50, 0.50, 0.20),
-        multi_shadow_matrix: [Mat4::IDENTITY; 4], // TODO: fill in these values
-        shadow_map_matrix: Mat4::from_cols_array_2d(&[
-            [0.00814, 0.00, 0.00, 0.00],
-            [0.00, -0.00504, -0.01631, 0.00],
-            [0.00, 0.01385, -0.00594, 0.00],
-            [0.49189, 0.67917, 1.09728, 1.00],
-        ]), // TODO: fill in these values
-        view: Mat4::ZERO,                         // TODO: fill in these values
-        eye: vec4(40.0, 47.40689, 37.02085, 1.0), // TODO: fill in these values
-        constant_color: vec4(1.0, 1.0, 1.0, 1.0),
-        light_map_pos: vec4(0.0, 0.0, 0.0, 0.0),
-        reflection_gain: vec4(1.0, 1.0, 1.0, 1.0),
-        hdr_constant: vec4(0.5, 2.0, 1.0, 1.0),
-        _g_fresnel_color: vec4(1.0, 1.0, 1.0, 1.0),
-        effect_light_param0: vec4(0.1, 0.1, -15.0, 0.0),
-        effect_light_param1: vec4(30.0, 12.0, 29.0, 11.0),
-        bg_rot_inv: Mat4::IDENTITY,
-        reflection_color1: vec4(0.0, 0.0, 0.0, 0.0),
-        reflection_color2: vec4(0.0001, 0.0, 0.0, 0.0),
-        reflection_color3: vec4(0.315, 0.31792, 0.35, 1.0),
-        effect_light_param2: vec4(0.685, 0.68208, 0.65, 1.00),
-    }
-}
-
-fn fb1() -> crate::shader::model::Fb1 {
-    crate::shader::model::Fb1 {
-        light_map_matrix: Mat4::IDENTITY,
-        blink_color: vec4(1.0, 1.0, 1.0, 0.0),
-        g_constant_volume: vec4(1.0, 1.0, 1.0, 1.0),
-        g_constant_offset: vec4(0.0, 0.0, 0.0, 0.0),
-        uv_scroll_counter: vec4(0.35, 0.0, 0.0, 0.0), // TODO: changes over time?
-        spycloak_params: vec4(-100.0, 0.0, 0.0, 0.0),
-        compress_param: vec4(1.0, 0.0, 0.0, 0.0),
-        g_fresnel_color: vec4(1.0, 1.0, 1.0, 1.0),
-        depth_offset: vec4(0.0, 0.0, 0.0, 0.0),
-        outline_color: vec4(0.0, 0.0, 0.0, 1.0),
-        pad0_fb1: [
-            vec4(0.0, 0.0, 0.0, 0.0),
-            vec4(0.0, 0.0, 0.0, 0.0),
-            vec4(0.0, 0.0, 0.0, 0.0),
-        ],
-        light_map_color_gain: vec4(0.4875, 0.4875, 0.4875, 0.0),
-        light_map_color_offset: vec4(0.0, 0.0, 0.0, 0.0),
-        ceiling_dir: vec4(0.0, 1.0, 0.0, 0.0),
-        ceiling_color: vec4(0.15, 0.15, 0.15, 0.0),
-        ground_color: vec4(1.0, 1.0, 1.0, 0.0),
-        ambient_color: vec4(0.0, 0.0, 0.0, 0.0),
-        light_dir_color1: vec4(0.75, 0.75, 0.75, 0.0),
-        light_dir_color2: vec4(0.2, 0.2, 0.2, 1.0),
-        light_dir_color3: vec4(0.0, 0.0, 0.0, 0.0),
-        light_dir1: vec4(0.0, -0.84323, -0.53756, 0.0),
-        light_dir2: vec4(-0.87287, 0.43644, -0.21822, 0.0),
-        light_dir3: vec4(0.0, 0.0, 0.0, 0.0),
-        fog_color: vec4(1.0, 1.0, 1.0, 1.0),
-        g_fresnel_offset: vec4(0.0, 0.0, 0.0, 0.0),
-        shadow_map_param: vec4(0.001, 0.0, 0.0, 0.0),
-        char_shadow_color: vec4(0.315, 0.31792, 0.35, 1.0),
-        char_shadow_color2: vec4(0.685, 0.68208, 0.65, 1.0),
-        soft_lighting_params2: vec4(0.0, 0.0, 0.0, 1.0),
-        bg_shadow_color: vec4(0.81, 0.8175, 0.90, 1.0),
-        g_ibl_color_gain: vec4(1.0, 1.0, 1.0, 0.0),
-        g_ibl_color_offset: vec4(0.15, 0.15, 0.15, 0.0),
-        g_constant_min: Vec4::ZERO,
-        loupe_shadow_params: Vec4::ZERO,
-        soft_light_color_gain: Vec4::ZERO,
-        soft_light_color_offset: Vec4::ZERO,
-        character_color: Vec4::ZERO,
-    }
+// The global buffers use the repr(C) types from sm4sh_model directly.
+// The WGSL uniform layout matches since all fields are vec4 or mat4 aligned to 16 bytes.
+const _: () = assert!(
+    size_of::<Fb0>() == <crate::shader::model::Fb0 as ShaderSize>::SHADER_SIZE.get() as usize
+);
+const _: () = assert!(
+    size_of::<Fb1>() == <crate::shader::model::Fb1 as ShaderSize>::SHADER_SIZE.get() as usize
+);
+const _: () = assert!(
+    size_of::<Fb3>() == <crate::shader::model::Fb3 as ShaderSize>::SHADER_SIZE.get() as usize
+);
+const _: () = assert!(
+    size_of::<Fb4>() == <crate::shader::model::Fb4 as ShaderSize>::SHADER_SIZE.get() as usize
+);
+const _: () = assert!(
+    size_of::<Fb5>() == <crate::shader::model::Fb5 as ShaderSize>::SHADER_SIZE.get() as usize
+);
+
+fn global_buffer<T: bytemuck::Pod>(device: &wgpu::Device, label: &str, data: &T) -> wgpu::Buffer {
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some(label),
+        contents: bytemuck::bytes_of(data),
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    })
 }
 
 // Group resizable resources to avoid duplicating this logic.